@@ -1,5 +1,8 @@
+#[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::*;
+#[cfg(target_arch = "wasm32")]
 use web_sys::HtmlCanvasElement;
+#[cfg(target_arch = "wasm32")]
 use winit::{
     event::*,
     event_loop::{ControlFlow, EventLoop},
@@ -7,13 +10,18 @@ use winit::{
     window::{WindowAttributes, WindowBuilder},
 };
 
-mod window;
+pub mod window;
+#[cfg(target_arch = "wasm32")]
 mod ctx;
+#[cfg(target_arch = "wasm32")]
 mod gl;
-mod events;
+pub mod events;
+pub mod renderer;
 
+#[cfg(target_arch = "wasm32")]
 use ctx::*;
 
+#[cfg(target_arch = "wasm32")]
 #[wasm_bindgen(start)]
 pub async fn run() {
     console_log::init().unwrap();
@@ -25,6 +33,7 @@ pub async fn run() {
     let _ = wasm_bindgen_futures::spawn_local(run_event_loop(event_loop, context));
 }
 
+#[cfg(target_arch = "wasm32")]
 async fn run_event_loop(event_loop: EventLoop<()>, mut context: Context) {
     event_loop.set_control_flow(ControlFlow::Wait);
     event_loop.run(move |event, elwt | {
@@ -0,0 +1,392 @@
+use super::*;
+
+use std::{collections::HashMap, ops::Range};
+
+#[derive(Clone)]
+pub struct AnimationFrame {
+    pub sprite: Sprite,
+    pub duration: f32, // seconds
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlaybackMode {
+    Loop,
+    Once,     // stops on the last frame
+    PingPong, // plays forward then backward, repeating
+}
+
+#[derive(Clone, Debug)]
+pub struct AnimationTag {
+    pub frames: Range<usize>,
+    pub mode: PlaybackMode,
+    pub reverse: bool, // starts on the last frame and plays backward
+    pub repeat: u32,   // passes before stopping, 0 repeats forever; each ping-pong leg is a pass
+}
+
+impl AnimationTag {
+    pub fn new(frames: Range<usize>, mode: PlaybackMode) -> Self {
+        Self {
+            frames,
+            mode,
+            reverse: false,
+            repeat: 0,
+        }
+    }
+    pub fn reversed(self, reverse: bool) -> Self {
+        Self { reverse, ..self }
+    }
+    pub fn repeated(self, repeat: u32) -> Self {
+        Self { repeat, ..self }
+    }
+}
+
+// where playback is within a tag's frames, stepped over the frames' durations alone
+#[derive(Clone, Debug)]
+struct Playback {
+    tag: AnimationTag,
+    frame: usize,   // index into frames, always inside the tag's range
+    elapsed: f32,   // time spent on the current frame
+    reversed: bool, // the current direction
+    passes: u32,    // times the end of the range was reached
+    finished: bool,
+}
+
+impl Playback {
+    fn new(tag: AnimationTag) -> Self {
+        Self {
+            frame: if tag.reverse {
+                tag.frames.end - 1
+            } else {
+                tag.frames.start
+            },
+            reversed: tag.reverse,
+            tag,
+            elapsed: 0.0,
+            passes: 0,
+            finished: false,
+        }
+    }
+    // durations of every frame, indexed like the animation's frames
+    fn update(&mut self, durations: &[f32], dt: f32) {
+        let range_duration: f32 = durations[self.tag.frames.clone()].iter().sum();
+
+        // a range of zero-length frames would never leave the loop below
+        if self.finished || range_duration <= 0.0 {
+            return;
+        }
+
+        self.elapsed += dt;
+
+        while !self.finished && self.elapsed >= durations[self.frame] {
+            self.elapsed -= durations[self.frame];
+            self.advance();
+        }
+    }
+    fn advance(&mut self) {
+        let first = self.tag.frames.start;
+        let last = self.tag.frames.end - 1;
+        let end = if self.reversed { first } else { last };
+
+        if self.frame != end {
+            self.frame = if self.reversed {
+                self.frame - 1
+            } else {
+                self.frame + 1
+            };
+            return;
+        }
+
+        self.passes += 1;
+        let repeat = match self.tag.mode {
+            PlaybackMode::Once => 1,
+            _ => self.tag.repeat,
+        };
+        if repeat != 0 && self.passes >= repeat {
+            self.finished = true;
+            self.elapsed = 0.0;
+            return;
+        }
+
+        match self.tag.mode {
+            PlaybackMode::Loop | PlaybackMode::Once => {
+                self.frame = if self.reversed { last } else { first };
+            }
+            PlaybackMode::PingPong => {
+                // turns on the end frame without showing it twice
+                self.reversed = !self.reversed;
+                if first != last {
+                    self.frame = if self.reversed {
+                        self.frame - 1
+                    } else {
+                        self.frame + 1
+                    };
+                }
+            }
+        }
+    }
+}
+
+pub struct AnimatedSprite {
+    frames: Vec<AnimationFrame>,
+    durations: Vec<f32>,
+    tags: HashMap<String, AnimationTag>,
+    current_tag: Option<String>,
+    playback: Playback,
+    pub speed: f32,
+}
+
+impl AnimatedSprite {
+    // frames must not be empty
+    pub fn new(frames: Vec<AnimationFrame>, mode: PlaybackMode) -> Self {
+        assert!(
+            !frames.is_empty(),
+            "AnimatedSprite needs at least one frame."
+        );
+
+        let durations = frames.iter().map(|frame| frame.duration).collect();
+        let playback = Playback::new(AnimationTag::new(0..frames.len(), mode));
+
+        Self {
+            frames,
+            durations,
+            tags: HashMap::new(),
+            current_tag: None,
+            playback,
+            speed: 1.0,
+        }
+    }
+    pub fn from_texture_areas(
+        texture: Rc<Texture>,
+        texture_areas: &[(SpriteTextureArea, f32)],
+        mode: PlaybackMode,
+    ) -> Self {
+        let frames = texture_areas
+            .iter()
+            .map(|&(texture_area, duration)| AnimationFrame {
+                sprite: Sprite::create(Rc::clone(&texture), Some(texture_area)),
+                duration,
+            })
+            .collect();
+
+        Self::new(frames, mode)
+    }
+    // frames laid out left to right, top to bottom in cells of frame_dimensions
+    pub fn from_grid(
+        texture: Rc<Texture>,
+        frame_dimensions: PixelDimensions,
+        frame_count: usize,
+        frame_duration: f32,
+        mode: PlaybackMode,
+    ) -> Self {
+        assert!(
+            frame_dimensions.width > 0 && frame_dimensions.height > 0,
+            "Animation grid cells need a width and a height."
+        );
+
+        let columns = (texture.dimensions.width / frame_dimensions.width).max(1) as usize;
+
+        let texture_areas: Vec<(SpriteTextureArea, f32)> = (0..frame_count)
+            .map(|index| {
+                let column = (index % columns) as u32;
+                let row = (index / columns) as u32;
+                let texture_area = SpriteTextureArea {
+                    coordinates: PixelCoordinates {
+                        x: (column * frame_dimensions.width) as i32,
+                        y: (row * frame_dimensions.height) as i32,
+                    },
+                    dimensions: frame_dimensions,
+                };
+                (texture_area, frame_duration)
+            })
+            .collect();
+
+        Self::from_texture_areas(texture, &texture_areas, mode)
+    }
+    pub fn add_tag(&mut self, name: &str, frames: Range<usize>, mode: PlaybackMode) {
+        self.insert_tag(name, AnimationTag::new(frames, mode));
+    }
+    pub fn insert_tag(&mut self, name: &str, tag: AnimationTag) {
+        assert!(
+            tag.frames.start < tag.frames.end && tag.frames.end <= self.frames.len(),
+            "Animation tag {name} is out of range."
+        );
+        self.tags.insert(name.to_string(), tag);
+    }
+    pub fn tag(&self, name: &str) -> Option<&AnimationTag> {
+        self.tags.get(name)
+    }
+    pub fn current_tag(&self) -> Option<&str> {
+        self.current_tag.as_deref()
+    }
+    // switches to the named tag from its starting frame; does nothing if it is already playing,
+    // but replays a tag that has finished
+    pub fn play(&mut self, name: &str) -> bool {
+        if self.current_tag.as_deref() == Some(name) && !self.playback.finished {
+            return true;
+        }
+
+        let Some(tag) = self.tags.get(name) else {
+            return false;
+        };
+
+        self.playback = Playback::new(tag.clone());
+        self.current_tag = Some(name.to_string());
+        true
+    }
+    // plays every frame, ignoring tags
+    pub fn play_all(&mut self, mode: PlaybackMode) {
+        self.playback = Playback::new(AnimationTag::new(0..self.frames.len(), mode));
+        self.current_tag = None;
+    }
+    pub fn restart(&mut self) {
+        self.playback = Playback::new(self.playback.tag.clone());
+    }
+    pub fn update(&mut self, dt: f32) {
+        self.playback.update(&self.durations, dt * self.speed);
+    }
+    pub fn is_finished(&self) -> bool {
+        self.playback.finished
+    }
+    pub fn current_frame(&self) -> usize {
+        self.playback.frame
+    }
+    pub fn frames(&self) -> &[AnimationFrame] {
+        &self.frames
+    }
+    pub fn sprite(&self) -> &Sprite {
+        &self.frames[self.playback.frame].sprite
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frames_visited(playback: &mut Playback, durations: &[f32], steps: usize) -> Vec<usize> {
+        (0..steps)
+            .map(|_| {
+                playback.update(durations, 0.1);
+                playback.frame
+            })
+            .collect()
+    }
+
+    #[test]
+    fn loop_wraps_to_the_first_frame() {
+        let durations = [0.1; 3];
+        let mut playback = Playback::new(AnimationTag::new(0..3, PlaybackMode::Loop));
+
+        assert_eq!(
+            frames_visited(&mut playback, &durations, 5),
+            vec![1, 2, 0, 1, 2]
+        );
+        assert!(!playback.finished);
+    }
+
+    #[test]
+    fn once_stops_on_the_last_frame() {
+        let durations = [0.1; 3];
+        let mut playback = Playback::new(AnimationTag::new(0..3, PlaybackMode::Once));
+
+        assert_eq!(
+            frames_visited(&mut playback, &durations, 4),
+            vec![1, 2, 2, 2]
+        );
+        assert!(playback.finished);
+    }
+
+    #[test]
+    fn ping_pong_turns_without_repeating_the_end_frames() {
+        let durations = [0.1; 3];
+        let mut playback = Playback::new(AnimationTag::new(0..3, PlaybackMode::PingPong));
+
+        assert_eq!(
+            frames_visited(&mut playback, &durations, 6),
+            vec![1, 2, 1, 0, 1, 2]
+        );
+    }
+
+    #[test]
+    fn reversed_tags_start_on_the_last_frame_and_play_backward() {
+        let durations = [0.1; 3];
+        let mut playback =
+            Playback::new(AnimationTag::new(0..3, PlaybackMode::Once).reversed(true));
+
+        assert_eq!(playback.frame, 2);
+        assert_eq!(frames_visited(&mut playback, &durations, 3), vec![1, 0, 0]);
+        assert!(playback.finished);
+    }
+
+    #[test]
+    fn repeated_loops_stop_after_their_passes() {
+        let durations = [0.1; 2];
+        let mut playback = Playback::new(AnimationTag::new(0..2, PlaybackMode::Loop).repeated(2));
+
+        assert_eq!(
+            frames_visited(&mut playback, &durations, 4),
+            vec![1, 0, 1, 1]
+        );
+        assert!(playback.finished);
+    }
+
+    #[test]
+    fn repeated_ping_pong_counts_each_leg() {
+        let durations = [0.1; 3];
+        let mut playback = Playback::new(
+            AnimationTag::new(0..3, PlaybackMode::PingPong)
+                .reversed(true)
+                .repeated(2),
+        );
+
+        assert_eq!(
+            frames_visited(&mut playback, &durations, 5),
+            vec![1, 0, 1, 2, 2]
+        );
+        assert!(playback.finished);
+    }
+
+    #[test]
+    fn playback_stays_inside_its_range() {
+        let durations = [0.1; 6];
+        let mut playback = Playback::new(AnimationTag::new(2..4, PlaybackMode::Loop));
+
+        assert_eq!(
+            frames_visited(&mut playback, &durations, 4),
+            vec![3, 2, 3, 2]
+        );
+    }
+
+    #[test]
+    fn a_large_step_skips_several_frames_and_keeps_the_remainder() {
+        let durations = [0.1, 0.2, 0.3];
+        let mut playback = Playback::new(AnimationTag::new(0..3, PlaybackMode::Loop));
+
+        // 0.1 + 0.2 + 0.3 + 0.1 leaves 0.05 into the second frame of the next cycle
+        playback.update(&durations, 0.75);
+
+        assert_eq!(playback.frame, 1);
+        assert!((playback.elapsed - 0.05).abs() < 1e-5);
+    }
+
+    #[test]
+    fn a_large_step_finishes_a_once_range() {
+        let durations = [0.1; 3];
+        let mut playback = Playback::new(AnimationTag::new(0..3, PlaybackMode::Once));
+
+        playback.update(&durations, 100.0);
+
+        assert_eq!(playback.frame, 2);
+        assert!(playback.finished);
+        assert_eq!(playback.elapsed, 0.0);
+    }
+
+    #[test]
+    fn zero_length_frames_do_not_advance() {
+        let durations = [0.0; 3];
+        let mut playback = Playback::new(AnimationTag::new(0..3, PlaybackMode::Loop));
+
+        playback.update(&durations, 1.0);
+
+        assert_eq!(playback.frame, 0);
+    }
+}
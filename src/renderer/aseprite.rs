@@ -0,0 +1,994 @@
+use super::*;
+
+use animation::{AnimatedSprite, AnimationFrame, AnimationTag, PlaybackMode};
+
+use std::io::Read;
+
+const HEADER_MAGIC: u16 = 0xA5E0;
+const FRAME_MAGIC: u16 = 0xF1FA;
+
+const CHUNK_OLD_PALETTE: u16 = 0x0004;
+const CHUNK_LAYER: u16 = 0x2004;
+const CHUNK_CEL: u16 = 0x2005;
+const CHUNK_TAGS: u16 = 0x2018;
+const CHUNK_PALETTE: u16 = 0x2019;
+const CHUNK_SLICE: u16 = 0x2022;
+
+const LAYER_FLAG_VISIBLE: u16 = 1;
+const HEADER_FLAG_LAYER_OPACITY_VALID: u32 = 1;
+const SLICE_FLAG_NINE_PATCH: u32 = 1;
+const SLICE_FLAG_PIVOT: u32 = 2;
+
+#[derive(Debug)]
+pub enum AsepriteError {
+    Io(std::io::Error),
+    InvalidMagic,
+    UnexpectedEof,
+    UnsupportedColorDepth(u16),
+    InvalidCel, // cel pixel data doesn't match its dimensions
+    NoFrames,
+    FrameOutOfRange(usize),
+}
+
+impl From<std::io::Error> for AsepriteError {
+    fn from(error: std::io::Error) -> Self {
+        AsepriteError::Io(error)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AsepriteLayerType {
+    Image,
+    Group,
+    Tilemap,
+}
+
+#[derive(Clone, Debug)]
+pub struct AsepriteLayer {
+    pub name: String,
+    pub layer_type: AsepriteLayerType,
+    pub child_level: u16,
+    pub visible: bool, // false if the layer or any group containing it is hidden
+    pub opacity: u8,
+    pub blend_mode: u16, // only normal (0) is honored when flattening
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AsepriteDirection {
+    Forward,
+    Reverse,
+    PingPong,
+    PingPongReverse,
+}
+
+#[derive(Clone, Debug)]
+pub struct AsepriteTag {
+    pub name: String,
+    pub from: usize,
+    pub to: usize, // inclusive
+    pub direction: AsepriteDirection,
+    pub repeat: u16, // 0 repeats forever
+}
+
+impl AsepriteTag {
+    pub fn animation_tag(&self) -> AnimationTag {
+        let (mode, reverse) = match self.direction {
+            AsepriteDirection::Forward => (PlaybackMode::Loop, false),
+            AsepriteDirection::Reverse => (PlaybackMode::Loop, true),
+            AsepriteDirection::PingPong => (PlaybackMode::PingPong, false),
+            AsepriteDirection::PingPongReverse => (PlaybackMode::PingPong, true),
+        };
+
+        AnimationTag::new(self.from..self.to + 1, mode)
+            .reversed(reverse)
+            .repeated(self.repeat as u32)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct AsepriteSliceKey {
+    pub frame: usize, // the key applies from this frame until the next key
+    pub area: SpriteTextureArea,
+    pub nine_patch_center: Option<SpriteTextureArea>, // relative to area
+    pub pivot: Option<PixelCoordinates>,              // relative to area
+}
+
+#[derive(Clone, Debug)]
+pub struct AsepriteSlice {
+    pub name: String,
+    pub keys: Vec<AsepriteSliceKey>,
+}
+
+impl AsepriteSlice {
+    pub fn key_for_frame(&self, frame: usize) -> Option<&AsepriteSliceKey> {
+        self.keys.iter().rev().find(|key| key.frame <= frame)
+    }
+}
+
+enum AsepriteCelContent {
+    Pixels {
+        dimensions: PixelDimensions,
+        data: Vec<u8>, // in the file's color depth
+    },
+    Linked(usize), // frame whose cel on the same layer is reused
+}
+
+struct AsepriteCel {
+    layer: usize,
+    position: PixelCoordinates,
+    opacity: u8,
+    content: AsepriteCelContent,
+}
+
+pub struct AsepriteFrame {
+    pub duration: f32, // seconds
+    cels: Vec<AsepriteCel>,
+}
+
+pub struct AsepriteFile {
+    pub dimensions: PixelDimensions,
+    pub color_depth: u16,
+    pub transparent_index: u8,
+    pub palette: Vec<image::Rgba<u8>>,
+    pub layers: Vec<AsepriteLayer>,
+    pub frames: Vec<AsepriteFrame>,
+    pub tags: Vec<AsepriteTag>,
+    pub slices: Vec<AsepriteSlice>,
+    layer_opacity_valid: bool,
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, position: 0 }
+    }
+    fn take(&mut self, length: usize) -> Result<&'a [u8], AsepriteError> {
+        let end = self
+            .position
+            .checked_add(length)
+            .filter(|&end| end <= self.bytes.len())
+            .ok_or(AsepriteError::UnexpectedEof)?;
+        let bytes = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+    fn skip(&mut self, length: usize) -> Result<(), AsepriteError> {
+        self.take(length).map(|_| ())
+    }
+    fn byte(&mut self) -> Result<u8, AsepriteError> {
+        Ok(self.take(1)?[0])
+    }
+    fn word(&mut self) -> Result<u16, AsepriteError> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+    fn short(&mut self) -> Result<i16, AsepriteError> {
+        Ok(i16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+    fn dword(&mut self) -> Result<u32, AsepriteError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+    fn long(&mut self) -> Result<i32, AsepriteError> {
+        Ok(i32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+    fn string(&mut self) -> Result<String, AsepriteError> {
+        let length = self.word()? as usize;
+        Ok(String::from_utf8_lossy(self.take(length)?).into_owned())
+    }
+}
+
+impl AsepriteFile {
+    pub fn open(path: &str) -> Result<Self, AsepriteError> {
+        let bytes = std::fs::read(path)?;
+        AsepriteFile::parse(&bytes)
+    }
+    pub fn parse(bytes: &[u8]) -> Result<Self, AsepriteError> {
+        let mut header = Reader::new(bytes);
+
+        header.dword()?; // file size
+        if header.word()? != HEADER_MAGIC {
+            return Err(AsepriteError::InvalidMagic);
+        }
+        let frame_count = header.word()? as usize;
+        let width = header.word()? as u32;
+        let height = header.word()? as u32;
+        let color_depth = header.word()?;
+        let flags = header.dword()?;
+        header.skip(2 + 4 + 4)?; // speed, reserved
+        let transparent_index = header.byte()?;
+        header.skip(3 + 2 + 1 + 1 + 2 + 2 + 2 + 2 + 84)?;
+
+        if !matches!(color_depth, 32 | 16 | 8) {
+            return Err(AsepriteError::UnsupportedColorDepth(color_depth));
+        }
+
+        let mut file = AsepriteFile {
+            dimensions: PixelDimensions { width, height },
+            color_depth,
+            transparent_index,
+            palette: Vec::new(),
+            layers: Vec::new(),
+            frames: Vec::with_capacity(frame_count),
+            tags: Vec::new(),
+            slices: Vec::new(),
+            layer_opacity_valid: flags & HEADER_FLAG_LAYER_OPACITY_VALID != 0,
+        };
+
+        // effective visibility of the latest group seen at each child level
+        let mut group_visibility: Vec<bool> = Vec::new();
+
+        let mut frames = Reader::new(&bytes[header.position..]);
+
+        for _ in 0..frame_count {
+            let frame_size = frames.dword()? as usize;
+            let mut frame = Reader::new(frames.take(frame_size.saturating_sub(4))?);
+
+            if frame.word()? != FRAME_MAGIC {
+                return Err(AsepriteError::InvalidMagic);
+            }
+            let old_chunk_count = frame.word()? as usize;
+            let duration = frame.word()? as f32 / 1000.0;
+            frame.skip(2)?;
+            let chunk_count = match frame.dword()? as usize {
+                0 => old_chunk_count,
+                chunk_count => chunk_count,
+            };
+
+            let mut cels = Vec::new();
+
+            for _ in 0..chunk_count {
+                let chunk_size = frame.dword()? as usize;
+                let chunk_type = frame.word()?;
+                let mut chunk = Reader::new(frame.take(chunk_size.saturating_sub(6))?);
+
+                match chunk_type {
+                    CHUNK_LAYER => {
+                        let layer = read_layer(&mut chunk, &mut group_visibility)?;
+                        file.layers.push(layer);
+                    }
+                    CHUNK_CEL => {
+                        if let Some(cel) = read_cel(&mut chunk)? {
+                            cels.push(cel);
+                        }
+                    }
+                    CHUNK_TAGS => file.tags = read_tags(&mut chunk)?,
+                    CHUNK_PALETTE => read_palette(&mut chunk, &mut file.palette)?,
+                    // only used by files that predate the new palette chunk
+                    CHUNK_OLD_PALETTE if file.palette.is_empty() => {
+                        read_old_palette(&mut chunk, &mut file.palette)?
+                    }
+                    CHUNK_SLICE => file.slices.push(read_slice(&mut chunk)?),
+                    _ => (),
+                }
+            }
+
+            file.frames.push(AsepriteFrame { duration, cels });
+        }
+
+        Ok(file)
+    }
+    fn to_rgba(&self, data: &[u8]) -> Vec<image::Rgba<u8>> {
+        match self.color_depth {
+            32 => data
+                .chunks_exact(4)
+                .map(|pixel| image::Rgba([pixel[0], pixel[1], pixel[2], pixel[3]]))
+                .collect(),
+            16 => data
+                .chunks_exact(2)
+                .map(|pixel| image::Rgba([pixel[0], pixel[0], pixel[0], pixel[1]]))
+                .collect(),
+            _ => data
+                .iter()
+                .map(|&index| {
+                    if index == self.transparent_index {
+                        image::Rgba([0, 0, 0, 0])
+                    } else {
+                        self.palette
+                            .get(index as usize)
+                            .copied()
+                            .unwrap_or(image::Rgba([0, 0, 0, 0]))
+                    }
+                })
+                .collect(),
+        }
+    }
+    // composites every visible image layer of a frame, bottom layer first
+    pub fn flatten_frame(&self, frame: usize) -> Result<image::RgbaImage, AsepriteError> {
+        let mut output = image::RgbaImage::new(self.dimensions.width, self.dimensions.height);
+
+        let mut cels: Vec<&AsepriteCel> = self
+            .frames
+            .get(frame)
+            .ok_or(AsepriteError::FrameOutOfRange(frame))?
+            .cels
+            .iter()
+            .collect();
+        cels.sort_by_key(|cel| cel.layer);
+
+        for cel in cels {
+            let Some(layer) = self.layers.get(cel.layer) else {
+                continue;
+            };
+            if !layer.visible || layer.layer_type != AsepriteLayerType::Image {
+                continue;
+            }
+
+            let (dimensions, data) = match &cel.content {
+                AsepriteCelContent::Pixels { dimensions, data } => (*dimensions, data),
+                AsepriteCelContent::Linked(linked_frame) => {
+                    let linked = self
+                        .frames
+                        .get(*linked_frame)
+                        .and_then(|frame| frame.cels.iter().find(|c| c.layer == cel.layer));
+                    match linked.map(|linked| &linked.content) {
+                        Some(AsepriteCelContent::Pixels { dimensions, data }) => {
+                            (*dimensions, data)
+                        }
+                        _ => continue,
+                    }
+                }
+            };
+
+            let pixels = self.to_rgba(data);
+            if pixels.len() != (dimensions.width * dimensions.height) as usize {
+                return Err(AsepriteError::InvalidCel);
+            }
+
+            let layer_opacity = if self.layer_opacity_valid {
+                layer.opacity
+            } else {
+                255
+            };
+            let opacity = (cel.opacity as u32 * layer_opacity as u32 / 255) as u8;
+
+            for (index, pixel) in pixels.into_iter().enumerate() {
+                let x = cel.position.x + (index as u32 % dimensions.width) as i32;
+                let y = cel.position.y + (index as u32 / dimensions.width) as i32;
+                if x < 0
+                    || y < 0
+                    || x as u32 >= self.dimensions.width
+                    || y as u32 >= self.dimensions.height
+                {
+                    continue;
+                }
+                blend_over(output.get_pixel_mut(x as u32, y as u32), pixel, opacity);
+            }
+        }
+
+        Ok(output)
+    }
+}
+
+// straight-alpha source over destination
+fn blend_over(destination: &mut image::Rgba<u8>, source: image::Rgba<u8>, opacity: u8) {
+    let source_alpha = source[3] as f32 / 255.0 * opacity as f32 / 255.0;
+    let destination_alpha = destination[3] as f32 / 255.0;
+    let alpha = source_alpha + destination_alpha * (1.0 - source_alpha);
+
+    if alpha <= 0.0 {
+        *destination = image::Rgba([0, 0, 0, 0]);
+        return;
+    }
+
+    for channel in 0..3 {
+        let value = (source[channel] as f32 * source_alpha
+            + destination[channel] as f32 * destination_alpha * (1.0 - source_alpha))
+            / alpha;
+        destination[channel] = value.round() as u8;
+    }
+    destination[3] = (alpha * 255.0).round() as u8;
+}
+
+fn read_layer(
+    chunk: &mut Reader,
+    group_visibility: &mut Vec<bool>,
+) -> Result<AsepriteLayer, AsepriteError> {
+    let flags = chunk.word()?;
+    let layer_type = match chunk.word()? {
+        1 => AsepriteLayerType::Group,
+        2 => AsepriteLayerType::Tilemap,
+        _ => AsepriteLayerType::Image,
+    };
+    let child_level = chunk.word()?;
+    chunk.skip(2 + 2)?; // default width and height
+    let blend_mode = chunk.word()?;
+    let opacity = chunk.byte()?;
+    chunk.skip(3)?;
+    let name = chunk.string()?;
+
+    let parent_visible = match child_level {
+        0 => true,
+        level => group_visibility
+            .get(level as usize - 1)
+            .copied()
+            .unwrap_or(true),
+    };
+    let visible = parent_visible && flags & LAYER_FLAG_VISIBLE != 0;
+
+    if layer_type == AsepriteLayerType::Group {
+        group_visibility.resize(child_level as usize + 1, true);
+        group_visibility[child_level as usize] = visible;
+    }
+
+    Ok(AsepriteLayer {
+        name,
+        layer_type,
+        child_level,
+        visible,
+        opacity,
+        blend_mode,
+    })
+}
+
+fn read_cel(chunk: &mut Reader) -> Result<Option<AsepriteCel>, AsepriteError> {
+    let layer = chunk.word()? as usize;
+    let x = chunk.short()? as i32;
+    let y = chunk.short()? as i32;
+    let opacity = chunk.byte()?;
+    let cel_type = chunk.word()?;
+    chunk.skip(2 + 5)?; // z-index, reserved
+
+    let content = match cel_type {
+        0 | 2 => {
+            let width = chunk.word()? as u32;
+            let height = chunk.word()? as u32;
+            let remaining = chunk.bytes.len() - chunk.position;
+            let raw = chunk.take(remaining)?;
+            let data = if cel_type == 2 {
+                let mut data = Vec::new();
+                flate2::read::ZlibDecoder::new(raw).read_to_end(&mut data)?;
+                data
+            } else {
+                raw.to_vec()
+            };
+            AsepriteCelContent::Pixels {
+                dimensions: PixelDimensions { width, height },
+                data,
+            }
+        }
+        1 => AsepriteCelContent::Linked(chunk.word()? as usize),
+        // compressed tilemaps aren't flattened
+        _ => return Ok(None),
+    };
+
+    Ok(Some(AsepriteCel {
+        layer,
+        position: PixelCoordinates { x, y },
+        opacity,
+        content,
+    }))
+}
+
+fn read_tags(chunk: &mut Reader) -> Result<Vec<AsepriteTag>, AsepriteError> {
+    let count = chunk.word()?;
+    chunk.skip(8)?;
+
+    (0..count)
+        .map(|_| {
+            let from = chunk.word()? as usize;
+            let to = chunk.word()? as usize;
+            let direction = match chunk.byte()? {
+                1 => AsepriteDirection::Reverse,
+                2 => AsepriteDirection::PingPong,
+                3 => AsepriteDirection::PingPongReverse,
+                _ => AsepriteDirection::Forward,
+            };
+            let repeat = chunk.word()?;
+            chunk.skip(6 + 3 + 1)?; // reserved, deprecated color, extra byte
+            let name = chunk.string()?;
+            Ok(AsepriteTag {
+                name,
+                from,
+                to,
+                direction,
+                repeat,
+            })
+        })
+        .collect()
+}
+
+fn read_palette(
+    chunk: &mut Reader,
+    palette: &mut Vec<image::Rgba<u8>>,
+) -> Result<(), AsepriteError> {
+    let size = chunk.dword()? as usize;
+    let first = chunk.dword()? as usize;
+    let last = chunk.dword()? as usize;
+    chunk.skip(8)?;
+
+    palette.resize(size.max(palette.len()), image::Rgba([0, 0, 0, 0]));
+
+    for index in first..=last {
+        let flags = chunk.word()?;
+        let color = image::Rgba([chunk.byte()?, chunk.byte()?, chunk.byte()?, chunk.byte()?]);
+        if flags & 1 != 0 {
+            chunk.string()?;
+        }
+        if let Some(entry) = palette.get_mut(index) {
+            *entry = color;
+        }
+    }
+
+    Ok(())
+}
+
+fn read_old_palette(
+    chunk: &mut Reader,
+    palette: &mut Vec<image::Rgba<u8>>,
+) -> Result<(), AsepriteError> {
+    let packets = chunk.word()?;
+    let mut index = 0;
+
+    for _ in 0..packets {
+        index += chunk.byte()? as usize;
+        let count = match chunk.byte()? {
+            0 => 256,
+            count => count as usize,
+        };
+        for _ in 0..count {
+            let color = image::Rgba([chunk.byte()?, chunk.byte()?, chunk.byte()?, 255]);
+            if index >= palette.len() {
+                palette.resize(index + 1, image::Rgba([0, 0, 0, 0]));
+            }
+            palette[index] = color;
+            index += 1;
+        }
+    }
+
+    Ok(())
+}
+
+fn read_slice(chunk: &mut Reader) -> Result<AsepriteSlice, AsepriteError> {
+    let key_count = chunk.dword()?;
+    let flags = chunk.dword()?;
+    chunk.dword()?;
+    let name = chunk.string()?;
+
+    fn read_area(chunk: &mut Reader) -> Result<SpriteTextureArea, AsepriteError> {
+        Ok(SpriteTextureArea {
+            coordinates: PixelCoordinates {
+                x: chunk.long()?,
+                y: chunk.long()?,
+            },
+            dimensions: PixelDimensions {
+                width: chunk.dword()?,
+                height: chunk.dword()?,
+            },
+        })
+    }
+
+    let mut keys = Vec::with_capacity(key_count as usize);
+
+    for _ in 0..key_count {
+        let frame = chunk.dword()? as usize;
+        let area = read_area(chunk)?;
+        let nine_patch_center = if flags & SLICE_FLAG_NINE_PATCH != 0 {
+            Some(read_area(chunk)?)
+        } else {
+            None
+        };
+        let pivot = if flags & SLICE_FLAG_PIVOT != 0 {
+            Some(PixelCoordinates {
+                x: chunk.long()?,
+                y: chunk.long()?,
+            })
+        } else {
+            None
+        };
+        keys.push(AsepriteSliceKey {
+            frame,
+            area,
+            nine_patch_center,
+            pivot,
+        });
+    }
+
+    Ok(AsepriteSlice { name, keys })
+}
+
+// every frame flattened into one grid-packed texture
+pub struct AsepriteSheet {
+    pub texture: Rc<Texture>,
+    pub frame_areas: Vec<SpriteTextureArea>,
+    pub animation: AnimatedSprite,
+    pub slices: Vec<AsepriteSlice>,
+}
+
+impl AsepriteSheet {
+    pub(crate) fn create(
+        texture_manager: &TextureManager,
+        file: &AsepriteFile,
+    ) -> Result<Self, AsepriteError> {
+        let frame_count = file.frames.len().max(1);
+        let columns = (frame_count as f32).sqrt().ceil() as u32;
+        let rows = (frame_count as u32).div_ceil(columns);
+        let PixelDimensions { width, height } = file.dimensions;
+
+        let mut sheet = image::RgbaImage::new(width * columns, height * rows);
+        let mut frame_areas = Vec::with_capacity(file.frames.len());
+
+        for frame in 0..file.frames.len() {
+            let x = (frame as u32 % columns) * width;
+            let y = (frame as u32 / columns) * height;
+            let flattened = file.flatten_frame(frame)?;
+            image::imageops::replace(&mut sheet, &flattened, x as i64, y as i64);
+            frame_areas.push(SpriteTextureArea {
+                coordinates: PixelCoordinates {
+                    x: x as i32,
+                    y: y as i32,
+                },
+                dimensions: file.dimensions,
+            });
+        }
+
+        let texture = Rc::new(texture_manager.create_texture(sheet));
+
+        let frames: Vec<AnimationFrame> = frame_areas
+            .iter()
+            .zip(file.frames.iter())
+            .map(|(&area, frame)| AnimationFrame {
+                sprite: Sprite::create(Rc::clone(&texture), Some(area)),
+                duration: frame.duration,
+            })
+            .collect();
+
+        if frames.is_empty() {
+            return Err(AsepriteError::NoFrames);
+        }
+
+        let mut animation = AnimatedSprite::new(frames, PlaybackMode::Loop);
+        for tag in &file.tags {
+            if tag.from <= tag.to && tag.to < file.frames.len() {
+                animation.insert_tag(&tag.name, tag.animation_tag());
+            }
+        }
+
+        Ok(Self {
+            texture,
+            frame_areas,
+            animation,
+            slices: file.slices.clone(),
+        })
+    }
+    // the slice's area on the sheet for the given frame
+    pub fn slice_area(&self, name: &str, frame: usize) -> Option<SpriteTextureArea> {
+        let slice = self.slices.iter().find(|slice| slice.name == name)?;
+        let key = slice.key_for_frame(frame)?;
+        let frame_area = self.frame_areas.get(frame)?;
+
+        Some(SpriteTextureArea {
+            coordinates: PixelCoordinates {
+                x: frame_area.coordinates.x + key.area.coordinates.x,
+                y: frame_area.coordinates.y + key.area.coordinates.y,
+            },
+            dimensions: key.area.dimensions,
+        })
+    }
+    pub fn slice_sprite(&self, name: &str, frame: usize) -> Option<Sprite> {
+        let area = self.slice_area(name, frame)?;
+        Some(Sprite::create(Rc::clone(&self.texture), Some(area)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Write;
+
+    // little-endian writer for building files in the layout parse reads
+    #[derive(Default)]
+    struct Writer(Vec<u8>);
+
+    impl Writer {
+        fn byte(mut self, value: u8) -> Self {
+            self.0.push(value);
+            self
+        }
+        fn word(mut self, value: u16) -> Self {
+            self.0.extend(value.to_le_bytes());
+            self
+        }
+        fn short(mut self, value: i16) -> Self {
+            self.0.extend(value.to_le_bytes());
+            self
+        }
+        fn dword(mut self, value: u32) -> Self {
+            self.0.extend(value.to_le_bytes());
+            self
+        }
+        fn long(mut self, value: i32) -> Self {
+            self.0.extend(value.to_le_bytes());
+            self
+        }
+        fn zeros(mut self, length: usize) -> Self {
+            self.0.resize(self.0.len() + length, 0);
+            self
+        }
+        fn bytes(mut self, bytes: &[u8]) -> Self {
+            self.0.extend_from_slice(bytes);
+            self
+        }
+        fn string(self, value: &str) -> Self {
+            self.word(value.len() as u16).bytes(value.as_bytes())
+        }
+    }
+
+    fn file(color_depth: u16, frames: &[Vec<(u16, Writer)>]) -> Vec<u8> {
+        let mut bytes = Writer::default()
+            .dword(0)
+            .word(HEADER_MAGIC)
+            .word(frames.len() as u16)
+            .word(4)
+            .word(2)
+            .word(color_depth)
+            .dword(HEADER_FLAG_LAYER_OPACITY_VALID)
+            .word(100)
+            .zeros(8)
+            .byte(0) // transparent index
+            .zeros(3 + 2 + 1 + 1 + 2 + 2 + 2 + 2 + 84);
+
+        for chunks in frames {
+            let mut frame = Writer::default()
+                .word(FRAME_MAGIC)
+                .word(chunks.len() as u16)
+                .word(250)
+                .zeros(2)
+                .dword(chunks.len() as u32);
+            for (chunk_type, chunk) in chunks {
+                frame = frame
+                    .dword(chunk.0.len() as u32 + 6)
+                    .word(*chunk_type)
+                    .bytes(&chunk.0);
+            }
+            bytes = bytes.dword(frame.0.len() as u32 + 4).bytes(&frame.0);
+        }
+
+        bytes.0
+    }
+
+    fn layer(name: &str, layer_type: u16, child_level: u16, visible: bool) -> (u16, Writer) {
+        let chunk = Writer::default()
+            .word(if visible { LAYER_FLAG_VISIBLE } else { 0 })
+            .word(layer_type)
+            .word(child_level)
+            .zeros(4)
+            .word(0)
+            .byte(255)
+            .zeros(3)
+            .string(name);
+        (CHUNK_LAYER, chunk)
+    }
+
+    fn cel_header(layer: u16, x: i16, y: i16, cel_type: u16) -> Writer {
+        Writer::default()
+            .word(layer)
+            .short(x)
+            .short(y)
+            .byte(255)
+            .word(cel_type)
+            .zeros(7)
+    }
+
+    fn cel(layer: u16, x: i16, y: i16, width: u16, height: u16, data: &[u8]) -> (u16, Writer) {
+        let chunk = cel_header(layer, x, y, 0)
+            .word(width)
+            .word(height)
+            .bytes(data);
+        (CHUNK_CEL, chunk)
+    }
+
+    const RED: [u8; 4] = [255, 0, 0, 255];
+    const BLUE: [u8; 4] = [0, 0, 255, 255];
+
+    fn pixel(image: &image::RgbaImage, x: u32, y: u32) -> [u8; 4] {
+        image.get_pixel(x, y).0
+    }
+
+    #[test]
+    fn cels_are_placed_at_their_position() {
+        let bytes = file(
+            32,
+            &[vec![
+                layer("base", 0, 0, true),
+                cel(0, 1, 1, 2, 1, &[RED, BLUE].concat()),
+            ]],
+        );
+        let file = AsepriteFile::parse(&bytes).unwrap();
+
+        assert_eq!(
+            file.dimensions,
+            PixelDimensions {
+                width: 4,
+                height: 2
+            }
+        );
+        assert_eq!(file.frames[0].duration, 0.25);
+
+        let frame = file.flatten_frame(0).unwrap();
+        assert_eq!(pixel(&frame, 1, 1), RED);
+        assert_eq!(pixel(&frame, 2, 1), BLUE);
+        assert_eq!(pixel(&frame, 0, 0), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn compressed_and_linked_cels_flatten_the_same_pixels() {
+        let mut encoder =
+            flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&[RED, RED].concat()).unwrap();
+        let compressed = cel_header(0, 0, 0, 2)
+            .word(1)
+            .word(2)
+            .bytes(&encoder.finish().unwrap());
+        let linked = cel_header(0, 0, 0, 1).word(0);
+
+        let bytes = file(
+            32,
+            &[
+                vec![layer("base", 0, 0, true), (CHUNK_CEL, compressed)],
+                vec![(CHUNK_CEL, linked)],
+            ],
+        );
+        let file = AsepriteFile::parse(&bytes).unwrap();
+
+        for frame in 0..2 {
+            let frame = file.flatten_frame(frame).unwrap();
+            assert_eq!(pixel(&frame, 0, 0), RED);
+            assert_eq!(pixel(&frame, 0, 1), RED);
+            assert_eq!(pixel(&frame, 1, 0), [0, 0, 0, 0]);
+        }
+    }
+
+    #[test]
+    fn layers_in_hidden_groups_are_hidden() {
+        let bytes = file(
+            32,
+            &[vec![
+                layer("group", 1, 0, false),
+                layer("inside", 0, 1, true),
+                layer("outside", 0, 0, true),
+                cel(1, 0, 0, 1, 1, &RED),
+                cel(2, 1, 0, 1, 1, &BLUE),
+            ]],
+        );
+        let file = AsepriteFile::parse(&bytes).unwrap();
+
+        assert!(!file.layers[1].visible);
+        assert!(file.layers[2].visible);
+
+        let frame = file.flatten_frame(0).unwrap();
+        assert_eq!(pixel(&frame, 0, 0), [0, 0, 0, 0]);
+        assert_eq!(pixel(&frame, 1, 0), BLUE);
+    }
+
+    #[test]
+    fn indexed_cels_read_the_palette_and_skip_the_transparent_index() {
+        let palette = Writer::default()
+            .dword(2)
+            .dword(0)
+            .dword(1)
+            .zeros(8)
+            .word(0)
+            .bytes(&BLUE)
+            .word(1)
+            .bytes(&RED)
+            .string("red");
+
+        let bytes = file(
+            8,
+            &[vec![
+                (CHUNK_PALETTE, palette),
+                layer("base", 0, 0, true),
+                cel(0, 0, 0, 2, 1, &[0, 1]),
+            ]],
+        );
+        let file = AsepriteFile::parse(&bytes).unwrap();
+
+        assert_eq!(file.palette, [image::Rgba(BLUE), image::Rgba(RED)]);
+
+        let frame = file.flatten_frame(0).unwrap();
+        assert_eq!(pixel(&frame, 0, 0), [0, 0, 0, 0]);
+        assert_eq!(pixel(&frame, 1, 0), RED);
+    }
+
+    #[test]
+    fn tags_and_slices_are_read() {
+        let tags = Writer::default()
+            .word(2)
+            .zeros(8)
+            .word(0)
+            .word(1)
+            .byte(2)
+            .word(0)
+            .zeros(10)
+            .string("walk")
+            .word(1)
+            .word(1)
+            .byte(1)
+            .word(1)
+            .zeros(10)
+            .string("hit");
+        let slice = Writer::default()
+            .dword(2)
+            .dword(SLICE_FLAG_PIVOT)
+            .dword(0)
+            .string("hand")
+            .dword(0)
+            .long(1)
+            .long(0)
+            .dword(2)
+            .dword(2)
+            .long(1)
+            .long(1)
+            .dword(1)
+            .long(2)
+            .long(0)
+            .dword(1)
+            .dword(1)
+            .long(0)
+            .long(0);
+
+        let bytes = file(
+            32,
+            &[vec![(CHUNK_TAGS, tags), (CHUNK_SLICE, slice)], vec![]],
+        );
+        let file = AsepriteFile::parse(&bytes).unwrap();
+
+        assert_eq!(file.tags.len(), 2);
+        assert_eq!((file.tags[0].from, file.tags[0].to), (0, 1));
+        assert_eq!(file.tags[0].direction, AsepriteDirection::PingPong);
+        let walk = file.tags[0].animation_tag();
+        assert_eq!(walk.frames, 0..2);
+        assert_eq!(walk.mode, PlaybackMode::PingPong);
+        assert_eq!((walk.reverse, walk.repeat), (false, 0));
+        assert_eq!(file.tags[1].name, "hit");
+        assert_eq!(file.tags[1].direction, AsepriteDirection::Reverse);
+        let hit = file.tags[1].animation_tag();
+        assert_eq!(hit.frames, 1..2);
+        assert_eq!(hit.mode, PlaybackMode::Loop);
+        assert_eq!((hit.reverse, hit.repeat), (true, 1));
+
+        let slice = &file.slices[0];
+        assert_eq!(slice.name, "hand");
+        let key = slice.key_for_frame(0).unwrap();
+        assert_eq!(key.area.coordinates, PixelCoordinates { x: 1, y: 0 });
+        assert_eq!(key.pivot, Some(PixelCoordinates { x: 1, y: 1 }));
+        assert!(key.nine_patch_center.is_none());
+        assert_eq!(slice.key_for_frame(1).unwrap().frame, 1);
+    }
+
+    #[test]
+    fn malformed_files_are_rejected() {
+        let mut bytes = file(32, &[vec![layer("base", 0, 0, true)]]);
+
+        assert!(matches!(
+            AsepriteFile::parse(&bytes[..bytes.len() - 1]),
+            Err(AsepriteError::UnexpectedEof)
+        ));
+
+        assert!(matches!(
+            AsepriteFile::parse(&bytes).unwrap().flatten_frame(1),
+            Err(AsepriteError::FrameOutOfRange(1))
+        ));
+
+        bytes[4] = 0;
+        assert!(matches!(
+            AsepriteFile::parse(&bytes),
+            Err(AsepriteError::InvalidMagic)
+        ));
+    }
+
+    #[test]
+    fn blend_over_mixes_by_source_alpha_and_opacity() {
+        let mut destination = image::Rgba(BLUE);
+        blend_over(&mut destination, image::Rgba(RED), 128);
+        assert_eq!(destination.0, [128, 0, 127, 255]);
+
+        let mut destination = image::Rgba([0, 0, 0, 0]);
+        blend_over(&mut destination, image::Rgba([255, 255, 255, 0]), 255);
+        assert_eq!(destination.0, [0, 0, 0, 0]);
+    }
+}
@@ -1,7 +1,8 @@
 use super::*;
 pub struct BindGroupLayouts {
     pub texture: Rc<wgpu::BindGroupLayout>,
-    pub sprite_uniforms: Rc<wgpu::BindGroupLayout>,
+    pub surface_uniforms: Rc<wgpu::BindGroupLayout>,
+    pub post_effect_uniforms: Rc<wgpu::BindGroupLayout>,
 }
 
 impl BindGroupLayouts {
@@ -28,7 +29,7 @@ impl BindGroupLayouts {
             label: Some("Texture bind group layout"),
         })
     }
-    pub fn sprite_uniforms(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    pub fn surface_uniforms(device: &wgpu::Device) -> wgpu::BindGroupLayout {
         device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             entries: &[wgpu::BindGroupLayoutEntry {
                 binding: 0,
@@ -40,15 +41,35 @@ impl BindGroupLayouts {
                 },
                 count: None,
             }],
-            label: Some("SpriteUniforms bind group layout"),
+            label: Some("SurfaceUniforms bind group layout"),
+        })
+    }
+    // the globals shared by every post effect, then the effect's own parameters
+    pub fn post_effect_uniforms(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        let uniform_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[uniform_entry(0), uniform_entry(1)],
+            label: Some("post effect uniforms bind group layout"),
         })
     }
     pub fn create(device: &wgpu::Device) -> Self {
         let texture_bind_group_layout = BindGroupLayouts::texture(device);
-        let sprite_uniforms_bind_group_layout = BindGroupLayouts::sprite_uniforms(device);
+        let surface_uniforms_bind_group_layout = BindGroupLayouts::surface_uniforms(device);
+        let post_effect_uniforms_bind_group_layout = BindGroupLayouts::post_effect_uniforms(device);
         Self {
             texture: Rc::new(texture_bind_group_layout),
-            sprite_uniforms: Rc::new(sprite_uniforms_bind_group_layout),
+            surface_uniforms: Rc::new(surface_uniforms_bind_group_layout),
+            post_effect_uniforms: Rc::new(post_effect_uniforms_bind_group_layout),
         }
     }
 }
@@ -0,0 +1,551 @@
+use super::*;
+
+use text_layout::{FontGlyph, FontSource, TextAlignment};
+
+use std::{collections::HashMap, path::Path};
+
+#[derive(Debug)]
+pub enum BitmapFontError {
+    Io(std::io::Error),
+    Image(image::ImageError),
+    Malformed(&'static str),
+}
+
+impl From<std::io::Error> for BitmapFontError {
+    fn from(error: std::io::Error) -> Self {
+        BitmapFontError::Io(error)
+    }
+}
+
+impl From<image::ImageError> for BitmapFontError {
+    fn from(error: image::ImageError) -> Self {
+        BitmapFontError::Image(error)
+    }
+}
+
+// a char block as it appears in any of the three .fnt encodings
+#[derive(Clone, Copy, Debug, Default)]
+struct BitmapCharDescription {
+    id: u32,
+    x: i32,
+    y: i32,
+    width: u32,
+    height: u32,
+    x_offset: i32,
+    y_offset: i32,
+    x_advance: i32,
+    page: usize,
+}
+
+#[derive(Default)]
+struct BitmapFontDescription {
+    line_height: u32,
+    base: u32,
+    pages: Vec<String>,
+    chars: Vec<BitmapCharDescription>,
+    kernings: Vec<(u32, u32, i32)>,
+}
+
+impl BitmapFontDescription {
+    fn parse(bytes: &[u8]) -> Result<Self, BitmapFontError> {
+        if bytes.starts_with(b"BMF") {
+            return BitmapFontDescription::parse_binary(bytes);
+        }
+
+        let source = String::from_utf8_lossy(bytes);
+
+        // xml elements carry the same attributes as the text format's lines
+        let lines: Vec<&str> = if source.trim_start().starts_with('<') {
+            source.split('<').collect()
+        } else {
+            source.lines().collect()
+        };
+
+        let mut description = BitmapFontDescription::default();
+
+        for line in lines {
+            let line = line.trim().trim_end_matches('>').trim_end_matches('/');
+            let (tag, attributes) = parse_tag(line);
+            let attribute = |name: &str| -> i32 {
+                attributes
+                    .iter()
+                    .find(|(key, _)| *key == name)
+                    .and_then(|(_, value)| value.parse().ok())
+                    .unwrap_or(0)
+            };
+
+            match tag {
+                "common" => {
+                    description.line_height = attribute("lineHeight") as u32;
+                    description.base = attribute("base") as u32;
+                }
+                "page" => {
+                    let id = attribute("id") as usize;
+                    let file = attributes
+                        .iter()
+                        .find(|(key, _)| *key == "file")
+                        .map(|(_, value)| value.clone())
+                        .ok_or(BitmapFontError::Malformed("page without a file"))?;
+                    if description.pages.len() <= id {
+                        description.pages.resize(id + 1, String::new());
+                    }
+                    description.pages[id] = file;
+                }
+                "char" => description.chars.push(BitmapCharDescription {
+                    id: attribute("id") as u32,
+                    x: attribute("x"),
+                    y: attribute("y"),
+                    width: attribute("width") as u32,
+                    height: attribute("height") as u32,
+                    x_offset: attribute("xoffset"),
+                    y_offset: attribute("yoffset"),
+                    x_advance: attribute("xadvance"),
+                    page: attribute("page") as usize,
+                }),
+                "kerning" => description.kernings.push((
+                    attribute("first") as u32,
+                    attribute("second") as u32,
+                    attribute("amount"),
+                )),
+                _ => (),
+            }
+        }
+
+        Ok(description)
+    }
+    // version 3 of the AngelCode binary format
+    fn parse_binary(bytes: &[u8]) -> Result<Self, BitmapFontError> {
+        const TRUNCATED: BitmapFontError = BitmapFontError::Malformed("truncated binary font");
+
+        if bytes.get(3) != Some(&3) {
+            return Err(BitmapFontError::Malformed(
+                "unsupported binary font version",
+            ));
+        }
+
+        let u16_at = |block: &[u8], offset: usize| -> Result<u16, BitmapFontError> {
+            block
+                .get(offset..offset + 2)
+                .map(|b| u16::from_le_bytes([b[0], b[1]]))
+                .ok_or(TRUNCATED)
+        };
+        let i16_at = |block: &[u8], offset: usize| u16_at(block, offset).map(|v| v as i16);
+        let u32_at = |block: &[u8], offset: usize| -> Result<u32, BitmapFontError> {
+            block
+                .get(offset..offset + 4)
+                .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .ok_or(TRUNCATED)
+        };
+
+        let mut description = BitmapFontDescription::default();
+        let mut position = 4;
+
+        while position < bytes.len() {
+            let block_type = bytes[position];
+            let block_size = u32_at(bytes, position + 1)? as usize;
+            let block = bytes
+                .get(position + 5..position + 5 + block_size)
+                .ok_or(TRUNCATED)?;
+            position += 5 + block_size;
+
+            match block_type {
+                2 => {
+                    description.line_height = u16_at(block, 0)? as u32;
+                    description.base = u16_at(block, 2)? as u32;
+                }
+                3 => {
+                    description.pages = block
+                        .split(|&byte| byte == 0)
+                        .filter(|name| !name.is_empty())
+                        .map(|name| String::from_utf8_lossy(name).into_owned())
+                        .collect();
+                }
+                4 => {
+                    for char_block in block.chunks_exact(20) {
+                        description.chars.push(BitmapCharDescription {
+                            id: u32_at(char_block, 0)?,
+                            x: u16_at(char_block, 4)? as i32,
+                            y: u16_at(char_block, 6)? as i32,
+                            width: u16_at(char_block, 8)? as u32,
+                            height: u16_at(char_block, 10)? as u32,
+                            x_offset: i16_at(char_block, 12)? as i32,
+                            y_offset: i16_at(char_block, 14)? as i32,
+                            x_advance: i16_at(char_block, 16)? as i32,
+                            page: char_block[18] as usize,
+                        });
+                    }
+                }
+                5 => {
+                    for kerning_block in block.chunks_exact(10) {
+                        description.kernings.push((
+                            u32_at(kerning_block, 0)?,
+                            u32_at(kerning_block, 4)?,
+                            i16_at(kerning_block, 8)? as i32,
+                        ));
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        Ok(description)
+    }
+}
+
+// splits `tag key=value key="quoted value"` into the tag and its attributes
+fn parse_tag(line: &str) -> (&str, Vec<(&str, String)>) {
+    let (tag, mut rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let mut attributes = Vec::new();
+
+    while let Some((key, after_key)) = rest.trim_start().split_once('=') {
+        let (value, after_value) = match after_key.strip_prefix('"') {
+            Some(quoted) => quoted.split_once('"').unwrap_or((quoted, "")),
+            None => after_key
+                .split_once(char::is_whitespace)
+                .unwrap_or((after_key, "")),
+        };
+        attributes.push((key.trim(), value.to_string()));
+        rest = after_value;
+    }
+
+    (tag, attributes)
+}
+
+#[derive(Clone)]
+pub struct BitmapGlyph {
+    pub sprite: Option<Sprite>, // None for glyphs with no pixels, like space
+    pub offset: PixelCoordinates,
+    pub advance: i32,
+}
+
+pub struct BitmapFont {
+    pub line_height: u32,
+    pub base: u32,
+    pub pages: Vec<Rc<Texture>>,
+    glyphs: HashMap<char, BitmapGlyph>,
+    kerning: HashMap<(char, char), i32>,
+}
+
+impl BitmapFont {
+    // text, xml or binary .fnt; page images are resolved relative to the .fnt file
+    pub(crate) fn load(
+        texture_manager: &TextureManager,
+        path: &str,
+    ) -> Result<Self, BitmapFontError> {
+        let bytes = std::fs::read(path)?;
+        let description = BitmapFontDescription::parse(&bytes)?;
+        let directory = Path::new(path).parent().unwrap_or(Path::new(""));
+
+        let pages = description
+            .pages
+            .iter()
+            .map(|page| {
+                let page_image = image::open(directory.join(page))?.to_rgba8();
+                Ok(Rc::new(texture_manager.create_texture(page_image)))
+            })
+            .collect::<Result<Vec<_>, BitmapFontError>>()?;
+
+        let glyphs = description
+            .chars
+            .iter()
+            .filter_map(|char_description| {
+                let character = char::from_u32(char_description.id)?;
+                let sprite = if char_description.width > 0 && char_description.height > 0 {
+                    let texture = pages.get(char_description.page)?;
+                    Some(Sprite::create(
+                        Rc::clone(texture),
+                        Some(SpriteTextureArea {
+                            coordinates: PixelCoordinates {
+                                x: char_description.x,
+                                y: char_description.y,
+                            },
+                            dimensions: PixelDimensions {
+                                width: char_description.width,
+                                height: char_description.height,
+                            },
+                        }),
+                    ))
+                } else {
+                    None
+                };
+                let glyph = BitmapGlyph {
+                    sprite,
+                    offset: PixelCoordinates {
+                        x: char_description.x_offset,
+                        y: char_description.y_offset,
+                    },
+                    advance: char_description.x_advance,
+                };
+                Some((character, glyph))
+            })
+            .collect();
+
+        let kerning = description
+            .kernings
+            .iter()
+            .filter_map(|&(first, second, amount)| {
+                Some(((char::from_u32(first)?, char::from_u32(second)?), amount))
+            })
+            .collect();
+
+        Ok(Self {
+            line_height: description.line_height,
+            base: description.base,
+            pages,
+            glyphs,
+            kerning,
+        })
+    }
+    pub fn glyph(&self, character: char) -> Option<&BitmapGlyph> {
+        self.glyphs.get(&character)
+    }
+    pub fn kerning(&self, first: char, second: char) -> i32 {
+        self.kerning.get(&(first, second)).copied().unwrap_or(0)
+    }
+    // advance width of a single line, kerning included
+    pub fn line_width(&self, line: &str) -> i32 {
+        let mut width = 0;
+        let mut previous = None;
+
+        for character in line.chars() {
+            if let Some(previous) = previous {
+                width += self.kerning(previous, character);
+            }
+            if let Some(glyph) = self.glyph(character) {
+                width += glyph.advance;
+            }
+            previous = Some(character);
+        }
+
+        width
+    }
+    pub fn measure(&self, text: &str) -> PixelDimensions {
+        let width = text
+            .lines()
+            .map(|line| self.line_width(line))
+            .max()
+            .unwrap_or(0);
+        let lines = text.lines().count().max(1) as u32;
+
+        PixelDimensions {
+            width: width.max(0) as u32,
+            height: lines * self.line_height,
+        }
+    }
+    // position is the top of the first line; returns the bounds of the drawn text
+    pub fn draw_text(
+        &self,
+        surface: &PixelSurface,
+        text: &str,
+        position: PixelCoordinates,
+        color: Color,
+        alignment: TextAlignment,
+    ) -> (PixelCoordinates, PixelDimensions) {
+        text_layout::draw_text(surface, self, text, position, color, alignment)
+    }
+}
+
+impl FontSource for BitmapFont {
+    fn line_height(&self) -> f32 {
+        self.line_height as f32
+    }
+    fn ascent(&self) -> f32 {
+        self.base as f32
+    }
+    fn advance(&self, character: char) -> f32 {
+        self.glyph(character)
+            .map(|glyph| glyph.advance as f32)
+            .unwrap_or(0.0)
+    }
+    fn kerning(&self, first: char, second: char) -> f32 {
+        BitmapFont::kerning(self, first, second) as f32
+    }
+    fn glyph(&self, character: char) -> Option<FontGlyph> {
+        let glyph = BitmapFont::glyph(self, character)?;
+        Some(FontGlyph {
+            sprite: glyph.sprite.clone()?,
+            offset: glyph.offset,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attributes(line: &str) -> Vec<(&str, String)> {
+        parse_tag(line).1
+    }
+
+    // a binary block: its type, its size and then the bytes
+    fn block(block_type: u8, bytes: &[u8]) -> Vec<u8> {
+        let mut block = vec![block_type];
+        block.extend((bytes.len() as u32).to_le_bytes());
+        block.extend_from_slice(bytes);
+        block
+    }
+
+    fn char_block(id: u32, area: [u16; 4], offset: [i16; 2], advance: i16, page: u8) -> Vec<u8> {
+        let mut bytes = id.to_le_bytes().to_vec();
+        for value in area {
+            bytes.extend(value.to_le_bytes());
+        }
+        for value in offset {
+            bytes.extend(value.to_le_bytes());
+        }
+        bytes.extend(advance.to_le_bytes());
+        bytes.extend([page, 15]);
+        bytes
+    }
+
+    fn binary_font() -> Vec<u8> {
+        let mut common = Vec::new();
+        for value in [12u16, 9, 64, 32, 2] {
+            common.extend(value.to_le_bytes());
+        }
+        common.extend([0, 0, 0, 0, 0]);
+
+        let mut chars = char_block(65, [1, 2, 5, 7], [0, 2], 6, 0);
+        chars.extend(char_block(66, [8, 2, 5, 7], [-1, 2], 6, 1));
+
+        let mut kerning = 65u32.to_le_bytes().to_vec();
+        kerning.extend(66u32.to_le_bytes());
+        kerning.extend((-2i16).to_le_bytes());
+
+        let mut bytes = b"BMF\x03".to_vec();
+        bytes.extend(block(1, b"\x0c\x00skipped info"));
+        bytes.extend(block(2, &common));
+        bytes.extend(block(3, b"font_0.png\0font_1.png\0"));
+        bytes.extend(block(4, &chars));
+        bytes.extend(block(5, &kerning));
+        bytes
+    }
+
+    #[test]
+    fn tags_split_into_quoted_and_bare_attributes() {
+        let (tag, attributes) =
+            parse_tag(r#"info face="Pixel Sans" size=12 padding=0,0,0,0 charset="""#);
+
+        assert_eq!(tag, "info");
+        assert_eq!(
+            attributes,
+            [
+                ("face", "Pixel Sans".to_string()),
+                ("size", "12".to_string()),
+                ("padding", "0,0,0,0".to_string()),
+                ("charset", String::new()),
+            ]
+        );
+    }
+
+    #[test]
+    fn tags_tolerate_odd_spacing_and_unclosed_quotes() {
+        assert_eq!(parse_tag("chars"), ("chars", Vec::new()));
+        assert_eq!(
+            attributes("page  id=0   file=\"a b.png"),
+            [("id", "0".to_string()), ("file", "a b.png".to_string())]
+        );
+    }
+
+    #[test]
+    fn text_fonts_are_parsed() {
+        let source = r#"info face="Pixel Sans" size=12
+common lineHeight=12 base=9 scaleW=64 scaleH=32 pages=2
+page id=1 file="font 1.png"
+page id=0 file="font 0.png"
+chars count=2
+char id=65 x=1 y=2 width=5 height=7 xoffset=0 yoffset=2 xadvance=6 page=0
+char id=66 x=8 y=2 width=5 height=7 xoffset=-1 yoffset=2 xadvance=6 page=1
+kernings count=1
+kerning first=65 second=66 amount=-2
+"#;
+        let description = BitmapFontDescription::parse(source.as_bytes()).unwrap();
+
+        assert_eq!((description.line_height, description.base), (12, 9));
+        assert_eq!(description.pages, ["font 0.png", "font 1.png"]);
+        assert_eq!(description.chars.len(), 2);
+        let b = description.chars[1];
+        assert_eq!((b.id, b.x, b.y, b.width, b.height), (66, 8, 2, 5, 7));
+        assert_eq!((b.x_offset, b.y_offset, b.x_advance, b.page), (-1, 2, 6, 1));
+        assert_eq!(description.kernings, [(65, 66, -2)]);
+    }
+
+    #[test]
+    fn xml_fonts_with_self_closing_tags_are_parsed() {
+        let source = r#"<?xml version="1.0"?>
+<font>
+  <info face="Pixel Sans" size="12"/>
+  <common lineHeight="12" base="9" scaleW="64" scaleH="32" pages="1" />
+  <pages>
+    <page id="0" file="font.png" />
+  </pages>
+  <chars count="1">
+    <char id="65" x="1" y="2" width="5" height="7" xoffset="0" yoffset="2" xadvance="6" page="0"/>
+  </chars>
+  <kernings count="1">
+    <kerning first="65" second="66" amount="-2" />
+  </kernings>
+</font>
+"#;
+        let description = BitmapFontDescription::parse(source.as_bytes()).unwrap();
+
+        assert_eq!((description.line_height, description.base), (12, 9));
+        assert_eq!(description.pages, ["font.png"]);
+        let a = description.chars[0];
+        assert_eq!((a.id, a.x, a.width, a.x_advance, a.page), (65, 1, 5, 6, 0));
+        assert_eq!(description.kernings, [(65, 66, -2)]);
+    }
+
+    #[test]
+    fn pages_without_a_file_are_malformed() {
+        assert!(matches!(
+            BitmapFontDescription::parse(b"page id=0\n"),
+            Err(BitmapFontError::Malformed(_))
+        ));
+    }
+
+    #[test]
+    fn binary_fonts_are_parsed() {
+        let description = BitmapFontDescription::parse(&binary_font()).unwrap();
+
+        assert_eq!((description.line_height, description.base), (12, 9));
+        assert_eq!(description.pages, ["font_0.png", "font_1.png"]);
+        assert_eq!(description.chars.len(), 2);
+        let b = description.chars[1];
+        assert_eq!((b.id, b.x, b.y, b.width, b.height), (66, 8, 2, 5, 7));
+        assert_eq!((b.x_offset, b.y_offset, b.x_advance, b.page), (-1, 2, 6, 1));
+        assert_eq!(description.kernings, [(65, 66, -2)]);
+    }
+
+    #[test]
+    fn truncated_binary_fonts_are_malformed() {
+        let bytes = binary_font();
+
+        // cut inside the last block, and inside the first block's header
+        for length in [bytes.len() - 1, 6] {
+            assert!(matches!(
+                BitmapFontDescription::parse(&bytes[..length]),
+                Err(BitmapFontError::Malformed(_))
+            ));
+        }
+
+        // a common block too short for its fields
+        let mut short_common = b"BMF\x03".to_vec();
+        short_common.extend(block(2, &[12, 0, 9]));
+        assert!(matches!(
+            BitmapFontDescription::parse(&short_common),
+            Err(BitmapFontError::Malformed(_))
+        ));
+    }
+
+    #[test]
+    fn other_binary_versions_are_rejected() {
+        let mut bytes = binary_font();
+        bytes[3] = 2;
+
+        assert!(matches!(
+            BitmapFontDescription::parse(&bytes),
+            Err(BitmapFontError::Malformed(_))
+        ));
+    }
+}
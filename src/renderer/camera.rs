@@ -0,0 +1,249 @@
+use super::*;
+
+use transform::Transform2d;
+
+use nalgebra::{Point2, Vector2};
+
+// maps world coordinates onto a viewport of a surface: position is the world point shown at
+// the viewport's center, zoom is surface pixels per world unit and rotation is in fractions
+// of a turn, turning the world counter-clockwise on screen
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Camera2d {
+    pub position: Point2<f32>,
+    pub zoom: f32,
+    pub rotation: f32,
+    pub viewport_position: PixelCoordinates,
+    pub viewport_dimensions: PixelDimensions, // draws are clipped to the viewport
+    // rounds the view to whole surface pixels when unrotated, so pixel art does not shimmer
+    pub pixel_snap: bool,
+    // world-sized box around position that a followed target moves in without moving the camera
+    pub deadzone: Vector2<f32>,
+    // seconds for follow to close about two thirds of the distance, 0 to snap straight to it
+    pub smoothing: f32,
+}
+
+impl Camera2d {
+    // covers the whole of a surface of the given dimensions at zoom 1
+    pub fn new(viewport_dimensions: PixelDimensions) -> Self {
+        Self {
+            position: Point2::new(
+                viewport_dimensions.width as f32 / 2.0,
+                viewport_dimensions.height as f32 / 2.0,
+            ),
+            zoom: 1.0,
+            rotation: 0.0,
+            viewport_position: PixelCoordinates { x: 0, y: 0 },
+            viewport_dimensions,
+            pixel_snap: true,
+            deadzone: Vector2::zeros(),
+            smoothing: 0.0,
+        }
+    }
+    fn viewport_center(&self) -> Vector2<f32> {
+        Vector2::new(
+            self.viewport_position.x as f32 + self.viewport_dimensions.width as f32 / 2.0,
+            self.viewport_position.y as f32 + self.viewport_dimensions.height as f32 / 2.0,
+        )
+    }
+    // world to surface pixels
+    pub fn view_transform(&self) -> Transform2d {
+        let center = self.viewport_center();
+        let mut offset = center - self.position.coords * self.zoom;
+
+        if self.pixel_snap && self.rotation == 0.0 {
+            offset = offset.map(f32::round);
+        }
+
+        if self.rotation == 0.0 {
+            return Transform2d::scale(self.zoom, self.zoom).translated(offset.x, offset.y);
+        }
+
+        Transform2d::translation(-self.position.x, -self.position.y)
+            .rotated(self.rotation)
+            .scaled(self.zoom, self.zoom)
+            .translated(center.x, center.y)
+    }
+    pub fn world_to_screen(&self, world: Point2<f32>) -> Point2<f32> {
+        self.view_transform().transform_point(world)
+    }
+    // None only when zoom is 0
+    pub fn screen_to_world(&self, screen: Point2<f32>) -> Option<Point2<f32>> {
+        self.view_transform()
+            .inverse()
+            .map(|inverse| inverse.transform_point(screen))
+    }
+    // the world area in view, as the corners of the viewport in world coordinates
+    pub fn visible_corners(&self) -> Option<[Point2<f32>; 4]> {
+        let inverse = self.view_transform().inverse()?;
+        let (left, top) = (
+            self.viewport_position.x as f32,
+            self.viewport_position.y as f32,
+        );
+        let right = left + self.viewport_dimensions.width as f32;
+        let bottom = top + self.viewport_dimensions.height as f32;
+
+        Some([
+            inverse.transform_point(Point2::new(left, top)),
+            inverse.transform_point(Point2::new(right, top)),
+            inverse.transform_point(Point2::new(left, bottom)),
+            inverse.transform_point(Point2::new(right, bottom)),
+        ])
+    }
+    // moves towards keeping the target inside the deadzone; call once per frame
+    pub fn follow(&mut self, target: Point2<f32>, delta_seconds: f32) {
+        // a negative or NaN deadzone would make clamp panic, so it counts as none
+        let half_deadzone = self.deadzone.map(|size| size.max(0.0) / 2.0);
+        let offset = target - self.position;
+
+        let excess = Vector2::new(
+            offset.x - offset.x.clamp(-half_deadzone.x, half_deadzone.x),
+            offset.y - offset.y.clamp(-half_deadzone.y, half_deadzone.y),
+        );
+
+        let amount = if self.smoothing > 0.0 {
+            1.0 - (-delta_seconds / self.smoothing).exp()
+        } else {
+            1.0
+        };
+
+        self.position += excess * amount;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn camera() -> Camera2d {
+        Camera2d::new(PixelDimensions {
+            width: 320,
+            height: 180,
+        })
+    }
+
+    fn close_to(point: Point2<f32>, x: f32, y: f32) -> bool {
+        (point.x - x).abs() < 1e-3 && (point.y - y).abs() < 1e-3
+    }
+
+    #[test]
+    fn the_camera_position_lands_on_the_viewport_center() {
+        let camera = Camera2d {
+            position: Point2::new(40.0, 30.0),
+            zoom: 2.0,
+            viewport_position: PixelCoordinates { x: 100, y: 20 },
+            viewport_dimensions: PixelDimensions {
+                width: 200,
+                height: 100,
+            },
+            ..camera()
+        };
+
+        assert!(close_to(
+            camera.world_to_screen(Point2::new(40.0, 30.0)),
+            200.0,
+            70.0
+        ));
+        assert!(close_to(
+            camera.world_to_screen(Point2::new(41.0, 30.0)),
+            202.0,
+            70.0
+        ));
+    }
+
+    #[test]
+    fn screen_to_world_undoes_world_to_screen() {
+        let world = Point2::new(12.5, -7.25);
+
+        for rotation in [0.0, 0.125, 0.5] {
+            let camera = Camera2d {
+                position: Point2::new(3.0, 4.0),
+                zoom: 3.0,
+                rotation,
+                pixel_snap: false,
+                ..camera()
+            };
+
+            let screen = camera.world_to_screen(world);
+            let back = camera.screen_to_world(screen).unwrap();
+            assert!(close_to(back, world.x, world.y), "{back} at {rotation}");
+        }
+    }
+
+    #[test]
+    fn a_zero_zoom_has_no_world_point() {
+        let camera = Camera2d {
+            zoom: 0.0,
+            ..camera()
+        };
+
+        assert!(camera.screen_to_world(Point2::new(10.0, 10.0)).is_none());
+        assert!(camera.visible_corners().is_none());
+    }
+
+    #[test]
+    fn pixel_snap_rounds_the_view_to_whole_pixels() {
+        let snapped = Camera2d {
+            position: Point2::new(160.3, 90.6),
+            ..camera()
+        };
+        let unsnapped = Camera2d {
+            pixel_snap: false,
+            ..snapped
+        };
+
+        assert!(close_to(
+            snapped.world_to_screen(Point2::new(0.0, 0.0)),
+            0.0,
+            -1.0
+        ));
+        assert!(close_to(
+            unsnapped.world_to_screen(Point2::new(0.0, 0.0)),
+            -0.3,
+            -0.6
+        ));
+
+        // rotated views can't line up with the pixel grid, so they are left as they are
+        let rotated = Camera2d {
+            rotation: 0.5,
+            ..snapped
+        };
+        assert!(close_to(
+            rotated.world_to_screen(Point2::new(160.3, 90.6)),
+            160.0,
+            90.0
+        ));
+        assert!(close_to(
+            rotated.world_to_screen(Point2::new(161.3, 90.6)),
+            159.0,
+            90.0
+        ));
+    }
+
+    #[test]
+    fn follow_treats_a_negative_or_nan_deadzone_as_none() {
+        for deadzone in [Vector2::new(-8.0, -4.0), Vector2::new(f32::NAN, f32::NAN)] {
+            let mut camera = Camera2d {
+                deadzone,
+                ..camera()
+            };
+
+            camera.follow(Point2::new(200.0, 100.0), 1.0 / 60.0);
+
+            assert_eq!(camera.position, Point2::new(200.0, 100.0));
+        }
+    }
+
+    #[test]
+    fn follow_stays_put_inside_the_deadzone() {
+        let mut camera = Camera2d {
+            deadzone: Vector2::new(20.0, 10.0),
+            ..camera()
+        };
+
+        camera.follow(Point2::new(168.0, 93.0), 1.0 / 60.0);
+        assert_eq!(camera.position, Point2::new(160.0, 90.0));
+
+        camera.follow(Point2::new(175.0, 90.0), 1.0 / 60.0);
+        assert_eq!(camera.position, Point2::new(165.0, 90.0));
+    }
+}
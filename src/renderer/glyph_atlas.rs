@@ -0,0 +1,115 @@
+use super::*;
+
+const INITIAL_DIMENSION: u32 = 256;
+const PADDING: u32 = 1; // transparent gap right of and below every glyph
+
+struct Shelf {
+    y: u32,
+    height: u32,
+    x: u32, // first free column
+}
+
+// a texture that glyphs are packed into as they are first drawn; it doubles in size when full
+pub struct GlyphAtlas {
+    texture_manager: TextureManager,
+    image: image::RgbaImage, // cpu copy, so the texture can be recreated at a larger size
+    texture: Rc<Texture>,
+    shelves: Vec<Shelf>,
+}
+
+impl GlyphAtlas {
+    pub(crate) fn new(texture_manager: TextureManager) -> Self {
+        let image = image::RgbaImage::new(INITIAL_DIMENSION, INITIAL_DIMENSION);
+        let texture = Rc::new(texture_manager.create_texture(image.clone()));
+
+        Self {
+            texture_manager,
+            image,
+            texture,
+            shelves: Vec::new(),
+        }
+    }
+    // replaced whenever the atlas grows, so don't hold on to it between insertions
+    pub fn texture(&self) -> &Rc<Texture> {
+        &self.texture
+    }
+    // None if the atlas can't grow large enough to fit the image
+    pub fn insert(&mut self, glyph_image: &image::RgbaImage) -> Option<SpriteTextureArea> {
+        let (width, height) = glyph_image.dimensions();
+
+        let (x, y) = loop {
+            if let Some(position) = self.allocate(width + PADDING, height + PADDING) {
+                break position;
+            }
+            if !self.grow() {
+                return None;
+            }
+        };
+
+        image::imageops::replace(&mut self.image, glyph_image, x as i64, y as i64);
+        let coordinates = PixelCoordinates {
+            x: x as i32,
+            y: y as i32,
+        };
+        self.texture_manager
+            .write_texture_area(&self.texture, coordinates, glyph_image);
+
+        Some(SpriteTextureArea {
+            coordinates,
+            dimensions: PixelDimensions { width, height },
+        })
+    }
+    // the shortest shelf the cell fits on, or a new shelf below the last one
+    fn allocate(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        let (atlas_width, atlas_height) = self.image.dimensions();
+
+        let shelf = self
+            .shelves
+            .iter_mut()
+            .filter(|shelf| shelf.height >= height && shelf.x + width <= atlas_width)
+            .min_by_key(|shelf| shelf.height);
+
+        if let Some(shelf) = shelf {
+            let position = (shelf.x, shelf.y);
+            shelf.x += width;
+            return Some(position);
+        }
+
+        let y = self
+            .shelves
+            .last()
+            .map(|shelf| shelf.y + shelf.height)
+            .unwrap_or(0);
+
+        if width > atlas_width || y + height > atlas_height {
+            return None;
+        }
+
+        self.shelves.push(Shelf {
+            y,
+            height,
+            x: width,
+        });
+        Some((0, y))
+    }
+    // doubles the shorter side; existing glyphs keep their pixel coordinates
+    fn grow(&mut self) -> bool {
+        let max_dimension = self.texture_manager.max_texture_dimension();
+        let (width, height) = self.image.dimensions();
+
+        let (new_width, new_height) = if width <= height && width < max_dimension {
+            ((width * 2).min(max_dimension), height)
+        } else if height < max_dimension {
+            (width, (height * 2).min(max_dimension))
+        } else {
+            return false;
+        };
+
+        let mut image = image::RgbaImage::new(new_width, new_height);
+        image::imageops::replace(&mut image, &self.image, 0, 0);
+
+        self.texture = Rc::new(self.texture_manager.create_texture(image.clone()));
+        self.image = image;
+        true
+    }
+}
@@ -0,0 +1,381 @@
+use super::*;
+
+use nalgebra::Point2;
+
+use std::{
+    future::Future,
+    path::{Path, PathBuf},
+    pin::pin,
+    sync::Arc,
+    task::{Context, Poll, Wake},
+};
+
+// set to regenerate reference images instead of comparing against them
+pub const UPDATE_ENV_VAR: &str = "ECLIPTIC_UPDATE_GOLDEN";
+
+// scripted draw calls rendered on a headless renderer and compared to a committed PNG
+pub struct GoldenScene {
+    pub name: &'static str,
+    pub dimensions: PixelDimensions,
+    pub tolerance: u8, // max per-channel difference before a pixel counts as mismatched
+    pub script: fn(&Renderer),
+}
+
+pub struct ImageComparison {
+    pub mismatched_pixels: u32,
+    pub max_channel_difference: u8,
+    pub diff: image::RgbaImage, // mismatched pixels in red over a dimmed copy of the reference
+}
+
+#[derive(Debug)]
+pub enum GoldenError {
+    MissingReference(PathBuf),
+    DimensionMismatch {
+        expected: PixelDimensions,
+        actual: PixelDimensions,
+    },
+    PixelMismatch {
+        mismatched_pixels: u32,
+        max_channel_difference: u8,
+        diff_path: PathBuf,
+    },
+    Image(image::ImageError),
+}
+
+impl std::fmt::Display for GoldenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GoldenError::MissingReference(path) => write!(
+                f,
+                "no reference image at {}, set {} to create it",
+                path.display(),
+                UPDATE_ENV_VAR
+            ),
+            GoldenError::DimensionMismatch { expected, actual } => write!(
+                f,
+                "expected {}x{} pixels, rendered {}x{}",
+                expected.width, expected.height, actual.width, actual.height
+            ),
+            GoldenError::PixelMismatch {
+                mismatched_pixels,
+                max_channel_difference,
+                diff_path,
+            } => write!(
+                f,
+                "{} pixels differ by up to {}, see {}",
+                mismatched_pixels,
+                max_channel_difference,
+                diff_path.display()
+            ),
+            GoldenError::Image(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for GoldenError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            GoldenError::Image(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<image::ImageError> for GoldenError {
+    fn from(error: image::ImageError) -> Self {
+        GoldenError::Image(error)
+    }
+}
+
+pub struct GoldenHarness {
+    pub reference_dir: PathBuf,
+    pub output_dir: PathBuf,
+}
+
+// tests/golden for references, target/golden for actual and diff images
+impl Default for GoldenHarness {
+    fn default() -> Self {
+        let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        Self::new(
+            manifest_dir.join("tests").join("golden"),
+            manifest_dir.join("target").join("golden"),
+        )
+    }
+}
+
+impl GoldenHarness {
+    pub fn new(reference_dir: impl Into<PathBuf>, output_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            reference_dir: reference_dir.into(),
+            output_dir: output_dir.into(),
+        }
+    }
+    pub fn render(scene: &GoldenScene) -> image::RgbaImage {
+        let renderer = block_on(Renderer::new_headless(scene.dimensions));
+        renderer.clear();
+        (scene.script)(&renderer);
+        renderer.read_pixels()
+    }
+    pub fn run(&self, scene: &GoldenScene) -> Result<(), GoldenError> {
+        let actual = GoldenHarness::render(scene);
+        self.check(scene.name, &actual, scene.tolerance)
+    }
+    pub fn run_all(&self, scenes: &[GoldenScene]) -> Vec<(&'static str, Result<(), GoldenError>)> {
+        scenes
+            .iter()
+            .map(|scene| (scene.name, self.run(scene)))
+            .collect()
+    }
+    pub fn check(
+        &self,
+        name: &str,
+        actual: &image::RgbaImage,
+        tolerance: u8,
+    ) -> Result<(), GoldenError> {
+        let reference_path = self.reference_dir.join(format!("{name}.png"));
+
+        if std::env::var_os(UPDATE_ENV_VAR).is_some() {
+            std::fs::create_dir_all(&self.reference_dir).map_err(image::ImageError::IoError)?;
+            actual.save(&reference_path)?;
+            return Ok(());
+        }
+
+        if !reference_path.exists() {
+            return Err(GoldenError::MissingReference(reference_path));
+        }
+
+        let expected = image::open(&reference_path)?.to_rgba8();
+
+        if expected.dimensions() != actual.dimensions() {
+            let (expected_width, expected_height) = expected.dimensions();
+            let (actual_width, actual_height) = actual.dimensions();
+            return Err(GoldenError::DimensionMismatch {
+                expected: PixelDimensions {
+                    width: expected_width,
+                    height: expected_height,
+                },
+                actual: PixelDimensions {
+                    width: actual_width,
+                    height: actual_height,
+                },
+            });
+        }
+
+        let comparison = compare_images(&expected, actual, tolerance);
+
+        if comparison.mismatched_pixels == 0 {
+            return Ok(());
+        }
+
+        std::fs::create_dir_all(&self.output_dir).map_err(image::ImageError::IoError)?;
+        let diff_path = self.output_dir.join(format!("{name}.diff.png"));
+        actual.save(self.output_dir.join(format!("{name}.actual.png")))?;
+        comparison.diff.save(&diff_path)?;
+
+        Err(GoldenError::PixelMismatch {
+            mismatched_pixels: comparison.mismatched_pixels,
+            max_channel_difference: comparison.max_channel_difference,
+            diff_path,
+        })
+    }
+}
+
+// expected and actual must have the same dimensions
+pub fn compare_images(
+    expected: &image::RgbaImage,
+    actual: &image::RgbaImage,
+    tolerance: u8,
+) -> ImageComparison {
+    let (width, height) = expected.dimensions();
+    let mut diff = image::RgbaImage::new(width, height);
+    let mut mismatched_pixels = 0;
+    let mut max_channel_difference = 0;
+
+    for ((expected_pixel, actual_pixel), diff_pixel) in expected
+        .pixels()
+        .zip(actual.pixels())
+        .zip(diff.pixels_mut())
+    {
+        let difference = expected_pixel
+            .0
+            .iter()
+            .zip(actual_pixel.0.iter())
+            .map(|(e, a)| e.abs_diff(*a))
+            .max()
+            .unwrap_or(0);
+
+        max_channel_difference = max_channel_difference.max(difference);
+
+        *diff_pixel = if difference > tolerance {
+            mismatched_pixels += 1;
+            image::Rgba([255, 0, 0, 255])
+        } else {
+            let [r, g, b, _] = expected_pixel.0;
+            image::Rgba([r / 4, g / 4, b / 4, 255])
+        };
+    }
+
+    ImageComparison {
+        mismatched_pixels,
+        max_channel_difference,
+        diff,
+    }
+}
+
+// minimal executor for driving the async renderer constructors from tests
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    struct ThreadWaker(std::thread::Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    let waker = Arc::new(ThreadWaker(std::thread::current())).into();
+    let mut context = Context::from_waker(&waker);
+    let mut future = pin!(future);
+
+    loop {
+        match future.as_mut().poll(&mut context) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => std::thread::park(),
+        }
+    }
+}
+
+// 8x8 quadrant pattern so flips, rotations and uv offsets are visible in the output
+pub fn quadrant_image(width: u32, height: u32) -> image::RgbaImage {
+    image::RgbaImage::from_fn(width, height, |x, y| {
+        match (x < width / 2, y < height / 2) {
+            (true, true) => image::Rgba([255, 0, 0, 255]),
+            (false, true) => image::Rgba([0, 255, 0, 255]),
+            (true, false) => image::Rgba([0, 0, 255, 255]),
+            (false, false) => image::Rgba([255, 255, 255, 255]),
+        }
+    })
+}
+
+// built-in scenes covering the sprite transforms, Sprite::create uv math and subsurfaces
+pub fn scenes() -> Vec<GoldenScene> {
+    vec![
+        GoldenScene {
+            name: "sprite_identity",
+            dimensions: PixelDimensions {
+                width: 32,
+                height: 32,
+            },
+            tolerance: 0,
+            script: |renderer| {
+                let texture = renderer.create_texture(quadrant_image(8, 8));
+                let sprite = renderer.create_sprite(texture, None);
+                renderer.draw_sprite(&sprite, &SpriteDraw::at(PixelCoordinates { x: 4, y: 4 }));
+            },
+        },
+        GoldenScene {
+            name: "sprite_scaled",
+            dimensions: PixelDimensions {
+                width: 32,
+                height: 32,
+            },
+            tolerance: 0,
+            script: |renderer| {
+                let texture = renderer.create_texture(quadrant_image(8, 8));
+                let sprite = renderer.create_sprite(texture, None);
+                renderer.draw_sprite(
+                    &sprite,
+                    &SpriteDraw {
+                        dimensions: Some(PixelDimensions {
+                            width: 32,
+                            height: 16,
+                        }),
+                        ..Default::default()
+                    },
+                );
+            },
+        },
+        GoldenScene {
+            name: "sprite_texture_area",
+            dimensions: PixelDimensions {
+                width: 16,
+                height: 16,
+            },
+            tolerance: 0,
+            script: |renderer| {
+                let texture = renderer.create_texture(quadrant_image(8, 8));
+                let sprite = renderer.create_sprite(
+                    texture,
+                    Some(SpriteTextureArea {
+                        coordinates: PixelCoordinates { x: 4, y: 0 },
+                        dimensions: PixelDimensions {
+                            width: 4,
+                            height: 8,
+                        },
+                    }),
+                );
+                renderer.draw_sprite(&sprite, &SpriteDraw::at(PixelCoordinates { x: 6, y: 4 }));
+            },
+        },
+        GoldenScene {
+            name: "sprite_rotated_quarter",
+            dimensions: PixelDimensions {
+                width: 32,
+                height: 32,
+            },
+            tolerance: 0,
+            script: |renderer| {
+                let texture = renderer.create_texture(quadrant_image(8, 8));
+                let sprite = renderer.create_sprite(texture, None);
+                renderer.draw_sprite(
+                    &sprite,
+                    &SpriteDraw {
+                        position: PixelCoordinates { x: 12, y: 12 },
+                        transform: Transform2d::rotation(0.25).around(Point2::new(4.0, 4.0)),
+                        ..Default::default()
+                    },
+                );
+            },
+        },
+        GoldenScene {
+            name: "subsurface",
+            dimensions: PixelDimensions {
+                width: 32,
+                height: 32,
+            },
+            tolerance: 0,
+            script: |renderer| {
+                let texture = renderer.create_texture(quadrant_image(8, 8));
+                let sprite = renderer.create_sprite(texture, None);
+                let subsurface = renderer.create_subsurface(16, 16);
+                subsurface.draw_sprite(&sprite, &SpriteDraw::at(PixelCoordinates { x: 8, y: 8 }));
+                renderer.draw_subsurface(
+                    &subsurface,
+                    &SpriteDraw::at(PixelCoordinates { x: 8, y: 8 }),
+                );
+            },
+        },
+        GoldenScene {
+            // each draw shows the subsurface as it was when drawn, not as it ends up
+            name: "subsurface_redrawn",
+            dimensions: PixelDimensions {
+                width: 16,
+                height: 8,
+            },
+            tolerance: 0,
+            script: |renderer| {
+                let subsurface = renderer.create_subsurface(8, 8);
+                let dimensions = subsurface.dimensions;
+                let origin = PixelCoordinates::top_left();
+
+                subsurface.fill_rect(origin, dimensions, Color::rgba(1.0, 0.0, 0.0, 1.0));
+                renderer.draw_subsurface(&subsurface, &SpriteDraw::at(origin));
+
+                subsurface.fill_rect(origin, dimensions, Color::rgba(0.0, 0.0, 1.0, 1.0));
+                renderer.draw_subsurface(
+                    &subsurface,
+                    &SpriteDraw::at(PixelCoordinates { x: 8, y: 0 }),
+                );
+            },
+        },
+    ]
+}
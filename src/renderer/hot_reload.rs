@@ -0,0 +1,290 @@
+use super::*;
+
+use bind_group_layouts::BindGroupLayouts;
+use material::Material;
+use pipelines::{PipelineKind, Pipelines};
+
+use notify::Watcher;
+
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    rc::Weak,
+    sync::mpsc,
+};
+
+// the renderer's own shader files, relative to the watched directory, and the pipeline each
+// set is concatenated into
+const BUILTIN_SHADERS: [(PipelineKind, &[&str]); 5] = [
+    (
+        PipelineKind::DrawSprite,
+        &["sprite2d.wgsl", "diffuse2d.wgsl"],
+    ),
+    (
+        PipelineKind::DrawIndexed,
+        &["sprite2d.wgsl", "palette2d.wgsl"],
+    ),
+    (PipelineKind::DrawSolid, &["solid2d.wgsl"]),
+    (PipelineKind::DrawPath, &["path2d.wgsl"]),
+    (PipelineKind::WindowSurfaceRefresh, &["window_refresh.wgsl"]),
+];
+
+const SPRITE_PRELUDE_FILE: &str = "sprite2d.wgsl";
+
+#[derive(Debug)]
+pub enum ShaderWatchError {
+    Io(std::io::Error),
+    Notify(notify::Error),
+}
+
+impl From<std::io::Error> for ShaderWatchError {
+    fn from(error: std::io::Error) -> Self {
+        ShaderWatchError::Io(error)
+    }
+}
+
+impl From<notify::Error> for ShaderWatchError {
+    fn from(error: notify::Error) -> Self {
+        ShaderWatchError::Notify(error)
+    }
+}
+
+// why a changed shader was not swapped in; line and column are 1-based, and 0 when the error
+// can't be placed in the file, as for read errors or pipelines wgpu rejects
+#[derive(Clone, Debug)]
+pub struct ShaderError {
+    pub path: PathBuf,
+    pub line: u32,
+    pub column: u32,
+    pub message: String,
+}
+
+impl std::fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}: {}",
+            self.path.display(),
+            self.line,
+            self.column,
+            self.message
+        )
+    }
+}
+
+impl ShaderError {
+    fn unplaced(path: &Path, message: String) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            line: 0,
+            column: 0,
+            message,
+        }
+    }
+}
+
+// joins the parts with newlines and validates the result with naga, placing any error in the
+// part it falls in
+fn compile(parts: &[(PathBuf, String)]) -> Result<String, ShaderError> {
+    let source = parts
+        .iter()
+        .map(|(_, part)| part.as_str())
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let located = |location: Option<naga::SourceLocation>, message: String| {
+        let (line, column) = location.map_or((0, 0), |location| {
+            (location.line_number, location.line_position)
+        });
+
+        let mut error = ShaderError::unplaced(&parts[0].0, message);
+        let mut first_line = 1;
+        for (path, part) in parts {
+            if line >= first_line {
+                error.path = path.clone();
+                error.line = line - first_line + 1;
+                error.column = column;
+            }
+            first_line += part.matches('\n').count() as u32 + 1;
+        }
+        error
+    };
+
+    let module = naga::front::wgsl::parse_str(&source)
+        .map_err(|error| located(error.location(&source), error.message().to_string()))?;
+
+    naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::empty(),
+    )
+    .validate(&module)
+    .map_err(|error| {
+        // the outer validation errors only name the function, the cause is further down
+        let mut message = error.to_string();
+        let mut cause = std::error::Error::source(&error);
+        while let Some(inner) = cause {
+            message = format!("{}: {}", message, inner);
+            cause = inner.source();
+        }
+        located(error.location(&source), message)
+    })?;
+
+    Ok(source)
+}
+
+fn read(path: &Path) -> Result<String, ShaderError> {
+    std::fs::read_to_string(path).map_err(|error| ShaderError::unplaced(path, error.to_string()))
+}
+
+struct WatchedMaterial {
+    path: PathBuf,
+    material: Weak<Material>,
+}
+
+// watches WGSL files and rebuilds the pipelines made from them when they change on disk; a
+// file that fails to compile leaves the last good pipeline in place
+pub(crate) struct ShaderWatcher {
+    watcher: notify::RecommendedWatcher,
+    events: mpsc::Receiver<notify::Result<notify::Event>>,
+    watched_directories: HashSet<PathBuf>,
+    builtin_directory: Option<PathBuf>,
+    materials: Vec<WatchedMaterial>,
+}
+
+impl ShaderWatcher {
+    pub fn new() -> Result<Self, ShaderWatchError> {
+        let (sender, events) = mpsc::channel();
+        let watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            let _ = sender.send(event);
+        })?;
+
+        Ok(Self {
+            watcher,
+            events,
+            watched_directories: HashSet::new(),
+            builtin_directory: None,
+            materials: Vec::new(),
+        })
+    }
+    // directories rather than files are watched, since editors often save by replacing the file
+    fn watch_directory(&mut self, directory: &Path) -> Result<(), ShaderWatchError> {
+        if self.watched_directories.insert(directory.to_path_buf()) {
+            self.watcher
+                .watch(directory, notify::RecursiveMode::NonRecursive)?;
+        }
+        Ok(())
+    }
+    pub fn watch_builtin(&mut self, directory: &Path) -> Result<(), ShaderWatchError> {
+        let directory = directory.canonicalize()?;
+        self.watch_directory(&directory)?;
+        self.builtin_directory = Some(directory);
+        Ok(())
+    }
+    pub fn watch_material(
+        &mut self,
+        material: &Rc<Material>,
+        path: &Path,
+    ) -> Result<(), ShaderWatchError> {
+        let path = path.canonicalize()?;
+        if let Some(directory) = path.parent() {
+            self.watch_directory(directory)?;
+        }
+        self.materials.push(WatchedMaterial {
+            path,
+            material: Rc::downgrade(material),
+        });
+        Ok(())
+    }
+    fn changed_paths(&self) -> HashSet<PathBuf> {
+        self.events
+            .try_iter()
+            .filter_map(Result::ok)
+            .filter(|event| event.kind.is_create() || event.kind.is_modify())
+            .flat_map(|event| event.paths)
+            .filter_map(|path| path.canonicalize().ok())
+            .collect()
+    }
+    // rebuilds whatever the files changed since the last call feed into
+    pub fn poll(
+        &mut self,
+        device: &wgpu::Device,
+        bind_group_layouts: &BindGroupLayouts,
+        pipelines: &Pipelines,
+    ) -> Vec<ShaderError> {
+        let changed = self.changed_paths();
+        if changed.is_empty() {
+            return Vec::new();
+        }
+
+        let mut errors = Vec::new();
+
+        // materials are appended to the prelude as it is on disk once the built-in shaders
+        // are watched
+        let mut prelude = (
+            PathBuf::from(SPRITE_PRELUDE_FILE),
+            shaders::SPRITE_PRELUDE.to_string(),
+        );
+        let mut prelude_changed = false;
+
+        if let Some(directory) = &self.builtin_directory {
+            let prelude_path = directory.join(SPRITE_PRELUDE_FILE);
+            prelude_changed = changed.contains(&prelude_path);
+            // a read error here is reported by the draw_sprite rebuild below
+            if let Ok(source) = read(&prelude_path) {
+                prelude = (prelude_path, source);
+            }
+
+            for (kind, files) in BUILTIN_SHADERS {
+                let paths: Vec<PathBuf> = files.iter().map(|file| directory.join(file)).collect();
+                if !paths.iter().any(|path| changed.contains(path)) {
+                    continue;
+                }
+
+                let result = paths
+                    .into_iter()
+                    .map(|path| read(&path).map(|source| (path, source)))
+                    .collect::<Result<Vec<_>, _>>()
+                    .and_then(|parts| {
+                        let source = compile(&parts)?;
+                        pipelines
+                            .rebuild(device, bind_group_layouts, kind, &source)
+                            .map_err(|error| ShaderError::unplaced(&parts[0].0, error.to_string()))
+                    });
+
+                if let Err(error) = result {
+                    errors.push(error);
+                }
+            }
+        }
+
+        self.materials
+            .retain(|watched| watched.material.strong_count() > 0);
+
+        for watched in &self.materials {
+            if !prelude_changed && !changed.contains(&watched.path) {
+                continue;
+            }
+            let Some(material) = watched.material.upgrade() else {
+                continue;
+            };
+
+            let result = read(&watched.path).and_then(|source| {
+                let parts = [prelude.clone(), (watched.path.clone(), source)];
+                let source = compile(&parts)?;
+                material
+                    .rebuild(device, &source)
+                    .map_err(|error| ShaderError::unplaced(&watched.path, error.to_string()))
+            });
+
+            if let Err(error) = result {
+                errors.push(error);
+            }
+        }
+
+        for error in &errors {
+            log::error!("{}", error);
+        }
+
+        errors
+    }
+}
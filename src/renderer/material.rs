@@ -0,0 +1,172 @@
+use super::*;
+
+use bind_group_layouts::BindGroupLayouts;
+use pipelines::{checked, SpritePipelines};
+
+use std::{
+    any::{type_name, TypeId},
+    cell::RefCell,
+};
+
+// a custom fragment shader for draw_sprite, appended to sprite2d.wgsl; it sees the sprite's
+// texture at @group(0) and its own bind group at @group(2):
+//   @binding(0) the uniform, a struct matching the Pod type the material was created with
+//   @binding(1 + 2 * i) and @binding(2 + 2 * i) the i-th extra texture and its sampler
+pub struct Material {
+    pub label: String,
+    queue: Rc<wgpu::Queue>,
+    uniforms_type: TypeId,
+    uniforms_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+    pipeline_layout: wgpu::PipelineLayout,
+    pipelines: RefCell<Rc<SpritePipelines>>, // replaced when hot reloading
+    _textures: Vec<Rc<Texture>>,
+}
+
+impl Material {
+    pub(crate) fn create<U: bytemuck::Pod>(
+        device: &wgpu::Device,
+        queue: Rc<wgpu::Queue>,
+        bind_group_layouts: &BindGroupLayouts,
+        label: &str,
+        fragment_source: &str,
+        uniforms: &U,
+        textures: &[Rc<Texture>],
+    ) -> Result<Self, wgpu::Error> {
+        let mut layout_entries = vec![wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        }];
+        for index in 0..textures.len() as u32 {
+            layout_entries.push(wgpu::BindGroupLayoutEntry {
+                binding: 1 + 2 * index,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            });
+            layout_entries.push(wgpu::BindGroupLayoutEntry {
+                binding: 2 + 2 * index,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::NonFiltering),
+                count: None,
+            });
+        }
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &layout_entries,
+            label: Some("material bind group layout"),
+        });
+
+        // uniform bindings are kept to whole 16 byte rows
+        let bytes = bytemuck::bytes_of(uniforms);
+        let mut contents = bytes.to_vec();
+        contents.resize(bytes.len().max(16).next_multiple_of(16), 0);
+
+        let uniforms_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("material uniforms buffer"),
+            contents: &contents,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let mut entries = vec![wgpu::BindGroupEntry {
+            binding: 0,
+            resource: uniforms_buffer.as_entire_binding(),
+        }];
+        for (index, texture) in textures.iter().enumerate() {
+            let index = index as u32;
+            entries.push(wgpu::BindGroupEntry {
+                binding: 1 + 2 * index,
+                resource: wgpu::BindingResource::TextureView(&texture.wgpu_texture_view),
+            });
+            entries.push(wgpu::BindGroupEntry {
+                binding: 2 + 2 * index,
+                resource: wgpu::BindingResource::Sampler(&texture.wgpu_sampler),
+            });
+        }
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("material bind group"),
+            layout: &bind_group_layout,
+            entries: &entries,
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("material pipeline layout"),
+            bind_group_layouts: &[
+                &bind_group_layouts.texture,
+                &bind_group_layouts.surface_uniforms,
+                &bind_group_layout,
+            ],
+            push_constant_ranges: &[],
+        });
+
+        // user WGSL, so a shader wgpu rejects is handed back rather than left to the device
+        let source = format!("{}\n{}", shaders::SPRITE_PRELUDE, fragment_source);
+        let pipelines = checked(device, || {
+            let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some(label),
+                source: wgpu::ShaderSource::Wgsl(source.into()),
+            });
+
+            SpritePipelines::from_shader(device, label, &pipeline_layout, &shader)
+        })?;
+
+        Ok(Self {
+            label: label.to_string(),
+            queue,
+            uniforms_type: TypeId::of::<U>(),
+            uniforms_buffer,
+            bind_group,
+            #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+            pipeline_layout,
+            pipelines: RefCell::new(Rc::new(pipelines)),
+            _textures: textures.to_vec(),
+        })
+    }
+    // takes effect from the next flush, so every draw queued before then sees the new values;
+    // panics when U isn't the type the material was created with
+    pub fn set_uniforms<U: bytemuck::Pod>(&self, uniforms: &U) {
+        assert!(
+            TypeId::of::<U>() == self.uniforms_type,
+            "material {} was not created with uniforms of type {}",
+            self.label,
+            type_name::<U>()
+        );
+
+        self.queue
+            .write_buffer(&self.uniforms_buffer, 0, bytemuck::bytes_of(uniforms));
+    }
+    pub(crate) fn pipelines(&self) -> Rc<SpritePipelines> {
+        Rc::clone(&self.pipelines.borrow())
+    }
+    // source is the whole shader, prelude included; the old pipelines stay when wgpu rejects it
+    #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+    pub(crate) fn rebuild(&self, device: &wgpu::Device, source: &str) -> Result<(), wgpu::Error> {
+        let pipelines = checked(device, || {
+            let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some(&self.label),
+                source: wgpu::ShaderSource::Wgsl(source.into()),
+            });
+
+            SpritePipelines::from_shader(device, &self.label, &self.pipeline_layout, &shader)
+        })?;
+
+        *self.pipelines.borrow_mut() = Rc::new(pipelines);
+
+        Ok(())
+    }
+    pub(crate) fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+}
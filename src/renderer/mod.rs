@@ -1,6 +1,6 @@
-use std::{any::Any, rc::Rc};
+use std::rc::Rc;
 
-use pixel_surface::{Sprite, SpriteTextureArea};
+use pixel_surface::{Sprite, SpritePivot, SpriteTextureArea};
 use wgpu::util::DeviceExt;
 
 use crate::window::*;
@@ -15,10 +15,65 @@ mod shaders;
 
 mod bind_group_layouts;
 
-use self::{bind_group_layouts::BindGroupLayouts, pixel_surface::SpriteRotation, shaders::Shaders};
+mod sprite_batch;
+
+mod readback;
+
+mod primitives;
+
+pub mod texture_atlas;
+use texture_atlas::{TextureAtlas, TextureAtlasBuilder, TextureAtlasError};
+
+pub mod animation;
+
+pub mod aseprite;
+use aseprite::{AsepriteError, AsepriteFile, AsepriteSheet};
+
+pub mod sprite_sheet;
+use sprite_sheet::{SpriteSheet, SpriteSheetError};
+
+pub mod bitmap_font;
+use bitmap_font::{BitmapFont, BitmapFontError};
+
+pub mod glyph_atlas;
+
+pub mod truetype_font;
+use truetype_font::{TrueTypeFont, TrueTypeFontError};
+
+pub mod text_layout;
+use text_layout::{FontSource, TextAlignment, TextLayout};
+pub mod vector_path;
+use vector_path::{StrokeStyle, VectorPath};
+pub mod transform;
+use transform::Transform2d;
+pub mod camera;
+use camera::Camera2d;
+pub mod presentation;
+use presentation::{PresentationRect, PresentationScaling, SurfaceResolution};
+pub mod post_process;
+use post_process::{BuiltinEffect, PostEffect, PostProcessor};
+pub mod material;
+use material::Material;
+pub mod palette;
+use palette::{IndexedTexture, IndexedTextureError, Palette};
+pub mod retained;
+use retained::RetainedSprite;
+#[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+pub mod hot_reload;
+#[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+use hot_reload::{ShaderError, ShaderWatchError, ShaderWatcher};
+
+#[cfg(all(feature = "golden", not(target_arch = "wasm32")))]
+pub mod golden;
+
+use self::{
+    bind_group_layouts::BindGroupLayouts,
+    pixel_surface::{SpriteColor, SpriteDraw},
+    shaders::Shaders,
+};
 
 #[repr(C)]
-#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct PixelCoordinates {
     pub x: i32,
     pub y: i32,
@@ -39,7 +94,39 @@ impl PixelCoordinates {
     }
 }
 
-#[derive(Clone, Copy)]
+// linear rgba, straight alpha
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Color {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+impl Color {
+    pub const WHITE: Color = Color::rgba(1.0, 1.0, 1.0, 1.0);
+    pub const BLACK: Color = Color::rgba(0.0, 0.0, 0.0, 1.0);
+    pub const TRANSPARENT: Color = Color::rgba(0.0, 0.0, 0.0, 0.0);
+
+    pub const fn rgba(r: f32, g: f32, b: f32, a: f32) -> Self {
+        Self { r, g, b, a }
+    }
+    // rgb are sRGB encoded as in image files and color pickers, alpha is linear
+    pub fn from_rgba8(r: u8, g: u8, b: u8, a: u8) -> Self {
+        fn to_linear(value: u8) -> f32 {
+            let value = value as f32 / 255.0;
+            if value <= 0.04045 {
+                value / 12.92
+            } else {
+                ((value + 0.055) / 1.055).powf(2.4)
+            }
+        }
+        Self::rgba(to_linear(r), to_linear(g), to_linear(b), a as f32 / 255.0)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct PixelDimensions {
     pub width: u32,
     pub height: u32,
@@ -53,27 +140,6 @@ pub struct Vertex {
     pub z: f32,
 }
 
-impl Vertex {
-    fn desc() -> wgpu::VertexBufferLayout<'static> {
-        wgpu::VertexBufferLayout {
-            array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
-            step_mode: wgpu::VertexStepMode::Vertex,
-            attributes: &[
-                wgpu::VertexAttribute {
-                    offset: 0,
-                    shader_location: 0,
-                    format: wgpu::VertexFormat::Float32x3,
-                },
-                wgpu::VertexAttribute {
-                    offset: std::mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
-                    shader_location: 1,
-                    format: wgpu::VertexFormat::Float32x3,
-                },
-            ],
-        }
-    }
-}
-
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct RenderPlaneCoordinates {
@@ -111,8 +177,8 @@ pub struct RenderPlaneDimensions {
     pub height: f32,
 }
 
-impl RenderPlaneDimensions {
-    pub fn default() -> Self {
+impl Default for RenderPlaneDimensions {
+    fn default() -> Self {
         Self {
             width: 0.0,
             height: 0.0,
@@ -120,7 +186,8 @@ impl RenderPlaneDimensions {
     }
 }
 
-struct TextureManager {
+#[derive(Clone)]
+pub(crate) struct TextureManager {
     device: Rc<wgpu::Device>,
     queue: Rc<wgpu::Queue>,
     bind_group_layout: Rc<wgpu::BindGroupLayout>,
@@ -154,6 +221,55 @@ impl TextureManager {
             &self.bind_group_layout,
         )
     }
+    pub fn create_indexed_texture(&self, indices: &image::GrayImage) -> Texture {
+        Texture::create_indexed(&self.device, &self.queue, indices, &self.bind_group_layout)
+    }
+    // keeps the indices of a palette PNG rather than expanding them to colors
+    pub fn load_indexed_texture(&self, path: &str) -> Result<IndexedTexture, IndexedTextureError> {
+        let file = std::io::BufReader::new(std::fs::File::open(path)?);
+        let (indices, colors) = palette::decode_indexed_png(file)?;
+
+        Ok(IndexedTexture {
+            indices: Rc::new(self.create_indexed_texture(&indices)),
+            palette: Palette::new(self.clone(), &colors),
+        })
+    }
+    // overwrites part of an existing texture, e.g. newly rasterized glyphs in an atlas
+    pub fn write_texture_area(
+        &self,
+        texture: &Texture,
+        coordinates: PixelCoordinates,
+        texture_rgba: &image::RgbaImage,
+    ) {
+        let (width, height) = texture_rgba.dimensions();
+
+        self.queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &texture.wgpu_texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: coordinates.x as u32,
+                    y: coordinates.y as u32,
+                    z: 0,
+                },
+                aspect: wgpu::TextureAspect::All,
+            },
+            texture_rgba,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * width),
+                rows_per_image: Some(height),
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+    pub fn max_texture_dimension(&self) -> u32 {
+        self.device.limits().max_texture_dimension_2d
+    }
 }
 
 pub struct Texture {
@@ -171,11 +287,44 @@ impl Texture {
         texture_rgba: image::ImageBuffer<image::Rgba<u8>, Vec<u8>>,
         bind_group_layout: &wgpu::BindGroupLayout,
     ) -> Texture {
-        let dimensions = {
-            let (width, height) = texture_rgba.dimensions();
-            PixelDimensions { width, height }
-        };
-
+        let (width, height) = texture_rgba.dimensions();
+        Self::from_pixels(
+            device,
+            queue,
+            PixelDimensions { width, height },
+            wgpu::TextureFormat::Rgba8UnormSrgb,
+            4,
+            &texture_rgba,
+            bind_group_layout,
+        )
+    }
+    // one palette index per texel, read back in shaders as index / 255 in the red channel
+    pub fn create_indexed(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        indices: &image::GrayImage,
+        bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Texture {
+        let (width, height) = indices.dimensions();
+        Self::from_pixels(
+            device,
+            queue,
+            PixelDimensions { width, height },
+            wgpu::TextureFormat::R8Unorm,
+            1,
+            indices,
+            bind_group_layout,
+        )
+    }
+    fn from_pixels(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        dimensions: PixelDimensions,
+        format: wgpu::TextureFormat,
+        bytes_per_pixel: u32,
+        pixels: &[u8],
+        bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Texture {
         let texture_size = wgpu::Extent3d {
             width: dimensions.width,
             height: dimensions.height,
@@ -187,9 +336,10 @@ impl Texture {
             mip_level_count: 1,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            format,
             usage: wgpu::TextureUsages::TEXTURE_BINDING
                 | wgpu::TextureUsages::COPY_DST
+                | wgpu::TextureUsages::COPY_SRC
                 | wgpu::TextureUsages::RENDER_ATTACHMENT,
             view_formats: &[],
         });
@@ -200,10 +350,10 @@ impl Texture {
                 origin: wgpu::Origin3d::ZERO,
                 aspect: wgpu::TextureAspect::All,
             },
-            &texture_rgba,
+            pixels,
             wgpu::ImageDataLayout {
                 offset: 0,
-                bytes_per_row: Some(4 * dimensions.width),
+                bytes_per_row: Some(bytes_per_pixel * dimensions.width),
                 rows_per_image: Some(dimensions.height),
             },
             texture_size,
@@ -223,7 +373,7 @@ impl Texture {
         });
 
         let wgpu_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &bind_group_layout,
+            layout: bind_group_layout,
             entries: &[
                 wgpu::BindGroupEntry {
                     binding: 0,
@@ -249,9 +399,9 @@ impl Texture {
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
-struct TextureCoordinates {
-    u: f32,
-    v: f32,
+pub struct TextureCoordinates {
+    pub u: f32,
+    pub v: f32,
 }
 
 impl TextureCoordinates {
@@ -298,19 +448,30 @@ impl WindowRefreshVertex {
     }
 }
 
+struct OutputSurface {
+    surface: wgpu::Surface,
+    config: wgpu::SurfaceConfiguration,
+}
+
 pub struct Renderer {
-    device: wgpu::Device,
-    queue: wgpu::Queue,
-    bind_group_layouts: BindGroupLayouts,
+    device: Rc<wgpu::Device>,
+    queue: Rc<wgpu::Queue>,
+    bind_group_layouts: Rc<BindGroupLayouts>,
     texture_manager: TextureManager,
-    pipelines: Pipelines,
-    output_surface: wgpu::Surface,
+    pipelines: Rc<Pipelines>,
+    output_surface: Option<OutputSurface>, // None when headless
     swap_surface: PixelSurface,
+    presentation_scaling: PresentationScaling,
+    border_color: Color, // around the swap surface in the window
+    surface_resolution: SurfaceResolution,
+    post_processor: PostProcessor,
+    #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+    shader_watcher: Option<ShaderWatcher>, // created by the first watch
 }
 
 impl Renderer {
     pub async fn new(window: &Window) -> Renderer {
-        let window: winit::window::Window = window.winit_window();
+        let window = window.winit_window();
         let (width, height) = {
             let winit::dpi::PhysicalSize { width, height } = window.inner_size();
             (width, height)
@@ -322,7 +483,8 @@ impl Renderer {
             gles_minor_version: wgpu::Gles3MinorVersion::Automatic,
         });
 
-        let output_surface = unsafe { instance.create_surface(&window) }
+        // the window has to outlive the renderer, as the surface draws into it
+        let surface = unsafe { instance.create_surface(window) }
             .expect("Couldn't create surface from window.");
 
         let adapter = instance
@@ -334,31 +496,14 @@ impl Renderer {
             .await
             .expect("Could not obtain an appropriate adapter.");
 
-        let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    features: wgpu::Features::empty(),
-                    // WebGL doesn't support all of wgpu's features, so if
-                    // we're building for the web, we'll have to disable some.
-                    limits: if cfg!(target_arch = "wasm32") {
-                        wgpu::Limits::downlevel_webgl2_defaults()
-                    } else {
-                        wgpu::Limits::default()
-                    },
-                    label: None,
-                },
-                None, // Trace path
-            )
-            .await
-            .expect("Could not obtain an appropriate device and queue.");
+        let (device, queue) = Renderer::request_device(&adapter).await;
 
-        let surface_caps = output_surface.get_capabilities(&adapter);
+        let surface_caps = surface.get_capabilities(&adapter);
         let surface_format = surface_caps
             .formats
             .iter()
             .copied()
-            .filter(|f| f.is_srgb())
-            .next()
+            .find(|f| f.is_srgb())
             .unwrap_or(surface_caps.formats[0]);
 
         let config = wgpu::SurfaceConfiguration {
@@ -370,20 +515,119 @@ impl Renderer {
             alpha_mode: surface_caps.alpha_modes[0],
             view_formats: vec![],
         };
-        output_surface.configure(&device, &config);
+        surface.configure(&device, &config);
+
+        Renderer::from_device(
+            device,
+            queue,
+            Some(OutputSurface { surface, config }),
+            PixelDimensions { width, height },
+        )
+    }
+    // renders into the swap surface only, with no window or wgpu::Surface behind it
+    pub async fn new_headless(dimensions: PixelDimensions) -> Renderer {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::VULKAN | wgpu::Backends::GL,
+            flags: wgpu::InstanceFlags::empty(),
+            dx12_shader_compiler: wgpu::Dx12Compiler::Fxc,
+            gles_minor_version: wgpu::Gles3MinorVersion::Automatic,
+        });
+
+        // prefer a software adapter (lavapipe, llvmpipe) so machines without a GPU can render
+        let fallback_adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptionsBase {
+                power_preference: wgpu::PowerPreference::LowPower,
+                force_fallback_adapter: true,
+                compatible_surface: None,
+            })
+            .await;
+
+        let adapter = match fallback_adapter {
+            Some(adapter) => adapter,
+            None => instance
+                .request_adapter(&wgpu::RequestAdapterOptionsBase {
+                    power_preference: wgpu::PowerPreference::LowPower,
+                    force_fallback_adapter: false,
+                    compatible_surface: None,
+                })
+                .await
+                .expect("Could not obtain an appropriate adapter."),
+        };
+
+        let (device, queue) = Renderer::request_device(&adapter).await;
+
+        Renderer::from_device(device, queue, None, dimensions)
+    }
+    async fn request_device(adapter: &wgpu::Adapter) -> (wgpu::Device, wgpu::Queue) {
+        adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    features: wgpu::Features::empty(),
+                    // WebGL doesn't support all of wgpu's features, so if
+                    // we're building for the web, we'll have to disable some.
+                    // downlevel adapters, like the GL fallback for headless rendering, would
+                    // reject the full defaults too
+                    limits: if cfg!(target_arch = "wasm32")
+                        || !adapter.get_downlevel_capabilities().is_webgpu_compliant()
+                    {
+                        wgpu::Limits::downlevel_webgl2_defaults().using_resolution(adapter.limits())
+                    } else {
+                        wgpu::Limits::default()
+                    },
+                    label: None,
+                },
+                None, // Trace path
+            )
+            .await
+            .expect("Could not obtain an appropriate device and queue.")
+    }
+    fn from_device(
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+        output_surface: Option<OutputSurface>,
+        dimensions: PixelDimensions,
+    ) -> Renderer {
+        let device = Rc::new(device);
+        let queue = Rc::new(queue);
+
+        // headless renderers never blit, so the pixel surface format stands in
+        let output_format = match &output_surface {
+            Some(output_surface) => output_surface.config.format,
+            None => wgpu::TextureFormat::Rgba8UnormSrgb,
+        };
 
         let shaders = Shaders::create(&device);
         let bind_group_layouts = Rc::new(bind_group_layouts::BindGroupLayouts::create(&device));
 
-        let pipelines = Pipelines::create(&device, &shaders, &bind_group_layouts, &config);
-
-        let swap_surface = PixelSurface::new(
+        let pipelines = Rc::new(Pipelines::create(
             &device,
-            &queue,
+            &shaders,
             &bind_group_layouts,
-            &pipelines.draw_sprite,
-            width,
-            height,
+            output_format,
+        ));
+
+        let texture_manager = TextureManager::create(
+            Rc::clone(&device),
+            Rc::clone(&queue),
+            Rc::clone(&bind_group_layouts.texture),
+        );
+
+        let swap_surface = PixelSurface::new(
+            Rc::clone(&device),
+            Rc::clone(&queue),
+            Rc::clone(&bind_group_layouts),
+            Rc::clone(&pipelines),
+            texture_manager.clone(),
+            dimensions.width,
+            dimensions.height,
+        );
+
+        let post_processor = PostProcessor::new(
+            Rc::clone(&device),
+            Rc::clone(&queue),
+            Rc::clone(&bind_group_layouts),
+            Rc::clone(&pipelines),
+            texture_manager.clone(),
         );
 
         Self {
@@ -392,37 +636,227 @@ impl Renderer {
             output_surface,
             swap_surface,
             bind_group_layouts,
+            texture_manager,
             pipelines,
+            presentation_scaling: PresentationScaling::default(),
+            border_color: Color::BLACK,
+            surface_resolution: SurfaceResolution::default(),
+            post_processor,
+            #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+            shader_watcher: None,
             //shaders,
-            //config,
         }
     }
+    pub fn is_headless(&self) -> bool {
+        self.output_surface.is_none()
+    }
+    pub fn swap_surface(&self) -> &PixelSurface {
+        &self.swap_surface
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn read_pixels(&self) -> image::RgbaImage {
+        self.swap_surface.read_pixels()
+    }
+    pub async fn read_pixels_async(&self) -> image::RgbaImage {
+        self.swap_surface.read_pixels_async().await
+    }
     pub fn clear(&self) {
         self.swap_surface.clear();
     }
+    // WGSL with an fs_main entry point, appended to the declarations in post_effect.wgsl; params
+    // is the initial contents of the effect's uniform at @group(1) @binding(1); a shader wgpu
+    // rejects comes back as the error
+    pub fn create_post_effect(
+        &self,
+        label: &str,
+        fragment_source: &str,
+        params: &[u8],
+        extra_texture: Option<Rc<Texture>>,
+    ) -> Result<PostEffect, wgpu::Error> {
+        self.post_processor
+            .create_effect(label, fragment_source, params, extra_texture)
+    }
+    pub fn create_builtin_effect(&self, effect: BuiltinEffect) -> PostEffect {
+        self.post_processor.create_builtin_effect(effect)
+    }
+    // run in order by present, between the last draw and the blit to the window; the swap
+    // surface itself, as read back by read_pixels, is left as drawn
+    pub fn post_effects(&mut self) -> &mut Vec<PostEffect> {
+        &mut self.post_processor.effects
+    }
+    // the time effects see in their globals, in seconds
+    pub fn set_post_effect_time(&self, seconds: f32) {
+        self.post_processor.time.set(seconds);
+    }
+    // fragment_source is WGSL with an fs_main entry point, appended to sprite2d.wgsl; see
+    // Material for the bindings it can declare; a shader wgpu rejects comes back as the error,
+    // except on the web, where the check resolves too late and the device's handler gets it
+    pub fn create_material<U: bytemuck::Pod>(
+        &self,
+        label: &str,
+        fragment_source: &str,
+        uniforms: &U,
+        textures: &[Rc<Texture>],
+    ) -> Result<Rc<Material>, wgpu::Error> {
+        Material::create(
+            &self.device,
+            Rc::clone(&self.queue),
+            &self.bind_group_layouts,
+            label,
+            fragment_source,
+            uniforms,
+            textures,
+        )
+        .map(Rc::new)
+    }
+    #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+    fn shader_watcher(&mut self) -> Result<&mut ShaderWatcher, ShaderWatchError> {
+        if self.shader_watcher.is_none() {
+            self.shader_watcher = Some(ShaderWatcher::new()?);
+        }
+        Ok(self.shader_watcher.as_mut().unwrap())
+    }
+    // directory holds this crate's own shaders, normally its src/renderer; edits are picked up
+    // by reload_shaders
+    #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+    pub fn watch_shaders(&mut self, directory: &str) -> Result<(), ShaderWatchError> {
+        self.shader_watcher()?
+            .watch_builtin(std::path::Path::new(directory))
+    }
+    // path holds the fragment source the material was created from
+    #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+    pub fn watch_material(
+        &mut self,
+        material: &Rc<Material>,
+        path: &str,
+    ) -> Result<(), ShaderWatchError> {
+        self.shader_watcher()?
+            .watch_material(material, std::path::Path::new(path))
+    }
+    // call once a frame; rebuilds the pipelines of watched shaders that changed on disk, and
+    // returns the compile errors of those that kept their last good pipeline
+    #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+    pub fn reload_shaders(&mut self) -> Vec<ShaderError> {
+        match &mut self.shader_watcher {
+            Some(watcher) => watcher.poll(&self.device, &self.bind_group_layouts, &self.pipelines),
+            None => Vec::new(),
+        }
+    }
+    pub fn set_presentation_scaling(&mut self, scaling: PresentationScaling) {
+        self.presentation_scaling = scaling;
+    }
+    pub fn set_border_color(&mut self, color: Color) {
+        self.border_color = color;
+    }
+    // reconfigures the window's surface, then resizes the swap surface as the resolution policy
+    // says, in which case its contents are lost; zero sizes, as when minimized, are ignored
+    pub fn resize(&mut self, size: winit::dpi::PhysicalSize<u32>) {
+        if size.width == 0 || size.height == 0 {
+            return;
+        }
+
+        let max_dimension = self.texture_manager.max_texture_dimension();
+        let window = PixelDimensions {
+            width: size.width.min(max_dimension),
+            height: size.height.min(max_dimension),
+        };
+
+        if let Some(output_surface) = &mut self.output_surface {
+            output_surface.config.width = window.width;
+            output_surface.config.height = window.height;
+            output_surface
+                .surface
+                .configure(&self.device, &output_surface.config);
+        }
+
+        self.resize_swap_surface(window);
+    }
+    // applied straight away, against the current window size
+    pub fn set_surface_resolution(&mut self, resolution: SurfaceResolution) {
+        self.surface_resolution = resolution;
+        self.resize_swap_surface(self.window_dimensions());
+    }
+    fn resize_swap_surface(&mut self, window: PixelDimensions) {
+        let max_dimension = self.texture_manager.max_texture_dimension();
+        let dimensions = self.surface_resolution.dimensions(window);
+        let dimensions = PixelDimensions {
+            width: dimensions.width.min(max_dimension),
+            height: dimensions.height.min(max_dimension),
+        };
+
+        if dimensions == self.swap_surface.dimensions {
+            return;
+        }
+
+        let camera = self.swap_surface.camera();
+        let mut swap_surface = self.create_subsurface(dimensions.width, dimensions.height);
+        swap_surface.adopt_retained(&self.swap_surface);
+        self.swap_surface = swap_surface;
+        self.swap_surface.set_camera(camera);
+    }
+    // headless renderers have no window, so the swap surface stands in for it
+    fn window_dimensions(&self) -> PixelDimensions {
+        match &self.output_surface {
+            Some(output_surface) => PixelDimensions {
+                width: output_surface.config.width,
+                height: output_surface.config.height,
+            },
+            None => self.swap_surface.dimensions,
+        }
+    }
+    // where present puts the swap surface in the window, in window pixels
+    pub fn presentation_rect(&self) -> PresentationRect {
+        self.presentation_scaling
+            .rect(self.swap_surface.dimensions, self.window_dimensions())
+    }
+    // maps a cursor position in physical window pixels to the swap surface pixel under it,
+    // None over the borders
+    pub fn window_to_surface(&self, window_x: f32, window_y: f32) -> Option<PixelCoordinates> {
+        self.presentation_rect()
+            .window_to_surface(self.swap_surface.dimensions, window_x, window_y)
+    }
     pub fn present(&self) {
-        let output = self.output_surface.get_current_texture().unwrap();
+        self.swap_surface.flush();
+
+        let Some(output_surface) = &self.output_surface else {
+            return;
+        };
+
+        let presented = self.post_processor.run(&self.swap_surface);
+
+        let output = output_surface.surface.get_current_texture().unwrap();
         let view = output.texture.create_view(&wgpu::TextureViewDescriptor {
             ..Default::default()
         });
 
         let device = &self.device;
 
+        // the presentation rect in clip space; any part outside the window is clipped away
+        let window = self.window_dimensions();
+        let rect = self.presentation_rect();
+        let left = -1.0 + rect.x / window.width as f32 * 2.0;
+        let right = -1.0 + (rect.x + rect.width) / window.width as f32 * 2.0;
+        let top = 1.0 - rect.y / window.height as f32 * 2.0;
+        let bottom = 1.0 - (rect.y + rect.height) / window.height as f32 * 2.0;
+
         let vertices = [
             WindowRefreshVertex {
-                output_coords: RenderPlaneCoordinates::top_left(),
+                output_coords: RenderPlaneCoordinates { x: left, y: top },
                 texture_coords: TextureCoordinates::top_left(),
             },
             WindowRefreshVertex {
-                output_coords: RenderPlaneCoordinates::top_right(),
+                output_coords: RenderPlaneCoordinates { x: right, y: top },
                 texture_coords: TextureCoordinates::top_right(),
             },
             WindowRefreshVertex {
-                output_coords: RenderPlaneCoordinates::bottom_left(),
+                output_coords: RenderPlaneCoordinates { x: left, y: bottom },
                 texture_coords: TextureCoordinates::bottom_left(),
             },
             WindowRefreshVertex {
-                output_coords: RenderPlaneCoordinates::bottom_right(),
+                output_coords: RenderPlaneCoordinates {
+                    x: right,
+                    y: bottom,
+                },
                 texture_coords: TextureCoordinates::bottom_right(),
             },
         ];
@@ -442,6 +876,8 @@ impl Renderer {
         });
         let num_indices = indices.len() as u32;
 
+        let window_surface_refresh = self.pipelines.window_surface_refresh();
+
         let mut encoder = self
             .device
             .create_command_encoder(&wgpu::CommandEncoderDescriptor {
@@ -456,10 +892,10 @@ impl Renderer {
                     resolve_target: None,
                     ops: wgpu::Operations {
                         load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.1,
-                            g: 0.2,
-                            b: 0.3,
-                            a: 1.0,
+                            r: self.border_color.r as f64,
+                            g: self.border_color.g as f64,
+                            b: self.border_color.b as f64,
+                            a: self.border_color.a as f64,
                         }),
                         store: wgpu::StoreOp::Store,
                     },
@@ -469,8 +905,8 @@ impl Renderer {
                 timestamp_writes: None,
             });
 
-            render_pass.set_pipeline(&self.pipelines.window_surface_refresh);
-            render_pass.set_bind_group(0, &self.swap_surface.surface_texture.wgpu_bind_group, &[]);
+            render_pass.set_pipeline(&window_surface_refresh);
+            render_pass.set_bind_group(0, &presented.wgpu_bind_group, &[]);
             render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
             render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint16);
             render_pass.draw_indexed(0..num_indices, 0, 0..1);
@@ -484,36 +920,75 @@ impl Renderer {
             Rc::clone(&self.device),
             Rc::clone(&self.queue),
             Rc::clone(&self.bind_group_layouts),
-            Rc::clone(&self.pipelines.draw_sprite),
+            Rc::clone(&self.pipelines),
             self.texture_manager.clone(),
             width,
             height,
         )
     }
-    pub fn draw_sprite(
-        &self,
-        sprite: &Sprite,
-        position: Option<PixelCoordinates>,
-        dimensions: Option<PixelDimensions>,
-        rotation: Option<SpriteRotation>,
-        //blend_mode: Option<BlendMode>
-    ) {
-        self.swap_surface
-            .draw_sprite(sprite, position, dimensions, rotation)
+    pub fn draw_sprite(&self, sprite: &Sprite, draw: &SpriteDraw) {
+        self.swap_surface.draw_sprite(sprite, draw)
     }
-    pub fn draw_subsurface(
-        &self,
-        subsurface: &PixelSurface,
-        position: Option<PixelCoordinates>,
-        dimensions: Option<PixelDimensions>,
-        rotation: Option<SpriteRotation>,
-        //blend_mode: Option<BlendMode>
-    ) {
-        self.swap_surface.draw_subsurface(subsurface, position, dimensions, rotation)
+    pub fn create_retained_sprite(&self, sprite: &Sprite, draw: &SpriteDraw) -> RetainedSprite {
+        self.swap_surface.create_retained_sprite(sprite, draw)
+    }
+    pub fn draw_retained(&self) {
+        self.swap_surface.draw_retained()
+    }
+    pub fn draw_indexed_sprite(&self, sprite: &Sprite, palette: &Palette, draw: &SpriteDraw) {
+        self.swap_surface.draw_indexed_sprite(sprite, palette, draw)
+    }
+    pub fn draw_subsurface(&self, subsurface: &PixelSurface, draw: &SpriteDraw) {
+        self.swap_surface.draw_subsurface(subsurface, draw)
+    }
+    pub fn draw_line(&self, from: PixelCoordinates, to: PixelCoordinates, color: Color) {
+        self.swap_surface.draw_line(from, to, color)
+    }
+    pub fn draw_rect(&self, position: PixelCoordinates, dimensions: PixelDimensions, color: Color) {
+        self.swap_surface.draw_rect(position, dimensions, color)
+    }
+    pub fn fill_rect(&self, position: PixelCoordinates, dimensions: PixelDimensions, color: Color) {
+        self.swap_surface.fill_rect(position, dimensions, color)
+    }
+    pub fn draw_circle(&self, center: PixelCoordinates, radius: u32, color: Color) {
+        self.swap_surface.draw_circle(center, radius, color)
+    }
+    pub fn fill_circle(&self, center: PixelCoordinates, radius: u32, color: Color) {
+        self.swap_surface.fill_circle(center, radius, color)
+    }
+    pub fn draw_polygon(&self, points: &[PixelCoordinates], color: Color) {
+        self.swap_surface.draw_polygon(points, color)
+    }
+    pub fn fill_polygon(&self, points: &[PixelCoordinates], color: Color) {
+        self.swap_surface.fill_polygon(points, color)
+    }
+    pub fn set_camera(&self, camera: Option<Camera2d>) {
+        self.swap_surface.set_camera(camera)
+    }
+    pub fn camera(&self) -> Option<Camera2d> {
+        self.swap_surface.camera()
+    }
+    pub fn fill_path(&self, path: &VectorPath, color: Color) {
+        self.swap_surface.fill_path(path, color)
+    }
+    pub fn stroke_path(&self, path: &VectorPath, style: StrokeStyle, color: Color) {
+        self.swap_surface.stroke_path(path, style, color)
     }
     pub fn load_texture(&self, path: &str) -> Rc<Texture> {
         self.texture_manager.load_texture(path)
     }
+    pub fn create_texture(&self, texture_rgba: image::RgbaImage) -> Rc<Texture> {
+        Rc::new(self.texture_manager.create_texture(texture_rgba))
+    }
+    pub fn load_indexed_texture(&self, path: &str) -> Result<IndexedTexture, IndexedTextureError> {
+        self.texture_manager.load_indexed_texture(path)
+    }
+    pub fn create_indexed_texture(&self, indices: &image::GrayImage) -> Rc<Texture> {
+        Rc::new(self.texture_manager.create_indexed_texture(indices))
+    }
+    pub fn create_palette(&self, colors: &[image::Rgba<u8>]) -> Palette {
+        Palette::new(self.texture_manager.clone(), colors)
+    }
     pub fn create_sprite(
         &self,
         texture: Rc<Texture>,
@@ -521,4 +996,40 @@ impl Renderer {
     ) -> Sprite {
         self.swap_surface.create_sprite(texture, texture_area)
     }
+    pub fn load_aseprite(&self, path: &str) -> Result<AsepriteSheet, AsepriteError> {
+        let file = AsepriteFile::open(path)?;
+        AsepriteSheet::create(&self.texture_manager, &file)
+    }
+    // AngelCode BMFont .fnt in text, xml or binary form
+    pub fn load_bitmap_font(&self, path: &str) -> Result<BitmapFont, BitmapFontError> {
+        BitmapFont::load(&self.texture_manager, path)
+    }
+    // a BitmapFont, or a TrueTypeFont through at_size
+    pub fn draw_text(
+        &self,
+        font: &dyn FontSource,
+        text: &str,
+        position: PixelCoordinates,
+        color: Color,
+        alignment: TextAlignment,
+    ) -> (PixelCoordinates, PixelDimensions) {
+        text_layout::draw_text(&self.swap_surface, font, text, position, color, alignment)
+    }
+    pub fn draw_text_layout(&self, layout: &TextLayout, position: PixelCoordinates) {
+        layout.draw(&self.swap_surface, position)
+    }
+    // glyphs are rasterized on demand at whatever size the text is drawn
+    pub fn load_truetype_font(&self, path: &str) -> Result<TrueTypeFont, TrueTypeFontError> {
+        TrueTypeFont::load(&self.texture_manager, path)
+    }
+    // TexturePacker, Aseprite or free-tex-packer JSON, hash or array layout
+    pub fn load_sprite_sheet(&self, path: &str) -> Result<SpriteSheet, SpriteSheetError> {
+        SpriteSheet::load(&self.texture_manager, path)
+    }
+    pub fn create_texture_atlas(
+        &self,
+        builder: TextureAtlasBuilder,
+    ) -> Result<TextureAtlas, TextureAtlasError> {
+        TextureAtlas::create(&self.texture_manager, builder)
+    }
 }
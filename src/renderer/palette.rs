@@ -0,0 +1,290 @@
+use super::*;
+
+use std::{io::Read, ops::RangeInclusive};
+
+// entries in every palette row, one for each index an indexed texture can hold
+pub const PALETTE_SIZE: usize = 256;
+
+#[derive(Debug)]
+pub enum IndexedTextureError {
+    Io(std::io::Error),
+    Png(png::DecodingError),
+    NotIndexed, // the PNG stores colors rather than palette indices
+}
+
+impl From<std::io::Error> for IndexedTextureError {
+    fn from(error: std::io::Error) -> Self {
+        IndexedTextureError::Io(error)
+    }
+}
+
+impl From<png::DecodingError> for IndexedTextureError {
+    fn from(error: png::DecodingError) -> Self {
+        IndexedTextureError::Png(error)
+    }
+}
+
+// an indexed PNG as loaded, with the palette stored in the file
+pub struct IndexedTexture {
+    pub indices: Rc<Texture>,
+    pub palette: Palette,
+}
+
+// a row of colors that indexed sprites are drawn through; colors are sRGB encoded bytes as in
+// image files, and entries past the ones given are transparent
+pub struct Palette {
+    colors: Vec<image::Rgba<u8>>,
+    texture: Rc<Texture>,
+    texture_manager: TextureManager,
+}
+
+impl Palette {
+    pub(crate) fn new(texture_manager: TextureManager, colors: &[image::Rgba<u8>]) -> Self {
+        let mut colors = colors.to_vec();
+        colors.resize(PALETTE_SIZE, image::Rgba([0, 0, 0, 0]));
+
+        let texture = Rc::new(texture_manager.create_texture(Self::row(&colors)));
+
+        Self {
+            colors,
+            texture,
+            texture_manager,
+        }
+    }
+    fn row(colors: &[image::Rgba<u8>]) -> image::RgbaImage {
+        image::RgbaImage::from_fn(PALETTE_SIZE as u32, 1, |x, _| colors[x as usize])
+    }
+    pub fn colors(&self) -> &[image::Rgba<u8>] {
+        &self.colors
+    }
+    pub(crate) fn texture(&self) -> &Rc<Texture> {
+        &self.texture
+    }
+    // changes apply to every draw through the palette not yet flushed, as with palette swaps on
+    // hardware; draw with a second palette to show both at once
+    pub fn set_color(&mut self, index: u8, color: image::Rgba<u8>) {
+        self.set_colors(index, &[color]);
+    }
+    // colors past the end of the palette are dropped
+    pub fn set_colors(&mut self, first: u8, colors: &[image::Rgba<u8>]) {
+        let first = first as usize;
+        let count = colors.len().min(PALETTE_SIZE - first);
+        self.colors[first..first + count].copy_from_slice(&colors[..count]);
+        self.upload();
+    }
+    // rotates the entries in range by steps towards higher indices, wrapping around, for palette
+    // cycling effects like running water; negative steps rotate the other way
+    pub fn cycle(&mut self, range: RangeInclusive<u8>, steps: i32) {
+        if range.is_empty() {
+            return;
+        }
+
+        cycle_colors(&mut self.colors, range, steps);
+        self.upload();
+    }
+    fn upload(&self) {
+        self.texture_manager.write_texture_area(
+            &self.texture,
+            PixelCoordinates { x: 0, y: 0 },
+            &Self::row(&self.colors),
+        );
+    }
+}
+
+fn cycle_colors(colors: &mut [image::Rgba<u8>], range: RangeInclusive<u8>, steps: i32) {
+    let entries = &mut colors[*range.start() as usize..=*range.end() as usize];
+
+    let steps = steps.rem_euclid(entries.len() as i32) as usize;
+    entries.rotate_right(steps);
+}
+
+// decodes a palette PNG to one index per pixel, along with its palette
+pub(crate) fn decode_indexed_png(
+    reader: impl Read,
+) -> Result<(image::GrayImage, Vec<image::Rgba<u8>>), IndexedTextureError> {
+    let mut decoder = png::Decoder::new(reader);
+    // without expansion the indices come through as stored
+    decoder.set_transformations(png::Transformations::IDENTITY);
+    let mut reader = decoder.read_info()?;
+
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let frame = reader.next_frame(&mut buffer)?;
+    let info = reader.info();
+
+    let (Some(palette), png::ColorType::Indexed) = (&info.palette, frame.color_type) else {
+        return Err(IndexedTextureError::NotIndexed);
+    };
+
+    // tRNS holds alpha for the first entries, the rest are opaque
+    let alphas = info.trns.as_deref().unwrap_or(&[]);
+    let colors = palette
+        .chunks_exact(3)
+        .enumerate()
+        .map(|(index, rgb)| {
+            let alpha = alphas.get(index).copied().unwrap_or(255);
+            image::Rgba([rgb[0], rgb[1], rgb[2], alpha])
+        })
+        .collect();
+
+    // low bit depths pack several indices into each byte, leftmost pixel in the high bits
+    let bits = frame.bit_depth as usize;
+    let per_byte = 8 / bits;
+    let mask = ((1u16 << bits) - 1) as u8;
+    let indices = image::GrayImage::from_fn(frame.width, frame.height, |x, y| {
+        let row = &buffer[y as usize * frame.line_size..];
+        let byte = row[x as usize / per_byte];
+        let shift = 8 - bits * (x as usize % per_byte + 1);
+        image::Luma([(byte >> shift) & mask])
+    });
+
+    Ok((indices, colors))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grey(value: u8) -> image::Rgba<u8> {
+        image::Rgba([value, value, value, 255])
+    }
+
+    fn greys(values: &[u8]) -> Vec<image::Rgba<u8>> {
+        values.iter().copied().map(grey).collect()
+    }
+
+    #[test]
+    fn cycling_rotates_only_the_range() {
+        let mut colors = greys(&[0, 1, 2, 3, 4, 5]);
+
+        cycle_colors(&mut colors, 1..=4, 1);
+        assert_eq!(colors, greys(&[0, 4, 1, 2, 3, 5]));
+
+        cycle_colors(&mut colors, 1..=4, -1);
+        assert_eq!(colors, greys(&[0, 1, 2, 3, 4, 5]));
+    }
+
+    #[test]
+    fn cycling_wraps_steps_past_the_range_length() {
+        let mut colors = greys(&[0, 1, 2, 3]);
+
+        cycle_colors(&mut colors, 0..=3, 9);
+        assert_eq!(colors, greys(&[3, 0, 1, 2]));
+
+        cycle_colors(&mut colors, 0..=3, -6);
+        assert_eq!(colors, greys(&[1, 2, 3, 0]));
+    }
+
+    #[test]
+    fn cycling_reaches_the_last_palette_entry() {
+        let mut colors = greys(&[0; PALETTE_SIZE]);
+        colors[PALETTE_SIZE - 1] = grey(9);
+
+        cycle_colors(&mut colors, 254..=255, 1);
+        assert_eq!(colors[254], grey(9));
+        assert_eq!(colors[255], grey(0));
+    }
+
+    fn encode_png(
+        width: u32,
+        height: u32,
+        color_type: png::ColorType,
+        bit_depth: png::BitDepth,
+        palette: Option<(&[u8], &[u8])>,
+        data: &[u8],
+    ) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        {
+            let mut encoder = png::Encoder::new(&mut bytes, width, height);
+            encoder.set_color(color_type);
+            encoder.set_depth(bit_depth);
+            if let Some((palette, trns)) = palette {
+                encoder.set_palette(palette);
+                encoder.set_trns(trns);
+            }
+            let mut writer = encoder.write_header().unwrap();
+            writer.write_image_data(data).unwrap();
+        }
+        bytes
+    }
+
+    const PALETTE: [u8; 12] = [0, 0, 0, 255, 0, 0, 0, 255, 0, 0, 0, 255];
+
+    fn decode_indices(bit_depth: png::BitDepth, width: u32, rows: &[u8]) -> Vec<u8> {
+        let png = encode_png(
+            width,
+            2,
+            png::ColorType::Indexed,
+            bit_depth,
+            Some((&PALETTE, &[0])),
+            rows,
+        );
+        let (indices, _) = decode_indexed_png(png.as_slice()).unwrap();
+        indices.into_raw()
+    }
+
+    #[test]
+    fn low_bit_depths_unpack_leftmost_pixel_first() {
+        // 1 bit, 3 pixels a row, each row padded to a byte
+        assert_eq!(
+            decode_indices(png::BitDepth::One, 3, &[0b1010_0000, 0b0110_0000]),
+            [1, 0, 1, 0, 1, 1]
+        );
+        // 2 bits, 5 pixels a row across two bytes
+        assert_eq!(
+            decode_indices(
+                png::BitDepth::Two,
+                5,
+                &[0b0001_1011, 0b1000_0000, 0b1110_0100, 0b0100_0000]
+            ),
+            [0, 1, 2, 3, 2, 3, 2, 1, 0, 1]
+        );
+        assert_eq!(
+            decode_indices(png::BitDepth::Four, 3, &[0x12, 0x00, 0x21, 0x20]),
+            [1, 2, 0, 2, 1, 2]
+        );
+        assert_eq!(
+            decode_indices(png::BitDepth::Eight, 2, &[0, 1, 2, 0]),
+            [0, 1, 2, 0]
+        );
+    }
+
+    #[test]
+    fn palette_alpha_comes_from_trns_for_the_first_entries() {
+        let png = encode_png(
+            1,
+            1,
+            png::ColorType::Indexed,
+            png::BitDepth::Eight,
+            Some((&PALETTE, &[0, 128])),
+            &[2],
+        );
+        let (_, colors) = decode_indexed_png(png.as_slice()).unwrap();
+
+        assert_eq!(
+            colors,
+            [
+                image::Rgba([0, 0, 0, 0]),
+                image::Rgba([255, 0, 0, 128]),
+                image::Rgba([0, 255, 0, 255]),
+                image::Rgba([0, 0, 255, 255]),
+            ]
+        );
+    }
+
+    #[test]
+    fn color_pngs_are_not_indexed() {
+        let png = encode_png(
+            1,
+            1,
+            png::ColorType::Rgb,
+            png::BitDepth::Eight,
+            None,
+            &[1, 2, 3],
+        );
+
+        assert!(matches!(
+            decode_indexed_png(png.as_slice()),
+            Err(IndexedTextureError::NotIndexed)
+        ));
+    }
+}
@@ -0,0 +1,16 @@
+// Fragment shader, appended to sprite2d.wgsl; t_diffuse holds palette indices in its red
+// channel, each the column of the color in the palette row
+
+@group(2) @binding(0)
+var t_palette: texture_2d<f32>;
+@group(2) @binding(1)
+var s_palette: sampler;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let index = i32(round(textureSample(t_diffuse, s_diffuse, in.tex_coords).r * 255.0));
+    let entries = i32(textureDimensions(t_palette).x);
+    let color = textureLoad(t_palette, vec2<i32>(min(index, entries - 1), 0), 0);
+
+    return sprite_color(color, in);
+}
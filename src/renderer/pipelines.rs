@@ -1,11 +1,162 @@
 use super::*;
 use bind_group_layouts::*;
+use pixel_surface::BlendMode;
 use shaders::*;
 
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    future::Future,
+    task::{Context, Poll, Waker},
+};
+
+// runs build in an error scope, so a rejected shader or pipeline comes back as an error instead of
+// reaching the device's uncaptured error handler
+pub(crate) fn checked<T>(
+    device: &wgpu::Device,
+    build: impl FnOnce() -> T,
+) -> Result<T, wgpu::Error> {
+    device.push_error_scope(wgpu::ErrorFilter::Validation);
+    let built = build();
+
+    // native devices validate as calls are made, so the scope has already resolved
+    let mut popped = std::pin::pin!(device.pop_error_scope());
+    match popped
+        .as_mut()
+        .poll(&mut Context::from_waker(Waker::noop()))
+    {
+        Poll::Ready(Some(error)) => Err(error),
+        _ => Ok(built),
+    }
+}
+
+// each pipeline is swappable so hot reloading can rebuild it; draws fetch them at encode time
 pub struct Pipelines {
-    pub draw_sprite: Rc<wgpu::RenderPipeline>,
+    draw_sprite: RefCell<Rc<SpritePipelines>>,
+    draw_indexed: RefCell<Rc<SpritePipelines>>,
+    draw_solid: RefCell<Rc<wgpu::RenderPipeline>>,
+    draw_path: RefCell<Rc<wgpu::RenderPipeline>>,
     //pub swap_draw_surface: Rc<wgpu::RenderPipeline>,
-    pub window_surface_refresh: Rc<wgpu::RenderPipeline>,
+    window_surface_refresh: RefCell<Rc<wgpu::RenderPipeline>>,
+    #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+    output_format: wgpu::TextureFormat,
+}
+
+// the pipelines built from the renderer's own shaders
+#[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PipelineKind {
+    DrawSprite,
+    DrawIndexed,
+    DrawSolid,
+    DrawPath,
+    WindowSurfaceRefresh,
+}
+
+// one draw_sprite pipeline per BlendMode, all created up front
+pub struct SpritePipelines {
+    variants: HashMap<BlendMode, wgpu::RenderPipeline>,
+}
+
+impl SpritePipelines {
+    pub fn create(
+        device: &wgpu::Device,
+        shader: &wgpu::ShaderModule,
+        bind_group_layouts: &BindGroupLayouts,
+    ) -> Self {
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("draw_sprite pipeline layout"),
+            bind_group_layouts: &[
+                &bind_group_layouts.texture,
+                &bind_group_layouts.surface_uniforms,
+            ],
+            push_constant_ranges: &[],
+        });
+
+        Self::from_shader(
+            device,
+            "draw_sprite render pipeline",
+            &pipeline_layout,
+            shader,
+        )
+    }
+    // the layout must start with the texture and surface uniforms groups, as materials' do
+    pub fn from_shader(
+        device: &wgpu::Device,
+        label: &str,
+        pipeline_layout: &wgpu::PipelineLayout,
+        shader: &wgpu::ShaderModule,
+    ) -> Self {
+        let variants = BlendMode::ALL
+            .iter()
+            .map(|&blend_mode| {
+                let pipeline = surface_pipeline(
+                    device,
+                    label,
+                    pipeline_layout,
+                    shader,
+                    &[
+                        pixel_surface::Vertex2d::desc(),
+                        sprite_batch::SpriteInstance::desc(),
+                    ],
+                    blend_mode.blend_state(),
+                    None, // mirroring transforms reverse the winding
+                );
+                (blend_mode, pipeline)
+            })
+            .collect();
+
+        Self { variants }
+    }
+    pub fn get(&self, blend_mode: BlendMode) -> &wgpu::RenderPipeline {
+        &self.variants[&blend_mode]
+    }
+}
+
+// a pipeline that renders into a PixelSurface texture
+pub(crate) fn surface_pipeline(
+    device: &wgpu::Device,
+    label: &str,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    buffers: &[wgpu::VertexBufferLayout],
+    blend: wgpu::BlendState,
+    cull_mode: Option<wgpu::Face>,
+) -> wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(label),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: "vs_main",
+            buffers,
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: "fs_main",
+            targets: &[Some(wgpu::ColorTargetState {
+                format: wgpu::TextureFormat::Rgba8UnormSrgb,
+                blend: Some(blend),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Cw,
+            cull_mode,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            unclipped_depth: false,
+            conservative: false,
+        },
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState {
+            count: 1,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        multiview: None,
+    })
 }
 
 impl Pipelines {
@@ -13,107 +164,218 @@ impl Pipelines {
         device: &wgpu::Device,
         shaders: &Shaders,
         bind_group_layouts: &BindGroupLayouts,
-        window_surface_config: &wgpu::SurfaceConfiguration,
+        output_format: wgpu::TextureFormat,
     ) -> Self {
-        let draw_sprite = {
-            let shader = &shaders.diffuse2d;
-
-            let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: Some("draw_sprite pipeline layout"),
-                bind_group_layouts: &[
-                    &bind_group_layouts.texture,
-                    &bind_group_layouts.sprite_uniforms,
-                ],
-                push_constant_ranges: &[],
-            });
-
-            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                label: Some("draw_sprite render pipeline"),
-                layout: Some(&pipeline_layout),
-                vertex: wgpu::VertexState {
-                    module: &shader,
-                    entry_point: "vs_main",
-                    buffers: &[pixel_surface::Vertex2d::desc()],
-                },
-                fragment: Some(wgpu::FragmentState {
-                    module: &shader,
-                    entry_point: "fs_main",
-                    targets: &[Some(wgpu::ColorTargetState {
-                        format: wgpu::TextureFormat::Rgba8UnormSrgb,
-                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
-                        write_mask: wgpu::ColorWrites::ALL,
-                    })],
-                }),
-                primitive: wgpu::PrimitiveState {
-                    topology: wgpu::PrimitiveTopology::TriangleList,
-                    strip_index_format: None,
-                    front_face: wgpu::FrontFace::Cw,
-                    cull_mode: Some(wgpu::Face::Back),
-                    polygon_mode: wgpu::PolygonMode::Fill,
-                    unclipped_depth: false,
-                    conservative: false,
-                },
-                depth_stencil: None,
-                multisample: wgpu::MultisampleState {
-                    count: 1,
-                    mask: !0,
-                    alpha_to_coverage_enabled: false,
-                },
-                multiview: None,
-            })
-        };
+        let draw_sprite = SpritePipelines::create(device, &shaders.diffuse2d, bind_group_layouts);
+        let draw_indexed =
+            Self::create_draw_indexed(device, &shaders.palette2d, bind_group_layouts);
+        let draw_solid = Self::create_draw_solid(device, &shaders.solid2d, bind_group_layouts);
+        let draw_path = Self::create_draw_path(device, &shaders.path2d, bind_group_layouts);
+        let window_surface_refresh = Self::create_window_surface_refresh(
+            device,
+            &shaders.window_surface_refresh,
+            bind_group_layouts,
+            output_format,
+        );
+
+        Self {
+            draw_sprite: RefCell::new(Rc::new(draw_sprite)),
+            draw_indexed: RefCell::new(Rc::new(draw_indexed)),
+            draw_solid: RefCell::new(Rc::new(draw_solid)),
+            draw_path: RefCell::new(Rc::new(draw_path)),
+            window_surface_refresh: RefCell::new(Rc::new(window_surface_refresh)),
+            #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+            output_format,
+        }
+    }
+    // sprites of palette indices, with the palette row bound after the surface uniforms
+    fn create_draw_indexed(
+        device: &wgpu::Device,
+        shader: &wgpu::ShaderModule,
+        bind_group_layouts: &BindGroupLayouts,
+    ) -> SpritePipelines {
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("draw_indexed_sprite pipeline layout"),
+            bind_group_layouts: &[
+                &bind_group_layouts.texture,
+                &bind_group_layouts.surface_uniforms,
+                &bind_group_layouts.texture,
+            ],
+            push_constant_ranges: &[],
+        });
+
+        SpritePipelines::from_shader(
+            device,
+            "draw_indexed_sprite render pipeline",
+            &pipeline_layout,
+            shader,
+        )
+    }
+    // untextured rects in a single color, for primitives
+    fn create_draw_solid(
+        device: &wgpu::Device,
+        shader: &wgpu::ShaderModule,
+        bind_group_layouts: &BindGroupLayouts,
+    ) -> wgpu::RenderPipeline {
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("draw_solid pipeline layout"),
+            bind_group_layouts: &[&bind_group_layouts.surface_uniforms],
+            push_constant_ranges: &[],
+        });
+
+        surface_pipeline(
+            device,
+            "draw_solid render pipeline",
+            &pipeline_layout,
+            shader,
+            &[
+                pixel_surface::Vertex2d::desc(),
+                sprite_batch::SolidInstance::desc(),
+            ],
+            BlendMode::Alpha.blend_state(),
+            Some(wgpu::Face::Back),
+        )
+    }
+    fn create_window_surface_refresh(
+        device: &wgpu::Device,
+        shader: &wgpu::ShaderModule,
+        bind_group_layouts: &BindGroupLayouts,
+        output_format: wgpu::TextureFormat,
+    ) -> wgpu::RenderPipeline {
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("window surface refresh pipeline layout"),
+            bind_group_layouts: &[&bind_group_layouts.texture],
+            push_constant_ranges: &[],
+        });
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("window surface refresh render pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: "vs_main",
+                buffers: &[super::WindowRefreshVertex::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: output_format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Cw,
+                cull_mode: Some(wgpu::Face::Back),
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        })
+    }
+    // tessellated vector paths with per-vertex color and antialiasing
+    fn create_draw_path(
+        device: &wgpu::Device,
+        shader: &wgpu::ShaderModule,
+        bind_group_layouts: &BindGroupLayouts,
+    ) -> wgpu::RenderPipeline {
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("draw_path pipeline layout"),
+            bind_group_layouts: &[&bind_group_layouts.surface_uniforms],
+            push_constant_ranges: &[],
+        });
 
-        let window_surface_refresh = {
-            let shader = &shaders.window_surface_refresh;
-
-            let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: Some("window surface refresh pipeline layout"),
-                bind_group_layouts: &[&bind_group_layouts.texture],
-                push_constant_ranges: &[],
-            });
-
-            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                label: Some("window surface refresh render pipeline"),
-                layout: Some(&pipeline_layout),
-                vertex: wgpu::VertexState {
-                    module: &shader,
-                    entry_point: "vs_main",
-                    buffers: &[super::WindowRefreshVertex::desc()],
-                },
-                fragment: Some(wgpu::FragmentState {
-                    module: &shader,
-                    entry_point: "fs_main",
-                    targets: &[Some(wgpu::ColorTargetState {
-                        format: window_surface_config.format,
-                        blend: Some(wgpu::BlendState::REPLACE),
-                        write_mask: wgpu::ColorWrites::ALL,
-                    })],
-                }),
-                primitive: wgpu::PrimitiveState {
-                    topology: wgpu::PrimitiveTopology::TriangleList,
-                    strip_index_format: None,
-                    front_face: wgpu::FrontFace::Cw,
-                    cull_mode: Some(wgpu::Face::Back),
-                    polygon_mode: wgpu::PolygonMode::Fill,
-                    unclipped_depth: false,
-                    conservative: false,
-                },
-                depth_stencil: None,
-                multisample: wgpu::MultisampleState {
-                    count: 1,
-                    mask: !0,
-                    alpha_to_coverage_enabled: false,
-                },
-                multiview: None,
+        surface_pipeline(
+            device,
+            "draw_path render pipeline",
+            &pipeline_layout,
+            shader,
+            &[vector_path::PathVertex::desc()],
+            BlendMode::Alpha.blend_state(),
+            // tessellated triangles don't share a winding
+            None,
+        )
+    }
+    // swaps in the pipeline built from a changed shader, which surfaces pick up at their next
+    // flush; the old one stays when wgpu rejects the new one
+    #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+    pub fn rebuild(
+        &self,
+        device: &wgpu::Device,
+        bind_group_layouts: &BindGroupLayouts,
+        kind: PipelineKind,
+        source: &str,
+    ) -> Result<(), wgpu::Error> {
+        let create_shader = || {
+            device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: None,
+                source: wgpu::ShaderSource::Wgsl(source.into()),
             })
         };
 
-        let draw_sprite = Rc::new(draw_sprite);
-        let window_surface_refresh = Rc::new(window_surface_refresh);
-
-        Self {
-            draw_sprite,
-            window_surface_refresh,
+        match kind {
+            PipelineKind::DrawSprite => {
+                let pipelines = checked(device, || {
+                    SpritePipelines::create(device, &create_shader(), bind_group_layouts)
+                })?;
+                *self.draw_sprite.borrow_mut() = Rc::new(pipelines);
+            }
+            PipelineKind::DrawIndexed => {
+                let pipelines = checked(device, || {
+                    Self::create_draw_indexed(device, &create_shader(), bind_group_layouts)
+                })?;
+                *self.draw_indexed.borrow_mut() = Rc::new(pipelines);
+            }
+            PipelineKind::DrawSolid => {
+                let pipeline = checked(device, || {
+                    Self::create_draw_solid(device, &create_shader(), bind_group_layouts)
+                })?;
+                *self.draw_solid.borrow_mut() = Rc::new(pipeline);
+            }
+            PipelineKind::DrawPath => {
+                let pipeline = checked(device, || {
+                    Self::create_draw_path(device, &create_shader(), bind_group_layouts)
+                })?;
+                *self.draw_path.borrow_mut() = Rc::new(pipeline);
+            }
+            PipelineKind::WindowSurfaceRefresh => {
+                let pipeline = checked(device, || {
+                    Self::create_window_surface_refresh(
+                        device,
+                        &create_shader(),
+                        bind_group_layouts,
+                        self.output_format,
+                    )
+                })?;
+                *self.window_surface_refresh.borrow_mut() = Rc::new(pipeline);
+            }
         }
+
+        Ok(())
+    }
+    pub fn draw_sprite(&self) -> Rc<SpritePipelines> {
+        Rc::clone(&self.draw_sprite.borrow())
+    }
+    pub fn draw_indexed(&self) -> Rc<SpritePipelines> {
+        Rc::clone(&self.draw_indexed.borrow())
+    }
+    pub fn draw_solid(&self) -> Rc<wgpu::RenderPipeline> {
+        Rc::clone(&self.draw_solid.borrow())
+    }
+    pub fn draw_path(&self) -> Rc<wgpu::RenderPipeline> {
+        Rc::clone(&self.draw_path.borrow())
+    }
+    pub fn window_surface_refresh(&self) -> Rc<wgpu::RenderPipeline> {
+        Rc::clone(&self.window_surface_refresh.borrow())
     }
 }
@@ -1,70 +1,238 @@
 use super::*;
 
 use bind_group_layouts::*;
-
-use std::{f32::consts::PI, rc::Rc};
+use camera::Camera2d;
+use palette::Palette;
+use pipelines::Pipelines;
+use primitives::PixelSpans;
+use readback::*;
+use retained::{RetainedLayer, RetainedSprite};
+use sprite_batch::*;
+use transform::Transform2d;
+use vector_path::{StrokeStyle, VectorPath};
+
+use std::{
+    cell::RefCell,
+    rc::{Rc, Weak},
+};
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
-struct Rotation2d {
-    pub rotation_matrix: [[f32; 2]; 2],
-    pub rotation_center: RenderPlaneCoordinates,
+pub struct SurfaceUniforms {
+    render_target_dimensions: [f32; 2],
+    view: [[f32; 2]; 3], // columns of the camera's view transform, as three vec2s in the shaders
 }
 
-impl Rotation2d {
-    pub fn by_fraction(fraction: f32, center: Option<RenderPlaneCoordinates>) -> Self {
-        let rotation_center = if let Some(center) = center {
-            center
-        } else {
-            RenderPlaneCoordinates { x: 0.0, y: 0.0 }
-        };
+// the point of a sprite that is placed at the draw position and transformed and mirrored around
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum SpritePivot {
+    #[default]
+    TopLeft,
+    TopCenter,
+    TopRight,
+    CenterLeft,
+    Center,
+    CenterRight,
+    BottomLeft,
+    BottomCenter,
+    BottomRight,
+    Normalized(f32, f32), // 0 to 1 across the sprite's width and height
+}
 
-        let rotation_matrix = cgmath::Matrix2::from_angle(cgmath::Rad(fraction * 2.0 * PI)).into();
+impl SpritePivot {
+    pub fn normalized(&self) -> (f32, f32) {
+        match *self {
+            SpritePivot::TopLeft => (0.0, 0.0),
+            SpritePivot::TopCenter => (0.5, 0.0),
+            SpritePivot::TopRight => (1.0, 0.0),
+            SpritePivot::CenterLeft => (0.0, 0.5),
+            SpritePivot::Center => (0.5, 0.5),
+            SpritePivot::CenterRight => (1.0, 0.5),
+            SpritePivot::BottomLeft => (0.0, 1.0),
+            SpritePivot::BottomCenter => (0.5, 1.0),
+            SpritePivot::BottomRight => (1.0, 1.0),
+            SpritePivot::Normalized(x, y) => (x, y),
+        }
+    }
+}
+
+// factors applied to the drawn dimensions; a negative factor mirrors the sprite about its pivot
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SpriteScale {
+    pub x: f32,
+    pub y: f32,
+}
 
+impl SpriteScale {
+    pub const IDENTITY: SpriteScale = SpriteScale { x: 1.0, y: 1.0 };
+
+    pub fn new(x: f32, y: f32) -> Self {
+        Self { x, y }
+    }
+    pub fn uniform(scale: f32) -> Self {
+        Self::new(scale, scale)
+    }
+    pub fn flipped(horizontal: bool, vertical: bool) -> Self {
+        Self::IDENTITY.flip(horizontal, vertical)
+    }
+    pub fn flip(self, horizontal: bool, vertical: bool) -> Self {
         Self {
-            rotation_center,
-            rotation_matrix,
+            x: if horizontal { -self.x } else { self.x },
+            y: if vertical { -self.y } else { self.y },
         }
     }
+}
 
-    pub fn from_matrix(
-        rotation_matrix: [[f32; 2]; 2],
-        center: Option<RenderPlaneCoordinates>,
-    ) -> Self {
-        let rotation_center = if let Some(center) = center {
-            center
-        } else {
-            RenderPlaneCoordinates { x: 0.0, y: 0.0 }
-        };
+impl Default for SpriteScale {
+    fn default() -> Self {
+        SpriteScale::IDENTITY
+    }
+}
+
+// per-draw color changes applied to a sprite's texels, in this order
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SpriteColor {
+    pub tint: Color,  // multiplied with the texture sample
+    pub flash: Color, // rgb mixed over the tinted texels by its alpha, keeping their alpha
+    pub opacity: f32, // multiplied with the final alpha
+}
+
+impl SpriteColor {
+    pub fn tinted(tint: Color) -> Self {
+        Self {
+            tint,
+            ..Default::default()
+        }
+    }
+    // amount is 0 for the sprite as is and 1 for a solid silhouette of the color
+    pub fn flashed(flash: Color, amount: f32) -> Self {
+        Self {
+            flash: Color::rgba(flash.r, flash.g, flash.b, amount),
+            ..Default::default()
+        }
+    }
+    pub fn faded(opacity: f32) -> Self {
+        Self {
+            opacity,
+            ..Default::default()
+        }
+    }
+}
 
+impl Default for SpriteColor {
+    fn default() -> Self {
         Self {
-            rotation_center,
-            rotation_matrix,
+            tint: Color::WHITE,
+            flash: Color::TRANSPARENT,
+            opacity: 1.0,
         }
     }
 }
 
-#[repr(C)]
-#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
-pub struct SpriteUniforms {
-    render_target_dimensions: [f32; 2],
-    position: [f32; 2],
-    dimensions: [f32; 2],
-    rotation_center: [f32; 2],
-    rotation: [[f32; 2]; 2],
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum BlendMode {
+    #[default]
+    Alpha,
+    PremultipliedAlpha,
+    Additive,
+    Multiply,
+    Screen,
+    Subtract,
+    Replace,
+}
+
+impl BlendMode {
+    pub const ALL: [BlendMode; 7] = [
+        BlendMode::Alpha,
+        BlendMode::PremultipliedAlpha,
+        BlendMode::Additive,
+        BlendMode::Multiply,
+        BlendMode::Screen,
+        BlendMode::Subtract,
+        BlendMode::Replace,
+    ];
+
+    // sources are straight (non-premultiplied) alpha except for PremultipliedAlpha, and for
+    // Multiply and Screen, whose straight texels sprite2d.wgsl premultiplies
+    pub fn blend_state(&self) -> wgpu::BlendState {
+        // destination alpha is composited the usual way for every mode that doesn't replace it
+        let over_alpha = wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::One,
+            dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+            operation: wgpu::BlendOperation::Add,
+        };
+
+        match self {
+            BlendMode::Alpha => wgpu::BlendState::ALPHA_BLENDING,
+            BlendMode::PremultipliedAlpha => wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING,
+            // dst + src * src_alpha
+            BlendMode::Additive => wgpu::BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::SrcAlpha,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha: over_alpha,
+            },
+            // src * src_alpha * dst + dst * (1 - src_alpha), so transparent texels leave dst
+            // untouched
+            BlendMode::Multiply => wgpu::BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::Dst,
+                    dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha: over_alpha,
+            },
+            // src * src_alpha + dst * (1 - src * src_alpha)
+            BlendMode::Screen => wgpu::BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::One,
+                    dst_factor: wgpu::BlendFactor::OneMinusSrc,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha: over_alpha,
+            },
+            // dst - src * src_alpha
+            BlendMode::Subtract => wgpu::BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::SrcAlpha,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::ReverseSubtract,
+                },
+                alpha: over_alpha,
+            },
+            BlendMode::Replace => wgpu::BlendState::REPLACE,
+        }
+    }
 }
 
-pub struct SpriteRotation {
-    pub rotation_matrix: [[f32; 2]; 2],
-    pub rotation_center: Option<PixelCoordinates>, // top-left by default
+// how draw_sprite and the other sprite draws place and color a sprite; the default draws it as
+// it is with its pivot at the surface's origin
+#[derive(Clone, Default)]
+pub struct SpriteDraw {
+    // where the sprite's pivot lands, in whole pixels; place sprites between pixels with a
+    // translation in the transform
+    pub position: PixelCoordinates,
+    pub dimensions: Option<PixelDimensions>, // before scaling, None for the sprite's own
+    pub scale: SpriteScale,
+    pub transform: Transform2d, // applied with the pivot as its origin, before moving to position
+    pub blend_mode: BlendMode,
+    pub color: SpriteColor,
+    pub material: Option<Rc<Material>>, // None for the texture's colors as they are
 }
 
-impl SpriteRotation {
-    pub fn by_fraction(fraction: f32, rotation_center: Option<PixelCoordinates>) -> Self {
-        let rotation_matrix = cgmath::Matrix2::from_angle(cgmath::Rad(fraction * 2.0 * PI)).into();
+impl SpriteDraw {
+    pub fn at(position: PixelCoordinates) -> Self {
         Self {
-            rotation_matrix,
-            rotation_center,
+            position,
+            ..Default::default()
+        }
+    }
+    pub(crate) fn shading(&self) -> SpriteShading {
+        match &self.material {
+            Some(material) => SpriteShading::Material(Rc::clone(material)),
+            None => SpriteShading::Diffuse,
         }
     }
 }
@@ -79,7 +247,7 @@ pub struct Texture2dRect {
     pub dimensions: Texture2dDimensions,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug)]
 pub struct SpriteTextureArea {
     pub coordinates: PixelCoordinates,
     pub dimensions: PixelDimensions,
@@ -87,11 +255,13 @@ pub struct SpriteTextureArea {
 
 pub const RECT_INDICES: [u16; 6] = [0, 1, 2, 1, 3, 2];
 
+#[derive(Clone)]
 pub struct Sprite {
     pub texture: Rc<Texture>,
     pub vertices: [TextureCoordinates; 4],
     pub texture_area: Option<SpriteTextureArea>,
     pub dimensions: PixelDimensions,
+    pub pivot: SpritePivot,
 }
 
 impl Sprite {
@@ -148,6 +318,7 @@ impl Sprite {
                 texture_area,
                 vertices,
                 dimensions,
+                pivot: SpritePivot::default(),
             }
         } else {
             let dimensions = texture.dimensions;
@@ -161,12 +332,16 @@ impl Sprite {
                 ],
                 texture_area: None,
                 dimensions,
+                pivot: SpritePivot::default(),
             }
         }
     }
     pub fn from_texture(texture: Rc<Texture>) -> Self {
         Sprite::create(texture.clone(), None)
     }
+    pub fn with_pivot(self, pivot: SpritePivot) -> Self {
+        Self { pivot, ..self }
+    }
 }
 
 #[repr(C)]
@@ -198,308 +373,411 @@ impl Vertex2d {
     }
 }
 
-pub struct PixelSurface {
-    pub surface_texture: Texture,
-    //pub surface_texture_bind_group: wgpu::BindGroup,
-    pub dimensions: PixelDimensions,
+// a surface texture and the draws queued into it, with what flushing them takes; shared with the
+// subsurfaces those draws sample, so that a subsurface can flush them before it changes
+struct SurfaceTarget {
+    device: Rc<wgpu::Device>,
+    queue: Rc<wgpu::Queue>,
+    pipelines: Rc<Pipelines>,
+    surface_texture: Rc<Texture>,
+    dimensions: PixelDimensions,
+    surface_uniforms_bind_group: wgpu::BindGroup,
+    camera: RefCell<Option<Camera2d>>,
+    batch: RefCell<SpriteBatch>,
+    readers: RefCell<Vec<Weak<SurfaceTarget>>>, // surfaces with queued draws of this one
 }
 
-impl PixelSurface {
-    pub fn new_pixel_surface(
-        width: u32,
-        height: u32,
-    ) -> Self {
-        let mut surface_rgba = image::RgbaImage::new(width, height);
-
-        for mut _pixel in surface_rgba.pixels_mut() {
-            _pixel = &mut image::Rgba::<u8>::from([0, 0, 0, 0]);
+impl SurfaceTarget {
+    // submits every draw queued since the last flush in a single encoder
+    fn flush(&self) {
+        if self.batch.borrow().is_empty() {
+            return;
         }
 
-        let surface_texture = texture_manager.create_texture(surface_rgba);
-        let dimensions = PixelDimensions { width, height };
+        self.flush_readers();
 
-        Self {
-            surface_texture,
-            dimensions,
-        }
-    }
-    pub fn clear(&self) {
         let mut encoder = self
             .device
             .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                label: Some("rendersurface2d encoder"),
+                label: Some("pixel surface flush encoder"),
             });
 
-        {
-            let _render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render Pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &self.surface_texture.wgpu_texture_view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.0,
-                            g: 0.0,
-                            b: 0.0,
-                            a: 0.0,
-                        }),
-                        store: wgpu::StoreOp::Store,
-                    },
-                })],
-                depth_stencil_attachment: None,
-                occlusion_query_set: None,
-                timestamp_writes: None,
-            });
-        }
+        self.batch.borrow_mut().encode(
+            &self.device,
+            &self.queue,
+            &mut encoder,
+            &self.pipelines,
+            &self.batch_target(),
+        );
 
         self.queue.submit(std::iter::once(encoder.finish()));
     }
+    // about to change, so the surfaces that sample it as it is now have to be drawn first
+    fn flush_readers(&self) {
+        let readers = std::mem::take(&mut *self.readers.borrow_mut());
 
-    pub fn draw_subsurface(
-        &self,
-        subsurface: &PixelSurface,
-        position: Option<PixelCoordinates>,
-        dimensions: Option<PixelDimensions>,
-        rotation: Option<SpriteRotation>,
-        //blend_mode: Option<BlendMode>
-    ) {
-        let device = &self.device;
-
-        let position = position.unwrap_or(PixelCoordinates { x: 0, y: 0 });
+        for reader in readers.iter().filter_map(Weak::upgrade) {
+            reader.flush();
+        }
+    }
+    fn add_reader(&self, reader: &Rc<SurfaceTarget>) {
+        let mut readers = self.readers.borrow_mut();
 
-        let dimensions = dimensions.unwrap_or(PixelDimensions {
-            width: subsurface.dimensions.width,
-            height: subsurface.dimensions.height,
-        });
+        if !readers
+            .iter()
+            .any(|existing| std::ptr::eq(existing.as_ptr(), Rc::as_ptr(reader)))
+        {
+            readers.push(Rc::downgrade(reader));
+        }
+    }
+    fn batch_target(&self) -> BatchTarget<'_> {
+        BatchTarget {
+            view: &self.surface_texture.wgpu_texture_view,
+            surface_uniforms_bind_group: &self.surface_uniforms_bind_group,
+            scissor: self.camera_scissor(),
+        }
+    }
+    // the camera's viewport clamped to the surface
+    fn camera_scissor(&self) -> Option<(PixelCoordinates, PixelDimensions)> {
+        let camera = (*self.camera.borrow())?;
+
+        let left = camera
+            .viewport_position
+            .x
+            .clamp(0, self.dimensions.width as i32);
+        let top = camera
+            .viewport_position
+            .y
+            .clamp(0, self.dimensions.height as i32);
+        let right = (camera.viewport_position.x + camera.viewport_dimensions.width as i32)
+            .clamp(left, self.dimensions.width as i32);
+        let bottom = (camera.viewport_position.y + camera.viewport_dimensions.height as i32)
+            .clamp(top, self.dimensions.height as i32);
+
+        Some((
+            PixelCoordinates { x: left, y: top },
+            PixelDimensions {
+                width: (right - left) as u32,
+                height: (bottom - top) as u32,
+            },
+        ))
+    }
+}
 
-        let (rotation_matrix, rotation_center) = {
-            let rotation = rotation.unwrap_or(SpriteRotation {
-                rotation_matrix: cgmath::Matrix2::from_angle(cgmath::Rad(0.0)).into(),
-                rotation_center: None,
-            });
+pub struct PixelSurface {
+    device: Rc<wgpu::Device>,
+    queue: Rc<wgpu::Queue>,
+    texture_manager: TextureManager,
+    surface_uniforms_buffer: wgpu::Buffer,
+    target: Rc<SurfaceTarget>,
+    retained: Rc<RefCell<RetainedLayer>>,
+    pub surface_texture: Rc<Texture>,
+    pub dimensions: PixelDimensions,
+}
 
-            let rotation_center = rotation
-                .rotation_center
-                .unwrap_or(PixelCoordinates { x: 0, y: 0 });
+impl PixelSurface {
+    pub(crate) fn new(
+        device: Rc<wgpu::Device>,
+        queue: Rc<wgpu::Queue>,
+        bind_group_layouts: Rc<BindGroupLayouts>,
+        pipelines: Rc<Pipelines>,
+        texture_manager: TextureManager,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        let surface_rgba = image::RgbaImage::new(width, height);
 
-            (rotation.rotation_matrix, rotation_center)
-        };
+        let surface_texture = Rc::new(texture_manager.create_texture(surface_rgba));
+        let dimensions = PixelDimensions { width, height };
 
-        let sprite_uniforms = SpriteUniforms {
-            render_target_dimensions: [self.dimensions.width as f32, self.dimensions.height as f32],
-            position: [position.x as f32, position.y as f32],
-            dimensions: [dimensions.width as f32, dimensions.height as f32],
-            rotation: rotation_matrix,
-            rotation_center: [rotation_center.x as f32, rotation_center.y as f32],
+        let surface_uniforms = SurfaceUniforms {
+            render_target_dimensions: [width as f32, height as f32],
+            view: Transform2d::identity().to_columns(),
         };
 
-        let sprite_uniforms_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("sprite uniform buffer"),
-            contents: bytemuck::cast_slice(&[sprite_uniforms]),
-            usage: wgpu::BufferUsages::UNIFORM,
-        });
+        let surface_uniforms_buffer =
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("surface uniform buffer"),
+                contents: bytemuck::cast_slice(&[surface_uniforms]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
 
-        let sprite_uniforms_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("transform 2d bind group"),
-            layout: &self.bind_group_layouts.sprite_uniforms,
+        let surface_uniforms_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("surface uniforms bind group"),
+            layout: &bind_group_layouts.surface_uniforms,
             entries: &[wgpu::BindGroupEntry {
                 binding: 0,
-                resource: sprite_uniforms_buffer.as_entire_binding(),
+                resource: surface_uniforms_buffer.as_entire_binding(),
             }],
         });
 
-        let vertices: [Vertex2d; 4] = [
-            Vertex2d {
-                // top-left
-                tex_coords: TextureCoordinates::top_left(),
-                plane_coords: RenderPlaneCoordinates { x: 0.0, y: 0.0 },
-            },
-            Vertex2d {
-                // top-right
-                tex_coords: TextureCoordinates::top_right(),
-                plane_coords: RenderPlaneCoordinates { x: 1.0, y: 0.0 },
-            },
-            Vertex2d {
-                // bottom-left
-                tex_coords: TextureCoordinates::bottom_left(),
-                plane_coords: RenderPlaneCoordinates { x: 0.0, y: 1.0 },
-            },
-            Vertex2d {
-                // bottom-right
-                tex_coords: TextureCoordinates::bottom_right(),
-                plane_coords: RenderPlaneCoordinates { x: 1.0, y: 1.0 },
-            },
-        ];
-
-        let indices: [u16; 6] = [0, 1, 2, 3, 2, 1];
-
-        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Vertex Buffer"),
-            contents: bytemuck::cast_slice(&vertices),
-            usage: wgpu::BufferUsages::VERTEX,
-        });
-        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Index Buffer"),
-            contents: bytemuck::cast_slice(&indices),
-            usage: wgpu::BufferUsages::INDEX,
+        let target = Rc::new(SurfaceTarget {
+            device: Rc::clone(&device),
+            queue: Rc::clone(&queue),
+            pipelines,
+            surface_texture: Rc::clone(&surface_texture),
+            dimensions,
+            surface_uniforms_bind_group,
+            camera: RefCell::new(None),
+            batch: RefCell::new(SpriteBatch::new(&device)),
+            readers: RefCell::new(Vec::new()),
         });
-        let num_indices = indices.len() as u32;
+        let retained = Rc::new(RefCell::new(RetainedLayer::new(&device)));
 
-        let mut encoder = self
-            .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                label: Some("surface_2d draw sprite encoder"),
-            });
-
-        {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render Pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &self.surface_texture.wgpu_texture_view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Load,
-                        store: wgpu::StoreOp::Store,
-                    },
-                })],
-                depth_stencil_attachment: None,
-                occlusion_query_set: None,
-                timestamp_writes: None,
-            });
-
-            render_pass.set_pipeline(&self.pipeline);
-            render_pass.set_bind_group(0, &subsurface.surface_texture.wgpu_bind_group, &[]);
-            render_pass.set_bind_group(1, &sprite_uniforms_bind_group, &[]);
-            render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
-            render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint16);
-            render_pass.draw_indexed(0..num_indices, 0, 0..1);
+        Self {
+            device,
+            queue,
+            texture_manager,
+            surface_uniforms_buffer,
+            target,
+            retained,
+            surface_texture,
+            dimensions,
         }
-
-        self.queue.submit(std::iter::once(encoder.finish()));
+    }
+    // drops any queued draws; the surface is cleared when it is next flushed
+    pub fn clear(&self) {
+        self.target.batch.borrow_mut().clear();
+    }
+    // submits every draw queued since the last flush in a single encoder, after flushing the
+    // surfaces that have queued draws of this one
+    pub fn flush(&self) {
+        self.target.flush();
     }
 
-    // copy a given texture to the surface buffer with parameters
-    pub fn draw_sprite(
-        &self,
-        sprite: &Sprite,
-        position: Option<PixelCoordinates>,
-        dimensions: Option<PixelDimensions>,
-        rotation: Option<SpriteRotation>,
-        //blend_mode: Option<BlendMode>
-    ) {
-        let device = &self.device;
-
-        let position = position.unwrap_or(PixelCoordinates { x: 0, y: 0 });
-
-        let dimensions = dimensions.unwrap_or(PixelDimensions {
-            width: sprite.dimensions.width,
-            height: sprite.dimensions.height,
-        });
-
-        let (rotation_matrix, rotation_center) = {
-            let rotation = rotation.unwrap_or(SpriteRotation {
-                rotation_matrix: cgmath::Matrix2::from_angle(cgmath::Rad(0.0)).into(),
-                rotation_center: None,
-            });
-
-            let rotation_center = rotation
-                .rotation_center
-                .unwrap_or(PixelCoordinates { x: 0, y: 0 });
-
-            (rotation.rotation_matrix, rotation_center)
-        };
+    // draws queued from here on are in the camera's world coordinates and clipped to its
+    // viewport, None draws in surface pixels again; draws queued before are flushed first
+    pub fn set_camera(&self, camera: Option<Camera2d>) {
+        self.flush();
 
-        let sprite_uniforms = SpriteUniforms {
+        let view = camera
+            .map(|camera| camera.view_transform())
+            .unwrap_or_default();
+        let surface_uniforms = SurfaceUniforms {
             render_target_dimensions: [self.dimensions.width as f32, self.dimensions.height as f32],
-            position: [position.x as f32, position.y as f32],
-            dimensions: [dimensions.width as f32, dimensions.height as f32],
-            rotation: rotation_matrix,
-            rotation_center: [rotation_center.x as f32, rotation_center.y as f32],
+            view: view.to_columns(),
         };
+        self.queue.write_buffer(
+            &self.surface_uniforms_buffer,
+            0,
+            bytemuck::cast_slice(&[surface_uniforms]),
+        );
 
-        let sprite_uniforms_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("sprite uniform buffer"),
-            contents: bytemuck::cast_slice(&[sprite_uniforms]),
-            usage: wgpu::BufferUsages::UNIFORM,
-        });
-
-        let sprite_uniforms_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("transform 2d bind group"),
-            layout: &self.bind_group_layouts.sprite_uniforms,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: sprite_uniforms_buffer.as_entire_binding(),
-            }],
-        });
+        *self.target.camera.borrow_mut() = camera;
+    }
+    pub fn camera(&self) -> Option<Camera2d> {
+        *self.target.camera.borrow()
+    }
 
-        let vertices: [Vertex2d; 4] = [
-            Vertex2d {
-                // top-left
-                tex_coords: sprite.vertices[0],
-                plane_coords: RenderPlaneCoordinates { x: 0.0, y: 0.0 },
-            },
-            Vertex2d {
-                // top-right
-                tex_coords: sprite.vertices[1],
-                plane_coords: RenderPlaneCoordinates { x: 1.0, y: 0.0 },
-            },
-            Vertex2d {
-                // bottom-left
-                tex_coords: sprite.vertices[2],
-                plane_coords: RenderPlaneCoordinates { x: 0.0, y: 1.0 },
-            },
-            Vertex2d {
-                // bottom-right
-                tex_coords: sprite.vertices[3],
-                plane_coords: RenderPlaneCoordinates { x: 1.0, y: 1.0 },
-            },
+    // the subsurface is drawn as it is now: it is flushed first, and since the queued draw
+    // samples it only when this surface is flushed, the subsurface flushes this surface before
+    // its next change lands; its pivot is the top-left
+    pub fn draw_subsurface(&self, subsurface: &PixelSurface, draw: &SpriteDraw) {
+        subsurface.flush();
+
+        let tex_coords = [
+            TextureCoordinates::top_left(),
+            TextureCoordinates::top_right(),
+            TextureCoordinates::bottom_left(),
+            TextureCoordinates::bottom_right(),
         ];
 
-        let indices: [u16; 6] = [0, 1, 2, 3, 2, 1];
+        self.queue_draw(
+            &subsurface.surface_texture,
+            tex_coords,
+            subsurface.dimensions,
+            SpritePivot::TopLeft,
+            draw,
+            draw.shading(),
+        );
+        subsurface.target.add_reader(&self.target);
+    }
 
-        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Vertex Buffer"),
-            contents: bytemuck::cast_slice(&vertices),
-            usage: wgpu::BufferUsages::VERTEX,
-        });
-        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Index Buffer"),
-            contents: bytemuck::cast_slice(&indices),
-            usage: wgpu::BufferUsages::INDEX,
-        });
-        let num_indices = indices.len() as u32;
+    // copy a given texture to the surface buffer with parameters
+    pub fn draw_sprite(&self, sprite: &Sprite, draw: &SpriteDraw) {
+        self.queue_draw(
+            &sprite.texture,
+            sprite.vertices,
+            sprite.dimensions,
+            sprite.pivot,
+            draw,
+            draw.shading(),
+        );
+    }
+    // draws a sprite of an indexed texture, its indices looked up in the palette, which takes
+    // the place of the draw's material
+    pub fn draw_indexed_sprite(&self, sprite: &Sprite, palette: &Palette, draw: &SpriteDraw) {
+        self.queue_draw(
+            &sprite.texture,
+            sprite.vertices,
+            sprite.dimensions,
+            sprite.pivot,
+            draw,
+            SpriteShading::Indexed(Rc::clone(palette.texture())),
+        );
+    }
+    // a sprite the surface keeps, drawn by draw_retained until the returned handle is dropped;
+    // the draw is as for draw_sprite and can be changed through the handle
+    pub fn create_retained_sprite(&self, sprite: &Sprite, draw: &SpriteDraw) -> RetainedSprite {
+        RetainedSprite::create(&self.retained, sprite, draw)
+    }
+    // draws every live retained sprite in one pass, over whatever was drawn before; their
+    // instances stay uploaded between calls, so only sprites changed since are written again
+    pub fn draw_retained(&self) {
+        self.flush();
+
+        let mut retained = self.retained.borrow_mut();
+        retained.prepare(&self.device, &self.queue);
+
+        if retained.is_empty() {
+            return;
+        }
+
+        self.target.flush_readers();
 
         let mut encoder = self
             .device
             .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                label: Some("surface_2d draw sprite encoder"),
+                label: Some("pixel surface retained encoder"),
             });
 
-        {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render Pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &self.surface_texture.wgpu_texture_view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Load,
-                        store: wgpu::StoreOp::Store,
-                    },
-                })],
-                depth_stencil_attachment: None,
-                occlusion_query_set: None,
-                timestamp_writes: None,
-            });
+        retained.batch().encode_pass(
+            &mut encoder,
+            &self.target.pipelines,
+            &self.target.batch_target(),
+        );
+
+        self.queue.submit(std::iter::once(encoder.finish()));
+    }
+    // takes over the other surface's retained sprites, handles included, as when the swap
+    // surface is recreated at a new size
+    pub(crate) fn adopt_retained(&mut self, other: &PixelSurface) {
+        self.retained = Rc::clone(&other.retained);
+    }
+    pub(crate) fn queue_draw(
+        &self,
+        texture: &Rc<Texture>,
+        tex_coords: [TextureCoordinates; 4],
+        source_dimensions: PixelDimensions,
+        pivot: SpritePivot,
+        draw: &SpriteDraw,
+        shading: SpriteShading,
+    ) {
+        let instance = sprite_instance(tex_coords, source_dimensions, pivot, draw);
 
-            render_pass.set_pipeline(&self.pipeline);
-            render_pass.set_bind_group(0, &sprite.texture.wgpu_bind_group, &[]);
-            render_pass.set_bind_group(1, &sprite_uniforms_bind_group, &[]);
-            render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
-            render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint16);
-            render_pass.draw_indexed(0..num_indices, 0, 0..1);
+        self.target
+            .batch
+            .borrow_mut()
+            .push(texture, draw.blend_mode, shading, instance);
+    }
+    fn queue_solid(&self, position: PixelCoordinates, dimensions: PixelDimensions, color: Color) {
+        if dimensions.width == 0 || dimensions.height == 0 {
+            return;
         }
 
-        self.queue.submit(std::iter::once(encoder.finish()));
+        self.target.batch.borrow_mut().push_solid(SolidInstance {
+            position: [position.x as f32, position.y as f32],
+            dimensions: [dimensions.width as f32, dimensions.height as f32],
+            color,
+        });
+    }
+    fn queue_spans(&self, spans: PixelSpans, color: Color) {
+        for (position, dimensions) in spans.into_rects() {
+            self.queue_solid(position, dimensions, color);
+        }
+    }
+    pub fn draw_line(&self, from: PixelCoordinates, to: PixelCoordinates, color: Color) {
+        let mut spans = PixelSpans::default();
+        spans.add_line(from, to);
+        self.queue_spans(spans, color);
+    }
+    // one pixel wide outline just inside the rect
+    pub fn draw_rect(&self, position: PixelCoordinates, dimensions: PixelDimensions, color: Color) {
+        let PixelDimensions { width, height } = dimensions;
+        if width <= 2 || height <= 2 {
+            self.fill_rect(position, dimensions, color);
+            return;
+        }
+
+        let (left, top) = (position.x, position.y);
+        let right = left + width as i32 - 1;
+        let bottom = top + height as i32 - 1;
+        let side = PixelDimensions {
+            width: 1,
+            height: height - 2,
+        };
+        let edge = PixelDimensions { width, height: 1 };
+
+        self.queue_solid(position, edge, color);
+        self.queue_solid(PixelCoordinates { x: left, y: bottom }, edge, color);
+        self.queue_solid(
+            PixelCoordinates {
+                x: left,
+                y: top + 1,
+            },
+            side,
+            color,
+        );
+        self.queue_solid(
+            PixelCoordinates {
+                x: right,
+                y: top + 1,
+            },
+            side,
+            color,
+        );
+    }
+    pub fn fill_rect(&self, position: PixelCoordinates, dimensions: PixelDimensions, color: Color) {
+        self.queue_solid(position, dimensions, color);
+    }
+    pub fn draw_circle(&self, center: PixelCoordinates, radius: u32, color: Color) {
+        let mut spans = PixelSpans::default();
+        spans.add_circle(center, radius);
+        self.queue_spans(spans, color);
+    }
+    pub fn fill_circle(&self, center: PixelCoordinates, radius: u32, color: Color) {
+        let mut spans = PixelSpans::default();
+        spans.add_filled_circle(center, radius);
+        self.queue_spans(spans, color);
+    }
+    // closed, the last point connects back to the first
+    pub fn draw_polygon(&self, points: &[PixelCoordinates], color: Color) {
+        let mut spans = PixelSpans::default();
+        spans.add_polygon(points);
+        self.queue_spans(spans, color);
+    }
+    pub fn fill_polygon(&self, points: &[PixelCoordinates], color: Color) {
+        let mut spans = PixelSpans::default();
+        spans.add_filled_polygon(points);
+        self.queue_spans(spans, color);
+    }
+    // antialiased, unlike the pixel-exact shapes above
+    pub fn fill_path(&self, path: &VectorPath, color: Color) {
+        let (vertices, indices) = path.fill_mesh(color);
+        self.target
+            .batch
+            .borrow_mut()
+            .push_path(&vertices, &indices);
+    }
+    pub fn stroke_path(&self, path: &VectorPath, style: StrokeStyle, color: Color) {
+        let (vertices, indices) = path.stroke_mesh(style, color);
+        self.target
+            .batch
+            .borrow_mut()
+            .push_path(&vertices, &indices);
+    }
+    // flushes queued draws, then copies the surface back off the GPU
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn read_pixels(&self) -> image::RgbaImage {
+        self.flush();
+        TextureReadback::copy_from(&self.device, &self.queue, &self.surface_texture)
+            .read(&self.device)
+    }
+    pub async fn read_pixels_async(&self) -> image::RgbaImage {
+        self.flush();
+        TextureReadback::copy_from(&self.device, &self.queue, &self.surface_texture)
+            .read_async(&self.device)
+            .await
     }
     pub fn load_texture(&self, path: &str) -> Rc<Texture> {
         self.texture_manager.load_texture(path)
@@ -512,3 +790,66 @@ impl PixelSurface {
         Sprite::create(texture, texture_area)
     }
 }
+
+// the instance of a sprite drawn with the given parameters, as queued by draw_sprite and kept by
+// retained sprites
+pub(crate) fn sprite_instance(
+    tex_coords: [TextureCoordinates; 4],
+    source_dimensions: PixelDimensions,
+    pivot: SpritePivot,
+    draw: &SpriteDraw,
+) -> SpriteInstance {
+    let SpriteDraw {
+        position,
+        dimensions,
+        scale,
+        transform,
+        blend_mode,
+        color,
+        ..
+    } = *draw;
+
+    let dimensions = dimensions.unwrap_or(source_dimensions);
+    let width = dimensions.width as f32 * scale.x.abs();
+    let height = dimensions.height as f32 * scale.y.abs();
+
+    // mirroring swaps the texture corners, and the pivot moves to the mirrored spot so
+    // that the sprite flips about it instead of about its center
+    let (mut pivot_x, mut pivot_y) = pivot.normalized();
+    let mut tex_coords = tex_coords;
+    if scale.x < 0.0 {
+        let [top_left, top_right, bottom_left, bottom_right] = tex_coords;
+        tex_coords = [top_right, top_left, bottom_right, bottom_left];
+        pivot_x = 1.0 - pivot_x;
+    }
+    if scale.y < 0.0 {
+        let [top_left, top_right, bottom_left, bottom_right] = tex_coords;
+        tex_coords = [bottom_left, bottom_right, top_left, top_right];
+        pivot_y = 1.0 - pivot_y;
+    }
+
+    // whole pixels, so that unscaled pixel art stays aligned to the surface's pixels
+    let pivot_offset = ((pivot_x * width).round(), (pivot_y * height).round());
+
+    // quad pixels to surface pixels: pivot to the origin, transform, then out to position
+    let transform = Transform2d::translation(-pivot_offset.0, -pivot_offset.1)
+        .then(&transform)
+        .translated(position.x as f32, position.y as f32);
+
+    SpriteInstance {
+        dimensions: [width, height],
+        transform: transform.to_columns(),
+        tex_coords,
+        tint: color.tint,
+        flash: color.flash,
+        opacity: color.opacity,
+        premultiplied: match blend_mode {
+            BlendMode::PremultipliedAlpha => 1.0,
+            _ => 0.0,
+        },
+        premultiply: match blend_mode {
+            BlendMode::Multiply | BlendMode::Screen => 1.0,
+            _ => 0.0,
+        },
+    }
+}
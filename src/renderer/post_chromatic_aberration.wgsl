@@ -0,0 +1,19 @@
+// splits red and blue away from the center, by offset pixels at the corners
+
+struct ChromaticAberrationParams {
+    offset: f32,
+}
+
+@group(1) @binding(1)
+var<uniform> params: ChromaticAberrationParams;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let shift = (in.tex_coords - 0.5) * 2.0 * params.offset / globals.resolution;
+
+    let red = textureSampleLevel(t_source, s_source, in.tex_coords + shift, 0.0);
+    let center = textureSampleLevel(t_source, s_source, in.tex_coords, 0.0);
+    let blue = textureSampleLevel(t_source, s_source, in.tex_coords - shift, 0.0);
+
+    return vec4<f32>(red.r, center.g, blue.b, center.a);
+}
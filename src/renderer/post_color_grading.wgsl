@@ -0,0 +1,42 @@
+// maps colors through a 3D lookup table in the extra texture, laid out as a strip of size
+// squares of size x size texels: red across each square, green down it and blue across squares
+
+struct ColorGradingParams {
+    amount: f32,
+    lut_size: f32,
+}
+
+@group(1) @binding(1)
+var<uniform> params: ColorGradingParams;
+
+fn linear_to_srgb(color: vec3<f32>) -> vec3<f32> {
+    let low = color * 12.92;
+    let high = 1.055 * pow(color, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(high, low, color <= vec3<f32>(0.0031308));
+}
+
+fn lut_texel(index: vec3<f32>) -> vec3<f32> {
+    let texel = vec2<i32>(i32(index.r + index.b * params.lut_size), i32(index.g));
+    return textureLoad(t_extra, texel, 0).rgb;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSampleLevel(t_source, s_source, in.tex_coords, 0.0);
+
+    // tables are authored against sRGB encoded colors, and their texels decode back to linear
+    let position = clamp(linear_to_srgb(color.rgb), vec3<f32>(0.0), vec3<f32>(1.0))
+        * (params.lut_size - 1.0);
+    let low = floor(position);
+    let high = min(low + 1.0, vec3<f32>(params.lut_size - 1.0));
+    let t = position - low;
+
+    // trilinear between the eight surrounding texels, since the sampler is nearest
+    let c00 = mix(lut_texel(low), lut_texel(vec3<f32>(high.r, low.g, low.b)), t.r);
+    let c10 = mix(lut_texel(vec3<f32>(low.r, high.g, low.b)), lut_texel(vec3<f32>(high.r, high.g, low.b)), t.r);
+    let c01 = mix(lut_texel(vec3<f32>(low.r, low.g, high.b)), lut_texel(vec3<f32>(high.r, low.g, high.b)), t.r);
+    let c11 = mix(lut_texel(vec3<f32>(low.r, high.g, high.b)), lut_texel(high), t.r);
+    let graded = mix(mix(c00, c10, t.g), mix(c01, c11, t.g), t.b);
+
+    return vec4<f32>(mix(color.rgb, graded, params.amount), color.a);
+}
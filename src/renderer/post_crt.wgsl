@@ -0,0 +1,28 @@
+// CRT tube: barrel curvature with black outside the tube, and darkened lines between scanlines
+
+struct CrtParams {
+    curvature: f32,
+    scanline_intensity: f32,
+    scanline_count: f32, // 0 for one per source row
+}
+
+@group(1) @binding(1)
+var<uniform> params: CrtParams;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let centered = in.tex_coords * 2.0 - 1.0;
+    let warped = centered + centered * (centered.yx * centered.yx) * params.curvature;
+    let tex_coords = warped * 0.5 + 0.5;
+
+    let color = textureSampleLevel(t_source, s_source, tex_coords, 0.0);
+
+    let count = select(params.scanline_count, globals.resolution.y, params.scanline_count <= 0.0);
+    let wave = 0.5 - 0.5 * cos(tex_coords.y * count * 6.28318530718);
+    let scanline = 1.0 - params.scanline_intensity * (1.0 - wave);
+
+    let inside = all(tex_coords >= vec2<f32>(0.0)) && all(tex_coords <= vec2<f32>(1.0));
+    let shaded = vec4<f32>(color.rgb * scanline, color.a);
+
+    return select(vec4<f32>(0.0, 0.0, 0.0, 1.0), shaded, inside);
+}
@@ -0,0 +1,41 @@
+// Shared by every post effect, whose own fragment shader is appended to this
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+}
+
+// a single triangle that covers the whole target
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    var out: VertexOutput;
+
+    let position = vec2<f32>(f32(index & 1u) * 4.0 - 1.0, f32(index >> 1u) * 4.0 - 1.0);
+    out.clip_position = vec4<f32>(position, 0.0, 1.0);
+    out.tex_coords = vec2<f32>(position.x + 1.0, 1.0 - position.y) * 0.5;
+
+    return out;
+}
+
+// the output of the previous effect, or the swap surface for the first one
+@group(0) @binding(0)
+var t_source: texture_2d<f32>;
+@group(0) @binding(1)
+var s_source: sampler;
+
+struct EffectGlobals {
+    resolution: vec2<f32>, // of the source, in pixels
+    time: f32,             // seconds, as set on the renderer
+    _padding: f32,
+}
+
+@group(1) @binding(0)
+var<uniform> globals: EffectGlobals;
+
+// the effect's own parameters go at @group(1) @binding(1)
+
+// the effect's extra texture, such as a lookup table; a single white texel when it has none
+@group(2) @binding(0)
+var t_extra: texture_2d<f32>;
+@group(2) @binding(1)
+var s_extra: sampler;
@@ -0,0 +1,18 @@
+// mixes towards the luminance of each pixel by amount
+
+struct GrayscaleParams {
+    amount: f32,
+}
+
+@group(1) @binding(1)
+var<uniform> params: GrayscaleParams;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSampleLevel(t_source, s_source, in.tex_coords, 0.0);
+
+    // the source is sampled as linear color, which these weights are for
+    let luminance = dot(color.rgb, vec3<f32>(0.2126, 0.7152, 0.0722));
+
+    return vec4<f32>(mix(color.rgb, vec3<f32>(luminance), params.amount), color.a);
+}
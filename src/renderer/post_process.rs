@@ -0,0 +1,334 @@
+use super::*;
+
+use bind_group_layouts::BindGroupLayouts;
+use pipelines::{checked, Pipelines};
+
+use std::cell::{Cell, RefCell};
+
+// prepended to every effect's fragment shader, see post_effect.wgsl for what it declares
+const EFFECT_PRELUDE: &str = include_str!("post_effect.wgsl");
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct EffectGlobals {
+    resolution: [f32; 2],
+    time: f32,
+    _padding: f32,
+}
+
+// effects that ship with the renderer; the same values update a created effect's parameters
+#[derive(Clone)]
+pub enum BuiltinEffect {
+    Crt {
+        curvature: f32,
+        scanline_intensity: f32,
+        scanline_count: f32, // 0 for one per surface row
+    },
+    Vignette {
+        intensity: f32,
+        radius: f32,   // from the center, where 1 is a corner
+        softness: f32, // distance over which it fades in
+    },
+    ChromaticAberration {
+        offset: f32, // pixels that red and blue split by at the corners
+    },
+    Grayscale {
+        amount: f32,
+    },
+    // lut is a strip of size squares of size x size texels: red across each square, green down
+    // it and blue across squares, as exported by most image editors
+    ColorGrading {
+        lut: Rc<Texture>,
+        amount: f32,
+    },
+}
+
+impl BuiltinEffect {
+    fn label(&self) -> &'static str {
+        match self {
+            BuiltinEffect::Crt { .. } => "crt post effect",
+            BuiltinEffect::Vignette { .. } => "vignette post effect",
+            BuiltinEffect::ChromaticAberration { .. } => "chromatic aberration post effect",
+            BuiltinEffect::Grayscale { .. } => "grayscale post effect",
+            BuiltinEffect::ColorGrading { .. } => "color grading post effect",
+        }
+    }
+    fn fragment_source(&self) -> &'static str {
+        match self {
+            BuiltinEffect::Crt { .. } => include_str!("post_crt.wgsl"),
+            BuiltinEffect::Vignette { .. } => include_str!("post_vignette.wgsl"),
+            BuiltinEffect::ChromaticAberration { .. } => {
+                include_str!("post_chromatic_aberration.wgsl")
+            }
+            BuiltinEffect::Grayscale { .. } => include_str!("post_grayscale.wgsl"),
+            BuiltinEffect::ColorGrading { .. } => include_str!("post_color_grading.wgsl"),
+        }
+    }
+    fn params(&self) -> [f32; 4] {
+        match self {
+            BuiltinEffect::Crt {
+                curvature,
+                scanline_intensity,
+                scanline_count,
+            } => [*curvature, *scanline_intensity, *scanline_count, 0.0],
+            BuiltinEffect::Vignette {
+                intensity,
+                radius,
+                softness,
+            } => [*intensity, *radius, *softness, 0.0],
+            BuiltinEffect::ChromaticAberration { offset } => [*offset, 0.0, 0.0, 0.0],
+            BuiltinEffect::Grayscale { amount } => [*amount, 0.0, 0.0, 0.0],
+            BuiltinEffect::ColorGrading { lut, amount } => {
+                [*amount, lut.dimensions.height as f32, 0.0, 0.0]
+            }
+        }
+    }
+    fn extra_texture(&self) -> Option<Rc<Texture>> {
+        match self {
+            BuiltinEffect::ColorGrading { lut, .. } => Some(Rc::clone(lut)),
+            _ => None,
+        }
+    }
+}
+
+// a full-screen fragment shader pass over the swap surface
+pub struct PostEffect {
+    pub label: String,
+    pub enabled: bool,
+    queue: Rc<wgpu::Queue>,
+    pipeline: wgpu::RenderPipeline,
+    params_buffer: wgpu::Buffer,
+    params_size: usize,
+    uniforms_bind_group: wgpu::BindGroup,
+    extra_texture: Rc<Texture>,
+}
+
+impl PostEffect {
+    // params beyond the size the effect was created with are dropped, and buffer writes come
+    // in whole 4 byte words
+    pub fn set_params(&self, params: &[u8]) {
+        let length = params.len().min(self.params_size) & !3;
+        self.queue
+            .write_buffer(&self.params_buffer, 0, &params[..length]);
+    }
+    pub fn set_builtin_params(&self, effect: &BuiltinEffect) {
+        self.set_params(bytemuck::cast_slice(&effect.params()));
+    }
+}
+
+// runs the enabled effects in order, ping-ponging between two surfaces sized like the source
+pub(crate) struct PostProcessor {
+    device: Rc<wgpu::Device>,
+    queue: Rc<wgpu::Queue>,
+    bind_group_layouts: Rc<BindGroupLayouts>,
+    pipelines: Rc<Pipelines>,
+    texture_manager: TextureManager,
+    pipeline_layout: wgpu::PipelineLayout,
+    globals_buffer: wgpu::Buffer,
+    blank_texture: Rc<Texture>,
+    targets: RefCell<Vec<PixelSurface>>,
+    pub effects: Vec<PostEffect>,
+    pub time: Cell<f32>,
+}
+
+impl PostProcessor {
+    pub(crate) fn new(
+        device: Rc<wgpu::Device>,
+        queue: Rc<wgpu::Queue>,
+        bind_group_layouts: Rc<BindGroupLayouts>,
+        pipelines: Rc<Pipelines>,
+        texture_manager: TextureManager,
+    ) -> Self {
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("post effect pipeline layout"),
+            bind_group_layouts: &[
+                &bind_group_layouts.texture,
+                &bind_group_layouts.post_effect_uniforms,
+                &bind_group_layouts.texture,
+            ],
+            push_constant_ranges: &[],
+        });
+
+        let globals_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("post effect globals buffer"),
+            size: std::mem::size_of::<EffectGlobals>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let blank_texture = Rc::new(texture_manager.create_texture(image::RgbaImage::from_pixel(
+            1,
+            1,
+            image::Rgba([255, 255, 255, 255]),
+        )));
+
+        Self {
+            device,
+            queue,
+            bind_group_layouts,
+            pipelines,
+            texture_manager,
+            pipeline_layout,
+            globals_buffer,
+            blank_texture,
+            targets: RefCell::new(Vec::new()),
+            effects: Vec::new(),
+            time: Cell::new(0.0),
+        }
+    }
+    // fragment_source is WGSL with an fs_main entry point, appended to post_effect.wgsl; a
+    // shader wgpu rejects comes back as the error, as for materials
+    pub fn create_effect(
+        &self,
+        label: &str,
+        fragment_source: &str,
+        params: &[u8],
+        extra_texture: Option<Rc<Texture>>,
+    ) -> Result<PostEffect, wgpu::Error> {
+        let source = format!("{}\n{}", EFFECT_PRELUDE, fragment_source);
+        let pipeline = checked(&self.device, || {
+            let shader = self
+                .device
+                .create_shader_module(wgpu::ShaderModuleDescriptor {
+                    label: Some(label),
+                    source: wgpu::ShaderSource::Wgsl(source.into()),
+                });
+
+            pipelines::surface_pipeline(
+                &self.device,
+                label,
+                &self.pipeline_layout,
+                &shader,
+                &[],
+                wgpu::BlendState::REPLACE,
+                None,
+            )
+        })?;
+
+        // uniform bindings are kept to whole 16 byte rows
+        let params_size = params.len().max(16).next_multiple_of(16);
+        let mut contents = params.to_vec();
+        contents.resize(params_size, 0);
+
+        let params_buffer = self
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("post effect params buffer"),
+                contents: &contents,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
+
+        let uniforms_bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("post effect uniforms bind group"),
+            layout: &self.bind_group_layouts.post_effect_uniforms,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: self.globals_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: params_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        Ok(PostEffect {
+            label: label.to_string(),
+            enabled: true,
+            queue: Rc::clone(&self.queue),
+            pipeline,
+            params_buffer,
+            params_size,
+            uniforms_bind_group,
+            extra_texture: extra_texture.unwrap_or_else(|| Rc::clone(&self.blank_texture)),
+        })
+    }
+    pub fn create_builtin_effect(&self, effect: BuiltinEffect) -> PostEffect {
+        self.create_effect(
+            effect.label(),
+            effect.fragment_source(),
+            bytemuck::cast_slice(&effect.params()),
+            effect.extra_texture(),
+        )
+        .expect("built-in post effect shaders compile")
+    }
+    // returns the texture holding the final result, which is the source's own texture when
+    // no effect is enabled
+    pub fn run(&self, source: &PixelSurface) -> Rc<Texture> {
+        let enabled: Vec<&PostEffect> = self.effects.iter().filter(|e| e.enabled).collect();
+
+        if enabled.is_empty() {
+            return Rc::clone(&source.surface_texture);
+        }
+
+        let mut targets = self.targets.borrow_mut();
+        if targets.len() != 2 || targets[0].dimensions != source.dimensions {
+            *targets = (0..2)
+                .map(|_| {
+                    PixelSurface::new(
+                        Rc::clone(&self.device),
+                        Rc::clone(&self.queue),
+                        Rc::clone(&self.bind_group_layouts),
+                        Rc::clone(&self.pipelines),
+                        self.texture_manager.clone(),
+                        source.dimensions.width,
+                        source.dimensions.height,
+                    )
+                })
+                .collect();
+        }
+
+        let globals = EffectGlobals {
+            resolution: [
+                source.dimensions.width as f32,
+                source.dimensions.height as f32,
+            ],
+            time: self.time.get(),
+            _padding: 0.0,
+        };
+        self.queue
+            .write_buffer(&self.globals_buffer, 0, bytemuck::cast_slice(&[globals]));
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("post effect encoder"),
+            });
+
+        let mut input = &source.surface_texture;
+
+        for (index, effect) in enabled.iter().enumerate() {
+            let output = &targets[index % 2].surface_texture;
+
+            {
+                let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some(&effect.label),
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view: &output.wgpu_texture_view,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                            store: wgpu::StoreOp::Store,
+                        },
+                    })],
+                    depth_stencil_attachment: None,
+                    occlusion_query_set: None,
+                    timestamp_writes: None,
+                });
+
+                render_pass.set_pipeline(&effect.pipeline);
+                render_pass.set_bind_group(0, &input.wgpu_bind_group, &[]);
+                render_pass.set_bind_group(1, &effect.uniforms_bind_group, &[]);
+                render_pass.set_bind_group(2, &effect.extra_texture.wgpu_bind_group, &[]);
+                render_pass.draw(0..3, 0..1);
+            }
+
+            input = output;
+        }
+
+        self.queue.submit(std::iter::once(encoder.finish()));
+
+        Rc::clone(input)
+    }
+}
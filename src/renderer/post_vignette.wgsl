@@ -0,0 +1,21 @@
+// darkens towards the corners, starting at radius from the center and fully in after softness
+
+struct VignetteParams {
+    intensity: f32,
+    radius: f32,
+    softness: f32,
+}
+
+@group(1) @binding(1)
+var<uniform> params: VignetteParams;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSampleLevel(t_source, s_source, in.tex_coords, 0.0);
+
+    // 0 at the center and 1 at the corners
+    let distance_from_center = length(in.tex_coords - 0.5) * 1.41421356237;
+    let falloff = smoothstep(params.radius, params.radius + params.softness, distance_from_center);
+
+    return vec4<f32>(color.rgb * (1.0 - params.intensity * falloff), color.a);
+}
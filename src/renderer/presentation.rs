@@ -0,0 +1,234 @@
+use super::*;
+
+// how Renderer::present fits the swap surface into the window; the uncovered parts of the
+// window are filled with the border color
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum PresentationScaling {
+    #[default]
+    Stretch, // covers the window, distorting the aspect ratio
+    AspectFit,    // as large as fits with the aspect ratio kept, letterboxed
+    IntegerScale, // the largest whole multiple that fits, so every pixel is the same size
+    FillCrop,     // covers the window with the aspect ratio kept, cropping the overflow
+}
+
+// how big Renderer keeps its swap surface as the window is resized
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum SurfaceResolution {
+    Fixed(PixelDimensions), // a virtual resolution, scaled up by the presentation scaling
+    #[default]
+    MatchWindow,
+    WindowDivided(u32), // a window sized surface at 1/N resolution, rounded up
+}
+
+impl SurfaceResolution {
+    pub fn dimensions(&self, window: PixelDimensions) -> PixelDimensions {
+        let dimensions = match *self {
+            SurfaceResolution::Fixed(dimensions) => dimensions,
+            SurfaceResolution::MatchWindow => window,
+            SurfaceResolution::WindowDivided(divisor) => {
+                let divisor = divisor.max(1);
+                PixelDimensions {
+                    width: window.width.div_ceil(divisor),
+                    height: window.height.div_ceil(divisor),
+                }
+            }
+        };
+
+        // textures can't be empty
+        PixelDimensions {
+            width: dimensions.width.max(1),
+            height: dimensions.height.max(1),
+        }
+    }
+}
+
+// where the surface lands in the window, in window pixels; may reach outside the window
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PresentationRect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl PresentationScaling {
+    pub fn rect(&self, surface: PixelDimensions, window: PixelDimensions) -> PresentationRect {
+        let (surface_width, surface_height) = (surface.width as f32, surface.height as f32);
+        let (window_width, window_height) = (window.width as f32, window.height as f32);
+
+        if surface.width == 0 || surface.height == 0 {
+            return PresentationRect {
+                x: 0.0,
+                y: 0.0,
+                width: window_width,
+                height: window_height,
+            };
+        }
+
+        let fit = (window_width / surface_width).min(window_height / surface_height);
+        let scale = match self {
+            PresentationScaling::Stretch => {
+                return PresentationRect {
+                    x: 0.0,
+                    y: 0.0,
+                    width: window_width,
+                    height: window_height,
+                }
+            }
+            PresentationScaling::AspectFit => fit,
+            // below 1x the surface is shrunk to fit after all rather than cropped
+            PresentationScaling::IntegerScale if fit >= 1.0 => fit.floor(),
+            PresentationScaling::IntegerScale => fit,
+            PresentationScaling::FillCrop => {
+                (window_width / surface_width).max(window_height / surface_height)
+            }
+        };
+
+        let width = surface_width * scale;
+        let height = surface_height * scale;
+
+        // whole window pixels, so integer scaled pixels all come out the same size
+        PresentationRect {
+            x: ((window_width - width) / 2.0).floor(),
+            y: ((window_height - height) / 2.0).floor(),
+            width,
+            height,
+        }
+    }
+}
+
+impl PresentationRect {
+    // None outside the rect, as over the letterbox borders
+    pub fn window_to_surface(
+        &self,
+        surface: PixelDimensions,
+        window_x: f32,
+        window_y: f32,
+    ) -> Option<PixelCoordinates> {
+        let u = (window_x - self.x) / self.width;
+        let v = (window_y - self.y) / self.height;
+
+        if !(0.0..1.0).contains(&u) || !(0.0..1.0).contains(&v) {
+            return None;
+        }
+
+        Some(PixelCoordinates {
+            x: (u * surface.width as f32) as i32,
+            y: (v * surface.height as f32) as i32,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dimensions(width: u32, height: u32) -> PixelDimensions {
+        PixelDimensions { width, height }
+    }
+
+    fn rect(x: f32, y: f32, width: f32, height: f32) -> PresentationRect {
+        PresentationRect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn stretch_covers_the_window() {
+        let presented =
+            PresentationScaling::Stretch.rect(dimensions(320, 180), dimensions(1000, 700));
+
+        assert_eq!(presented, rect(0.0, 0.0, 1000.0, 700.0));
+    }
+
+    #[test]
+    fn aspect_fit_letterboxes_the_longer_side() {
+        // twice the width fits, leaving 160 window pixels above and below
+        let presented =
+            PresentationScaling::AspectFit.rect(dimensions(320, 180), dimensions(640, 680));
+
+        assert_eq!(presented, rect(0.0, 160.0, 640.0, 360.0));
+    }
+
+    #[test]
+    fn integer_scale_keeps_to_whole_multiples() {
+        // 3.4x would fit, so 3x is centered with the rest as border
+        let presented =
+            PresentationScaling::IntegerScale.rect(dimensions(100, 50), dimensions(340, 200));
+
+        assert_eq!(presented, rect(20.0, 25.0, 300.0, 150.0));
+    }
+
+    #[test]
+    fn integer_scale_below_one_shrinks_to_fit() {
+        let presented =
+            PresentationScaling::IntegerScale.rect(dimensions(400, 200), dimensions(200, 200));
+
+        assert_eq!(presented, rect(0.0, 50.0, 200.0, 100.0));
+    }
+
+    #[test]
+    fn fill_crop_overflows_the_shorter_side() {
+        // twice the height covers the window, and the width overflows by 40 on each side
+        let presented =
+            PresentationScaling::FillCrop.rect(dimensions(100, 100), dimensions(120, 200));
+
+        assert_eq!(presented, rect(-40.0, 0.0, 200.0, 200.0));
+    }
+
+    #[test]
+    fn an_empty_surface_covers_the_window() {
+        let presented =
+            PresentationScaling::IntegerScale.rect(dimensions(0, 10), dimensions(300, 200));
+
+        assert_eq!(presented, rect(0.0, 0.0, 300.0, 200.0));
+    }
+
+    #[test]
+    fn window_positions_map_into_the_surface() {
+        let surface = dimensions(100, 50);
+        let presented = rect(20.0, 25.0, 300.0, 150.0);
+
+        assert_eq!(
+            presented.window_to_surface(surface, 20.0, 25.0),
+            Some(PixelCoordinates { x: 0, y: 0 })
+        );
+        assert_eq!(
+            presented.window_to_surface(surface, 25.9, 30.0),
+            Some(PixelCoordinates { x: 1, y: 1 })
+        );
+        assert_eq!(
+            presented.window_to_surface(surface, 319.9, 174.9),
+            Some(PixelCoordinates { x: 99, y: 49 })
+        );
+    }
+
+    #[test]
+    fn cursors_over_the_border_are_outside_the_surface() {
+        let surface = dimensions(100, 50);
+        let presented = rect(20.0, 25.0, 300.0, 150.0);
+
+        for (x, y) in [(19.9, 100.0), (320.0, 100.0), (100.0, 24.0), (100.0, 175.0)] {
+            assert_eq!(presented.window_to_surface(surface, x, y), None, "{x}, {y}");
+        }
+    }
+
+    #[test]
+    fn cropped_parts_of_the_surface_can_not_be_reached() {
+        let surface = dimensions(100, 100);
+        let presented = PresentationScaling::FillCrop.rect(surface, dimensions(120, 200));
+
+        // the window's left edge shows surface column 20, with the first 20 cropped away
+        assert_eq!(
+            presented.window_to_surface(surface, 0.0, 0.0),
+            Some(PixelCoordinates { x: 20, y: 0 })
+        );
+        assert_eq!(
+            presented.window_to_surface(surface, 119.0, 199.0),
+            Some(PixelCoordinates { x: 79, y: 99 })
+        );
+    }
+}
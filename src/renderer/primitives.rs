@@ -0,0 +1,321 @@
+use super::*;
+
+use std::collections::BTreeMap;
+
+// pixels collected as inclusive horizontal runs per row, so that pixels shared by several
+// segments of a shape are only drawn once and translucent colors blend evenly
+#[derive(Default)]
+pub struct PixelSpans {
+    rows: BTreeMap<i32, Vec<(i32, i32)>>,
+}
+
+impl PixelSpans {
+    pub fn add_pixel(&mut self, x: i32, y: i32) {
+        self.add_span(y, x, x);
+    }
+    pub fn add_span(&mut self, y: i32, start: i32, end: i32) {
+        let (start, end) = (start.min(end), start.max(end));
+        self.rows.entry(y).or_default().push((start, end));
+    }
+    // Bresenham, both endpoints included
+    pub fn add_line(&mut self, from: PixelCoordinates, to: PixelCoordinates) {
+        let (mut x, mut y) = (from.x, from.y);
+        let dx = (to.x - from.x).abs();
+        let dy = -(to.y - from.y).abs();
+        let step_x = if from.x < to.x { 1 } else { -1 };
+        let step_y = if from.y < to.y { 1 } else { -1 };
+        let mut error = dx + dy;
+
+        loop {
+            self.add_pixel(x, y);
+            if x == to.x && y == to.y {
+                break;
+            }
+            let doubled_error = 2 * error;
+            if doubled_error >= dy {
+                error += dy;
+                x += step_x;
+            }
+            if doubled_error <= dx {
+                error += dx;
+                y += step_y;
+            }
+        }
+    }
+    // midpoint circle; calls plot with every (x, y) of the first octant, x >= y
+    fn midpoint_circle(radius: u32, mut plot: impl FnMut(i32, i32)) {
+        let mut x = radius as i32;
+        let mut y = 0;
+        let mut error = 1 - x;
+
+        while x >= y {
+            plot(x, y);
+            y += 1;
+            if error < 0 {
+                error += 2 * y + 1;
+            } else {
+                x -= 1;
+                error += 2 * (y - x) + 1;
+            }
+        }
+    }
+    pub fn add_circle(&mut self, center: PixelCoordinates, radius: u32) {
+        PixelSpans::midpoint_circle(radius, |x, y| {
+            for (dx, dy) in [(x, y), (y, x)] {
+                self.add_pixel(center.x + dx, center.y + dy);
+                self.add_pixel(center.x - dx, center.y + dy);
+                self.add_pixel(center.x + dx, center.y - dy);
+                self.add_pixel(center.x - dx, center.y - dy);
+            }
+        });
+    }
+    // every row spans between the outline pixels of add_circle
+    pub fn add_filled_circle(&mut self, center: PixelCoordinates, radius: u32) {
+        PixelSpans::midpoint_circle(radius, |x, y| {
+            for (dx, dy) in [(x, y), (y, x)] {
+                self.add_span(center.y + dy, center.x - dx, center.x + dx);
+                self.add_span(center.y - dy, center.x - dx, center.x + dx);
+            }
+        });
+    }
+    // closed outline through the points
+    pub fn add_polygon(&mut self, points: &[PixelCoordinates]) {
+        for (index, &from) in points.iter().enumerate() {
+            let to = points[(index + 1) % points.len()];
+            self.add_line(from, to);
+        }
+    }
+    // even-odd fill of the pixels whose centers are inside, plus the outline so the filled
+    // shape covers exactly what add_polygon does
+    pub fn add_filled_polygon(&mut self, points: &[PixelCoordinates]) {
+        if points.len() < 3 {
+            self.add_polygon(points);
+            return;
+        }
+
+        let top = points.iter().map(|point| point.y).min().unwrap_or(0);
+        let bottom = points.iter().map(|point| point.y).max().unwrap_or(0);
+
+        let mut crossings = Vec::new();
+
+        for y in top..=bottom {
+            // edges are half-open in y so a vertex between two edges is crossed once
+            let sample_y = y as f32;
+            crossings.clear();
+
+            for (index, from) in points.iter().enumerate() {
+                let to = points[(index + 1) % points.len()];
+                let (from_y, to_y) = (from.y as f32, to.y as f32);
+
+                if (from_y <= sample_y) != (to_y <= sample_y) {
+                    let t = (sample_y - from_y) / (to_y - from_y);
+                    crossings.push(from.x as f32 + t * (to.x - from.x) as f32);
+                }
+            }
+
+            crossings.sort_by(f32::total_cmp);
+
+            for pair in crossings.chunks_exact(2) {
+                let start = pair[0].ceil() as i32;
+                let end = pair[1].floor() as i32;
+                if start <= end {
+                    self.add_span(y, start, end);
+                }
+            }
+        }
+
+        self.add_polygon(points);
+    }
+    // merges overlapping and touching runs, returning one rect per run
+    pub fn into_rects(self) -> Vec<(PixelCoordinates, PixelDimensions)> {
+        let mut rects = Vec::new();
+
+        for (y, mut spans) in self.rows {
+            spans.sort_unstable();
+
+            let mut merged: Vec<(i32, i32)> = Vec::new();
+            for (start, end) in spans {
+                match merged.last_mut() {
+                    Some(last) if start <= last.1 + 1 => last.1 = last.1.max(end),
+                    _ => merged.push((start, end)),
+                }
+            }
+
+            rects.extend(merged.into_iter().map(|(start, end)| {
+                (
+                    PixelCoordinates { x: start, y },
+                    PixelDimensions {
+                        width: (end - start + 1) as u32,
+                        height: 1,
+                    },
+                )
+            }));
+        }
+
+        rects
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::BTreeSet;
+
+    fn point(x: i32, y: i32) -> PixelCoordinates {
+        PixelCoordinates { x, y }
+    }
+
+    fn pixels(spans: PixelSpans) -> BTreeSet<(i32, i32)> {
+        rect_pixels(spans.into_rects())
+    }
+
+    fn rect_pixels(rects: Vec<(PixelCoordinates, PixelDimensions)>) -> BTreeSet<(i32, i32)> {
+        rects
+            .into_iter()
+            .flat_map(|(position, dimensions)| {
+                (0..dimensions.width as i32).map(move |dx| (position.x + dx, position.y))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn line_includes_both_endpoints_once() {
+        let mut spans = PixelSpans::default();
+        spans.add_line(point(1, 1), point(7, 4));
+        let line = pixels(spans);
+
+        assert!(line.contains(&(1, 1)));
+        assert!(line.contains(&(7, 4)));
+        // one pixel per step along the major axis
+        assert_eq!(line.len(), 7);
+    }
+
+    #[test]
+    fn steep_line_has_one_pixel_per_row() {
+        let mut spans = PixelSpans::default();
+        spans.add_line(point(0, 0), point(2, -6));
+        let line = pixels(spans);
+
+        assert_eq!(line.len(), 7);
+        for y in -6..=0 {
+            assert_eq!(line.iter().filter(|pixel| pixel.1 == y).count(), 1);
+        }
+    }
+
+    #[test]
+    fn line_covers_the_same_pixels_both_ways() {
+        let mut forward = PixelSpans::default();
+        forward.add_line(point(0, 0), point(5, 5));
+        let mut backward = PixelSpans::default();
+        backward.add_line(point(5, 5), point(0, 0));
+
+        assert_eq!(pixels(forward), pixels(backward));
+    }
+
+    #[test]
+    fn circle_of_radius_zero_is_its_center() {
+        let mut spans = PixelSpans::default();
+        spans.add_circle(point(3, 4), 0);
+
+        assert_eq!(pixels(spans), BTreeSet::from([(3, 4)]));
+    }
+
+    #[test]
+    fn circle_outline_is_symmetric_and_on_the_radius() {
+        let mut spans = PixelSpans::default();
+        spans.add_circle(point(0, 0), 5);
+        let outline = pixels(spans);
+
+        for &(x, y) in &outline {
+            assert!(outline.contains(&(-x, y)) && outline.contains(&(x, -y)));
+            assert!(outline.contains(&(y, x)));
+            let distance = ((x * x + y * y) as f32).sqrt();
+            assert!((distance - 5.0).abs() < 1.0, "({x}, {y}) is off the radius");
+        }
+        for extreme in [(5, 0), (-5, 0), (0, 5), (0, -5)] {
+            assert!(outline.contains(&extreme));
+        }
+    }
+
+    #[test]
+    fn filled_circle_covers_its_outline_without_gaps() {
+        let mut outline = PixelSpans::default();
+        outline.add_circle(point(2, 2), 4);
+        let outline = pixels(outline);
+
+        let mut filled = PixelSpans::default();
+        filled.add_filled_circle(point(2, 2), 4);
+        let filled = filled.into_rects();
+
+        // one run per row
+        assert_eq!(filled.len(), 9);
+        assert!(outline.is_subset(&rect_pixels(filled)));
+    }
+
+    #[test]
+    fn polygon_outline_closes_back_to_the_first_point() {
+        let mut spans = PixelSpans::default();
+        spans.add_polygon(&[point(0, 0), point(4, 0), point(4, 3)]);
+
+        let mut edges = PixelSpans::default();
+        edges.add_line(point(0, 0), point(4, 0));
+        edges.add_line(point(4, 0), point(4, 3));
+        edges.add_line(point(4, 3), point(0, 0));
+
+        assert_eq!(pixels(spans), pixels(edges));
+    }
+
+    #[test]
+    fn filled_rectangle_is_one_run_per_row() {
+        let mut spans = PixelSpans::default();
+        spans.add_filled_polygon(&[point(1, 1), point(5, 1), point(5, 3), point(1, 3)]);
+
+        let rects = spans.into_rects();
+        assert_eq!(rects.len(), 3);
+        for (row, (position, dimensions)) in rects.into_iter().enumerate() {
+            assert_eq!(position, point(1, 1 + row as i32));
+            assert_eq!(dimensions.width, 5);
+        }
+    }
+
+    #[test]
+    fn filled_polygon_leaves_the_notch_of_a_concave_shape() {
+        // a U, open at the top between x 2 and 4
+        let mut spans = PixelSpans::default();
+        spans.add_filled_polygon(&[
+            point(0, 0),
+            point(2, 0),
+            point(2, 4),
+            point(4, 4),
+            point(4, 0),
+            point(6, 0),
+            point(6, 6),
+            point(0, 6),
+        ]);
+        let filled = pixels(spans);
+
+        assert!(!filled.contains(&(3, 1)));
+        assert!(!filled.contains(&(3, 3)));
+        assert!(filled.contains(&(1, 2)) && filled.contains(&(5, 2)));
+        assert!(filled.contains(&(3, 5)));
+    }
+
+    #[test]
+    fn into_rects_merges_overlapping_and_touching_runs() {
+        let mut spans = PixelSpans::default();
+        spans.add_span(0, 0, 2);
+        spans.add_span(0, 3, 4);
+        spans.add_span(0, 1, 1);
+        spans.add_span(0, 7, 6);
+
+        let rects = spans.into_rects();
+        assert_eq!(
+            rects
+                .iter()
+                .map(|(position, dimensions)| (position.x, dimensions.width))
+                .collect::<Vec<_>>(),
+            [(0, 5), (6, 2)]
+        );
+    }
+}
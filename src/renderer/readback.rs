@@ -0,0 +1,173 @@
+use super::*;
+
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+};
+
+// a texture copied into a mappable buffer, rows padded to COPY_BYTES_PER_ROW_ALIGNMENT
+pub struct TextureReadback {
+    buffer: wgpu::Buffer,
+    dimensions: PixelDimensions,
+    padded_bytes_per_row: u32,
+}
+
+impl TextureReadback {
+    pub fn copy_from(device: &wgpu::Device, queue: &wgpu::Queue, texture: &Texture) -> Self {
+        let dimensions = texture.dimensions;
+
+        let unpadded_bytes_per_row = 4 * dimensions.width;
+        let alignment = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let padded_bytes_per_row = unpadded_bytes_per_row.div_ceil(alignment) * alignment;
+
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("readback buffer"),
+            size: (padded_bytes_per_row * dimensions.height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("readback encoder"),
+        });
+
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture: &texture.wgpu_texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: Some(dimensions.height),
+                },
+            },
+            wgpu::Extent3d {
+                width: dimensions.width,
+                height: dimensions.height,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        queue.submit(std::iter::once(encoder.finish()));
+
+        Self {
+            buffer,
+            dimensions,
+            padded_bytes_per_row,
+        }
+    }
+    // blocks on the device until the copy has landed; not available on wasm
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn read(self, device: &wgpu::Device) -> image::RgbaImage {
+        let (sender, receiver) = std::sync::mpsc::channel();
+
+        self.buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                let _ = sender.send(result);
+            });
+
+        device.poll(wgpu::Maintain::Wait);
+
+        receiver
+            .recv()
+            .expect("Readback buffer was dropped before it was mapped.")
+            .expect("Could not map readback buffer.");
+
+        self.to_image()
+    }
+    pub async fn read_async(self, device: &wgpu::Device) -> image::RgbaImage {
+        let state = Arc::new(Mutex::new(MapState::default()));
+
+        let callback_state = Arc::clone(&state);
+        self.buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                let mut state = callback_state.lock().unwrap();
+                state.result = Some(result);
+                if let Some(waker) = state.waker.take() {
+                    waker.wake();
+                }
+            });
+
+        MapFuture { device, state }
+            .await
+            .expect("Could not map readback buffer.");
+
+        self.to_image()
+    }
+    // strips the row padding from the mapped buffer
+    fn to_image(&self) -> image::RgbaImage {
+        let unpadded_bytes_per_row = (4 * self.dimensions.width) as usize;
+
+        let pixels = {
+            let mapped = self.buffer.slice(..).get_mapped_range();
+            let mut pixels =
+                Vec::with_capacity(unpadded_bytes_per_row * self.dimensions.height as usize);
+            for row in mapped.chunks(self.padded_bytes_per_row as usize) {
+                pixels.extend_from_slice(&row[..unpadded_bytes_per_row]);
+            }
+            pixels
+        };
+        self.buffer.unmap();
+
+        image::RgbaImage::from_raw(self.dimensions.width, self.dimensions.height, pixels)
+            .expect("Readback buffer is smaller than its texture.")
+    }
+}
+
+#[derive(Default)]
+struct MapState {
+    result: Option<Result<(), wgpu::BufferAsyncError>>,
+    waker: Option<Waker>,
+}
+
+// resolves once map_async has called back, polling the device until it does
+struct MapFuture<'a> {
+    device: &'a wgpu::Device,
+    state: Arc<Mutex<MapState>>,
+}
+
+impl<'a> Future for MapFuture<'a> {
+    type Output = Result<(), wgpu::BufferAsyncError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.device.poll(wgpu::Maintain::Poll);
+
+        let mut state = self.state.lock().unwrap();
+        match state.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                // native and WebGL backends only call back from inside device.poll, so poll again
+                // soon; on WebGPU polling is a no-op and the callback wakes the future itself
+                wake_soon(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+// from a timeout on the web, so the browser gets to run and signal the GL fence in between
+#[cfg(target_arch = "wasm32")]
+fn wake_soon(waker: Waker) {
+    use wasm_bindgen::{closure::Closure, JsCast};
+
+    let callback = Closure::once_into_js(move || waker.wake());
+    web_sys::window()
+        .expect("Readback needs a browser window to poll from.")
+        .set_timeout_with_callback(callback.unchecked_ref())
+        .expect("Could not schedule a readback poll.");
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn wake_soon(waker: Waker) {
+    waker.wake();
+}
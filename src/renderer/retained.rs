@@ -0,0 +1,304 @@
+use super::*;
+
+use pixel_surface::{sprite_instance, Sprite, SpriteColor, SpriteDraw, SpriteScale};
+use sprite_batch::{SpriteBatch, SpriteInstance};
+use transform::Transform2d;
+
+use std::cell::RefCell;
+
+// where a retained sprite goes among the others
+#[derive(Clone, Copy)]
+struct Placement {
+    depth: i32,
+    visible: bool,
+    sequence: u64, // creation order, which breaks depth ties
+}
+
+// what a retained sprite was last set to, from which its instance is rebuilt
+struct RetainedEntry {
+    sprite: Sprite,
+    draw: SpriteDraw,
+    placement: Placement,
+    batch_index: Option<u32>, // where its instance is in the uploaded batch, None when hidden
+    dirty: bool,              // queued in dirty_ids
+}
+
+impl RetainedEntry {
+    fn instance(&self) -> SpriteInstance {
+        sprite_instance(
+            self.sprite.vertices,
+            self.sprite.dimensions,
+            self.sprite.pivot,
+            &self.draw,
+        )
+    }
+}
+
+// the retained sprites of a surface, kept uploaded in a batch of their own; the batch is only
+// rebuilt when sprites come, go, reorder or change texture, other changes are written in place
+pub(crate) struct RetainedLayer {
+    entries: Vec<Option<RetainedEntry>>, // indexed by handle id, None for free ids
+    free_ids: Vec<usize>,
+    next_sequence: u64,
+    batch: SpriteBatch,
+    layout_dirty: bool,
+    dirty_ids: Vec<usize>, // changed in place since the last upload, each id once
+}
+
+impl RetainedLayer {
+    pub fn new(device: &wgpu::Device) -> Self {
+        Self {
+            entries: Vec::new(),
+            free_ids: Vec::new(),
+            next_sequence: 0,
+            batch: SpriteBatch::new(device),
+            layout_dirty: false,
+            dirty_ids: Vec::new(),
+        }
+    }
+    fn insert(&mut self, entry: RetainedEntry) -> usize {
+        self.layout_dirty = true;
+
+        match self.free_ids.pop() {
+            Some(id) => {
+                self.entries[id] = Some(entry);
+                id
+            }
+            None => {
+                self.entries.push(Some(entry));
+                self.entries.len() - 1
+            }
+        }
+    }
+    fn remove(&mut self, id: usize) {
+        self.entries[id] = None;
+        self.free_ids.push(id);
+        self.layout_dirty = true;
+    }
+    fn entry_mut(&mut self, id: usize) -> &mut RetainedEntry {
+        self.entries[id]
+            .as_mut()
+            .expect("Retained sprite outlived its entry.")
+    }
+    pub fn is_empty(&self) -> bool {
+        self.batch.is_empty()
+    }
+    // brings the uploaded batch up to date with the entries
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        if self.layout_dirty {
+            for entry in self.entries.iter_mut().flatten() {
+                entry.batch_index = None;
+                entry.dirty = false;
+            }
+            let order = draw_order(
+                self.entries
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(id, entry)| Some((id, entry.as_ref()?.placement))),
+            );
+
+            // consecutive sprites of a texture still share a draw call, as with draw_sprite
+            self.batch.reset();
+            for (index, id) in order.into_iter().enumerate() {
+                let Some(entry) = &mut self.entries[id] else {
+                    continue;
+                };
+                entry.batch_index = Some(index as u32);
+                self.batch.push(
+                    &entry.sprite.texture,
+                    entry.draw.blend_mode,
+                    entry.draw.shading(),
+                    entry.instance(),
+                );
+            }
+            self.batch.upload(device, queue);
+
+            self.layout_dirty = false;
+            self.dirty_ids.clear();
+            return;
+        }
+
+        for id in self.dirty_ids.drain(..) {
+            let Some(entry) = &mut self.entries[id] else {
+                continue;
+            };
+            entry.dirty = false;
+            if let Some(index) = entry.batch_index {
+                self.batch.write_instance(queue, index, entry.instance());
+            }
+        }
+    }
+    pub fn batch(&self) -> &SpriteBatch {
+        &self.batch
+    }
+}
+
+// ids of the visible sprites, lowest depth first and in creation order within a depth
+fn draw_order(placements: impl Iterator<Item = (usize, Placement)>) -> Vec<usize> {
+    let mut order: Vec<(usize, Placement)> = placements
+        .filter(|(_, placement)| placement.visible)
+        .collect();
+    order.sort_by_key(|(_, placement)| (placement.depth, placement.sequence));
+    order.into_iter().map(|(id, _)| id).collect()
+}
+
+// a sprite kept by its surface and drawn by every draw_retained until the handle is dropped;
+// the setters update it in place rather than queueing it again
+pub struct RetainedSprite {
+    layer: Rc<RefCell<RetainedLayer>>,
+    id: usize,
+}
+
+impl RetainedSprite {
+    pub(crate) fn create(
+        layer: &Rc<RefCell<RetainedLayer>>,
+        sprite: &Sprite,
+        draw: &SpriteDraw,
+    ) -> Self {
+        let mut retained = layer.borrow_mut();
+
+        let sequence = retained.next_sequence;
+        retained.next_sequence += 1;
+
+        let id = retained.insert(RetainedEntry {
+            sprite: sprite.clone(),
+            draw: draw.clone(),
+            placement: Placement {
+                depth: 0,
+                visible: true,
+                sequence,
+            },
+            batch_index: None,
+            dirty: false,
+        });
+
+        Self {
+            layer: Rc::clone(layer),
+            id,
+        }
+    }
+    // changes that keep the sprite's place in the batch
+    fn update(&self, change: impl FnOnce(&mut RetainedEntry)) {
+        let mut layer = self.layer.borrow_mut();
+        let entry = layer.entry_mut(self.id);
+        change(entry);
+
+        if !entry.dirty {
+            entry.dirty = true;
+            layer.dirty_ids.push(self.id);
+        }
+    }
+    // changes that take a rebuild of the batch
+    fn update_layout(&self, change: impl FnOnce(&mut RetainedEntry)) {
+        let mut layer = self.layer.borrow_mut();
+        change(layer.entry_mut(self.id));
+        layer.layout_dirty = true;
+    }
+    pub fn set_position(&self, position: PixelCoordinates) {
+        self.update(|entry| entry.draw.position = position);
+    }
+    pub fn set_dimensions(&self, dimensions: Option<PixelDimensions>) {
+        self.update(|entry| entry.draw.dimensions = dimensions);
+    }
+    pub fn set_scale(&self, scale: SpriteScale) {
+        self.update(|entry| entry.draw.scale = scale);
+    }
+    pub fn set_transform(&self, transform: Transform2d) {
+        self.update(|entry| entry.draw.transform = transform);
+    }
+    pub fn set_color(&self, color: SpriteColor) {
+        self.update(|entry| entry.draw.color = color);
+    }
+    pub fn set_tint(&self, tint: Color) {
+        self.update(|entry| entry.draw.color.tint = tint);
+    }
+    // a new blend mode or material can split or join draw calls, so this rebuilds the batch
+    pub fn set_draw(&self, draw: &SpriteDraw) {
+        self.update_layout(|entry| entry.draw = draw.clone());
+    }
+    // another frame of the same texture, as from an atlas or sprite sheet, is updated in place
+    pub fn set_frame(&self, sprite: &Sprite) {
+        let same_texture = {
+            let mut layer = self.layer.borrow_mut();
+            Rc::ptr_eq(&layer.entry_mut(self.id).sprite.texture, &sprite.texture)
+        };
+
+        if same_texture {
+            self.update(|entry| entry.sprite = sprite.clone());
+        } else {
+            self.update_layout(|entry| entry.sprite = sprite.clone());
+        }
+    }
+    pub fn set_visible(&self, visible: bool) {
+        self.update_layout(|entry| entry.placement.visible = visible);
+    }
+    // retained sprites are drawn lowest depth first, then in the order they were created
+    pub fn set_depth(&self, depth: i32) {
+        self.update_layout(|entry| entry.placement.depth = depth);
+    }
+}
+
+impl Drop for RetainedSprite {
+    fn drop(&mut self) {
+        self.layer.borrow_mut().remove(self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn placements(sprites: &[(i32, bool)]) -> impl Iterator<Item = (usize, Placement)> + '_ {
+        sprites.iter().enumerate().map(|(id, &(depth, visible))| {
+            (
+                id,
+                Placement {
+                    depth,
+                    visible,
+                    sequence: id as u64,
+                },
+            )
+        })
+    }
+
+    #[test]
+    fn lower_depths_are_drawn_first() {
+        assert_eq!(
+            draw_order(placements(&[(2, true), (-1, true), (0, true)])),
+            [1, 2, 0]
+        );
+    }
+
+    #[test]
+    fn equal_depths_keep_creation_order() {
+        assert_eq!(
+            draw_order(placements(&[(1, true), (0, true), (1, true), (0, true)])),
+            [1, 3, 0, 2]
+        );
+    }
+
+    #[test]
+    fn sequence_rather_than_id_breaks_ties() {
+        // a reused id is newer than the sprites created before it
+        let order = draw_order([(0, 5), (1, 3), (2, 4)].into_iter().map(|(id, sequence)| {
+            (
+                id,
+                Placement {
+                    depth: 0,
+                    visible: true,
+                    sequence,
+                },
+            )
+        }));
+
+        assert_eq!(order, [1, 2, 0]);
+    }
+
+    #[test]
+    fn hidden_sprites_are_left_out() {
+        assert_eq!(
+            draw_order(placements(&[(0, false), (1, true), (-5, false)])),
+            [1]
+        );
+    }
+}
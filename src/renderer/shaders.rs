@@ -1,17 +1,50 @@
+// vertex stage of draw_sprite, which diffuse2d.wgsl and material fragment shaders are appended to
+pub(crate) const SPRITE_PRELUDE: &str = include_str!("sprite2d.wgsl");
+
 pub struct Shaders {
     pub diffuse2d: wgpu::ShaderModule,
+    pub palette2d: wgpu::ShaderModule,
+    pub solid2d: wgpu::ShaderModule,
+    pub path2d: wgpu::ShaderModule,
     pub window_surface_refresh: wgpu::ShaderModule,
 }
 
 impl Shaders {
     pub fn create(device: &wgpu::Device) -> Self {
-        let draw_sprite = device.create_shader_module(wgpu::include_wgsl!("diffuse2d.wgsl"));
+        let draw_sprite = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("diffuse2d.wgsl"),
+            source: wgpu::ShaderSource::Wgsl(
+                concat!(
+                    include_str!("sprite2d.wgsl"),
+                    include_str!("diffuse2d.wgsl")
+                )
+                .into(),
+            ),
+        });
+
+        let palette2d = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("palette2d.wgsl"),
+            source: wgpu::ShaderSource::Wgsl(
+                concat!(
+                    include_str!("sprite2d.wgsl"),
+                    include_str!("palette2d.wgsl")
+                )
+                .into(),
+            ),
+        });
+
+        let solid2d = device.create_shader_module(wgpu::include_wgsl!("solid2d.wgsl"));
+
+        let path2d = device.create_shader_module(wgpu::include_wgsl!("path2d.wgsl"));
 
         let window_surface_refresh =
             device.create_shader_module(wgpu::include_wgsl!("window_refresh.wgsl"));
 
         Self {
             diffuse2d: draw_sprite,
+            palette2d,
+            solid2d,
+            path2d,
             window_surface_refresh,
         }
     }
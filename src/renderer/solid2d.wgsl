@@ -0,0 +1,55 @@
+// Vertex shader
+
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+}
+
+struct InstanceInput {
+    @location(2) position: vec2<f32>,
+    @location(3) dimensions: vec2<f32>,
+    @location(4) color: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+}
+
+struct SurfaceUniform {
+    render_target_dimensions: vec2<f32>,
+    // the camera, from world to surface pixels, as the columns of a mat3x2; a mat3x2 member
+    // would be laid out with padded columns on GL, which the buffer doesn't have
+    view_0: vec2<f32>,
+    view_1: vec2<f32>,
+    view_2: vec2<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> surface: SurfaceUniform;
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+    instance: InstanceInput,
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.color = instance.color;
+
+    let placed = model.position * instance.dimensions + instance.position;
+    let view = mat3x2<f32>(surface.view_0, surface.view_1, surface.view_2);
+    let translated = view * vec3<f32>(placed, 1.0);
+    let flipped_for_renderer = vec2<f32>(translated.x, -translated.y);
+    let scaled_to_renderer = (flipped_for_renderer / surface.render_target_dimensions) * 2.0;
+    let translated_to_render_coords = scaled_to_renderer + vec2<f32>(-1.0, 1.0);
+
+    out.clip_position = vec4<f32>(translated_to_render_coords, 0.0, 1.0);
+
+    return out;
+}
+
+// Fragment shader
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return in.color;
+}
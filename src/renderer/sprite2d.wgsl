@@ -0,0 +1,92 @@
+// Vertex stage and bindings shared by diffuse2d.wgsl and every Material; a fragment shader
+// appended to this samples t_diffuse at in.tex_coords and passes the texel to sprite_color
+
+
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) tex_coords: vec2<f32>,
+}
+
+struct InstanceInput {
+    @location(2) dimensions: vec2<f32>,
+    @location(3) transform_0: vec2<f32>,
+    @location(4) transform_1: vec2<f32>,
+    @location(5) transform_2: vec2<f32>,
+    @location(6) tex_top_left: vec2<f32>,
+    @location(7) tex_top_right: vec2<f32>,
+    @location(8) tex_bottom_left: vec2<f32>,
+    @location(9) tex_bottom_right: vec2<f32>,
+    @location(10) tint: vec4<f32>,
+    @location(11) flash: vec4<f32>,
+    @location(12) alpha: vec3<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) tint: vec4<f32>,
+    @location(2) flash: vec4<f32>,
+    @location(3) alpha: vec3<f32>,
+}
+
+struct SurfaceUniform {
+    render_target_dimensions: vec2<f32>,
+    // the camera, from world to surface pixels, as the columns of a mat3x2; a mat3x2 member
+    // would be laid out with padded columns on GL, which the buffer doesn't have
+    view_0: vec2<f32>,
+    view_1: vec2<f32>,
+    view_2: vec2<f32>,
+}
+
+@group(1) @binding(0)
+var<uniform> surface: SurfaceUniform;
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+    instance: InstanceInput,
+) -> VertexOutput {
+    var out: VertexOutput;
+
+    // the quad's own tex coords pick the matching corner of the sprite's texture area
+    let tex_top = mix(instance.tex_top_left, instance.tex_top_right, model.tex_coords.x);
+    let tex_bottom = mix(instance.tex_bottom_left, instance.tex_bottom_right, model.tex_coords.x);
+    out.tex_coords = mix(tex_top, tex_bottom, model.tex_coords.y);
+    out.tint = instance.tint;
+    out.flash = instance.flash;
+    out.alpha = instance.alpha;
+
+    let transform = mat3x2<f32>(instance.transform_0, instance.transform_1, instance.transform_2);
+    let view = mat3x2<f32>(surface.view_0, surface.view_1, surface.view_2);
+
+    let scaled = model.position * instance.dimensions;
+    let translated = view * vec3<f32>(transform * vec3<f32>(scaled, 1.0), 1.0);
+    let flipped_for_renderer = vec2<f32>(translated.x, -translated.y);
+    let scaled_to_renderer= (flipped_for_renderer / surface.render_target_dimensions) * 2.0;
+    let translated_to_render_coords = scaled_to_renderer + vec2<f32>(-1.0, 1.0);
+
+    out.clip_position = vec4<f32>(translated_to_render_coords, 0.0, 1.0);
+
+    return out;
+}
+
+@group(0) @binding(0)
+var t_diffuse: texture_2d<f32>;
+@group(0) @binding(1)
+var s_diffuse: sampler;
+
+// applies the draw's tint, flash and opacity
+fn sprite_color(texel: vec4<f32>, in: VertexOutput) -> vec4<f32> {
+    let color = texel * in.tint;
+    let opacity = in.alpha.x;
+    let premultiplied = in.alpha.y;
+    let premultiply = in.alpha.z;
+    let alpha = color.a * opacity;
+
+    // premultiplied texels need the flash color and the opacity applied to rgb as well
+    let flash = in.flash.rgb * mix(1.0, color.a, premultiplied);
+    let rgb = mix(color.rgb, flash, in.flash.a) * mix(1.0, opacity, premultiplied);
+
+    // multiply and screen blend premultiplied colors, which straight texels are turned into here
+    return vec4<f32>(rgb * mix(1.0, alpha, premultiply), alpha);
+}
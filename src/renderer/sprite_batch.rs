@@ -0,0 +1,585 @@
+use super::*;
+
+use pipelines::{Pipelines, SpritePipelines};
+use pixel_surface::{BlendMode, Vertex2d, RECT_INDICES};
+use vector_path::PathVertex;
+
+use std::ops::Range;
+
+// per-sprite data read by diffuse2d.wgsl with step_mode Instance
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct SpriteInstance {
+    pub dimensions: [f32; 2],
+    pub transform: [[f32; 2]; 3], // columns of the affine matrix from quad to surface pixels
+    pub tex_coords: [TextureCoordinates; 4],
+    pub tint: Color,
+    pub flash: Color,
+    pub opacity: f32,
+    pub premultiplied: f32, // 1 when the texture's colors are premultiplied by alpha
+    pub premultiply: f32,   // 1 when the blend mode takes premultiplied colors from straight ones
+}
+
+impl SpriteInstance {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 11] = wgpu::vertex_attr_array![
+        2 => Float32x2, // dimensions
+        3 => Float32x2, // transform column 0
+        4 => Float32x2, // transform column 1
+        5 => Float32x2, // transform column 2, the translation
+        6 => Float32x2, // tex coords top-left
+        7 => Float32x2, // tex coords top-right
+        8 => Float32x2, // tex coords bottom-left
+        9 => Float32x2, // tex coords bottom-right
+        10 => Float32x4, // tint
+        11 => Float32x4, // flash
+        12 => Float32x3, // opacity, premultiplied, premultiply
+    ];
+
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<SpriteInstance>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+// per-rect data read by solid2d.wgsl, used for primitives
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct SolidInstance {
+    pub position: [f32; 2],
+    pub dimensions: [f32; 2],
+    pub color: Color,
+}
+
+impl SolidInstance {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 3] = wgpu::vertex_attr_array![
+        2 => Float32x2, // position
+        3 => Float32x2, // dimensions
+        4 => Float32x4, // color
+    ];
+
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<SolidInstance>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+// wgpu buffer that is reallocated to the next power of two when written past its capacity
+pub struct GrowableBuffer {
+    buffer: wgpu::Buffer,
+    capacity: wgpu::BufferAddress,
+    usage: wgpu::BufferUsages,
+    label: &'static str,
+}
+
+impl GrowableBuffer {
+    pub fn new(
+        device: &wgpu::Device,
+        label: &'static str,
+        usage: wgpu::BufferUsages,
+        capacity: wgpu::BufferAddress,
+    ) -> Self {
+        let usage = usage | wgpu::BufferUsages::COPY_DST;
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: capacity,
+            usage,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            buffer,
+            capacity,
+            usage,
+            label,
+        }
+    }
+    pub fn write(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, contents: &[u8]) {
+        let required = contents.len() as wgpu::BufferAddress;
+
+        if required > self.capacity {
+            self.capacity = required.next_power_of_two();
+            self.buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(self.label),
+                size: self.capacity,
+                usage: self.usage,
+                mapped_at_creation: false,
+            });
+        }
+
+        queue.write_buffer(&self.buffer, 0, contents);
+    }
+    // overwrites part of what was last written, which must already cover it
+    pub fn write_at(&self, queue: &wgpu::Queue, offset: wgpu::BufferAddress, contents: &[u8]) {
+        queue.write_buffer(&self.buffer, offset, contents);
+    }
+    pub fn slice(&self, bounds: Range<wgpu::BufferAddress>) -> wgpu::BufferSlice<'_> {
+        self.buffer.slice(bounds)
+    }
+}
+
+enum SpriteBatchKind {
+    Sprite {
+        texture: Rc<Texture>,
+        blend_mode: BlendMode,
+        shading: SpriteShading,
+    },
+    Solid,
+    Path,
+}
+
+// how a sprite's texels become colors
+#[derive(Clone)]
+pub enum SpriteShading {
+    Diffuse,
+    Material(Rc<Material>),
+    Indexed(Rc<Texture>), // the texture holds palette indices, looked up in this palette row
+}
+
+impl SpriteShading {
+    fn matches(&self, other: &SpriteShading) -> bool {
+        match (self, other) {
+            (SpriteShading::Diffuse, SpriteShading::Diffuse) => true,
+            (SpriteShading::Material(a), SpriteShading::Material(b)) => Rc::ptr_eq(a, b),
+            (SpriteShading::Indexed(a), SpriteShading::Indexed(b)) => Rc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+    // palettes are a bind group rather than a pipeline of their own, so they don't count
+    fn pipeline_id(&self) -> SpriteShadingPipeline {
+        match self {
+            SpriteShading::Diffuse => SpriteShadingPipeline::Diffuse,
+            SpriteShading::Material(material) => {
+                SpriteShadingPipeline::Material(Rc::as_ptr(material))
+            }
+            SpriteShading::Indexed(_) => SpriteShadingPipeline::Indexed,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum SpriteShadingPipeline {
+    Diffuse,
+    Material(*const Material),
+    Indexed,
+}
+
+impl SpriteBatchKind {
+    // whether draws of both kinds can share one draw call
+    fn matches(&self, other: &SpriteBatchKind) -> bool {
+        match (self, other) {
+            (
+                SpriteBatchKind::Sprite {
+                    texture,
+                    blend_mode,
+                    shading,
+                },
+                SpriteBatchKind::Sprite {
+                    texture: other_texture,
+                    blend_mode: other_blend_mode,
+                    shading: other_shading,
+                },
+            ) => {
+                Rc::ptr_eq(texture, other_texture)
+                    && blend_mode == other_blend_mode
+                    && shading.matches(other_shading)
+            }
+            (SpriteBatchKind::Solid, SpriteBatchKind::Solid) => true,
+            (SpriteBatchKind::Path, SpriteBatchKind::Path) => true,
+            _ => false,
+        }
+    }
+}
+
+// a run of consecutive draws that share a pipeline and texture and become one draw call;
+// range is into the instance list of the group's kind, or into the path indices
+struct SpriteBatchGroup {
+    kind: SpriteBatchKind,
+    range: Range<u32>,
+}
+
+// which pipeline and buffers are bound while encoding
+#[derive(Clone, Copy, PartialEq)]
+enum BoundPipeline {
+    Sprite(BlendMode, SpriteShadingPipeline),
+    Solid,
+    Path,
+}
+
+const INITIAL_INSTANCE_CAPACITY: wgpu::BufferAddress = 256;
+
+// where a batch is drawn: the view, the surface uniforms the draws read and the rect, if any,
+// they are clipped to, which must lie within the view
+pub struct BatchTarget<'a> {
+    pub view: &'a wgpu::TextureView,
+    pub surface_uniforms_bind_group: &'a wgpu::BindGroup,
+    pub scissor: Option<(PixelCoordinates, PixelDimensions)>,
+}
+
+// the draws queued on a surface between flushes; runs of consecutive draws that share a texture,
+// blend mode and shading become one instanced draw call, but draws of a texture separated by
+// other draws are not gathered together, since reordering them would change how they blend
+pub struct SpriteBatch {
+    instances: Vec<SpriteInstance>,
+    solid_instances: Vec<SolidInstance>,
+    path_vertices: Vec<PathVertex>,
+    path_indices: Vec<u32>,
+    groups: Vec<SpriteBatchGroup>,
+    clear_pending: bool,
+    quad_vertex_buffer: wgpu::Buffer,
+    quad_index_buffer: wgpu::Buffer,
+    instance_buffer: GrowableBuffer,
+    solid_instance_buffer: GrowableBuffer,
+    path_vertex_buffer: GrowableBuffer,
+    path_index_buffer: GrowableBuffer,
+}
+
+impl SpriteBatch {
+    pub fn new(device: &wgpu::Device) -> Self {
+        // the quad's texture coordinates are used as weights to pick between the instance's
+        // four texture coordinates in the vertex shader
+        let quad_vertices: [Vertex2d; 4] = [
+            Vertex2d {
+                // top-left
+                tex_coords: TextureCoordinates::top_left(),
+                plane_coords: RenderPlaneCoordinates { x: 0.0, y: 0.0 },
+            },
+            Vertex2d {
+                // top-right
+                tex_coords: TextureCoordinates::top_right(),
+                plane_coords: RenderPlaneCoordinates { x: 1.0, y: 0.0 },
+            },
+            Vertex2d {
+                // bottom-left
+                tex_coords: TextureCoordinates::bottom_left(),
+                plane_coords: RenderPlaneCoordinates { x: 0.0, y: 1.0 },
+            },
+            Vertex2d {
+                // bottom-right
+                tex_coords: TextureCoordinates::bottom_right(),
+                plane_coords: RenderPlaneCoordinates { x: 1.0, y: 1.0 },
+            },
+        ];
+
+        let quad_vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("sprite batch quad vertex buffer"),
+            contents: bytemuck::cast_slice(&quad_vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        let quad_index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("sprite batch quad index buffer"),
+            contents: bytemuck::cast_slice(&RECT_INDICES),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+        let instance_buffer = GrowableBuffer::new(
+            device,
+            "sprite batch instance buffer",
+            wgpu::BufferUsages::VERTEX,
+            INITIAL_INSTANCE_CAPACITY
+                * std::mem::size_of::<SpriteInstance>() as wgpu::BufferAddress,
+        );
+        let solid_instance_buffer = GrowableBuffer::new(
+            device,
+            "sprite batch solid instance buffer",
+            wgpu::BufferUsages::VERTEX,
+            INITIAL_INSTANCE_CAPACITY * std::mem::size_of::<SolidInstance>() as wgpu::BufferAddress,
+        );
+        let path_vertex_buffer = GrowableBuffer::new(
+            device,
+            "sprite batch path vertex buffer",
+            wgpu::BufferUsages::VERTEX,
+            INITIAL_INSTANCE_CAPACITY * std::mem::size_of::<PathVertex>() as wgpu::BufferAddress,
+        );
+        let path_index_buffer = GrowableBuffer::new(
+            device,
+            "sprite batch path index buffer",
+            wgpu::BufferUsages::INDEX,
+            INITIAL_INSTANCE_CAPACITY * std::mem::size_of::<u32>() as wgpu::BufferAddress,
+        );
+
+        Self {
+            instances: Vec::new(),
+            solid_instances: Vec::new(),
+            path_vertices: Vec::new(),
+            path_indices: Vec::new(),
+            groups: Vec::new(),
+            clear_pending: false,
+            quad_vertex_buffer,
+            quad_index_buffer,
+            instance_buffer,
+            solid_instance_buffer,
+            path_vertex_buffer,
+            path_index_buffer,
+        }
+    }
+    // only consecutive draws are merged so that draw order is preserved for blending
+    fn extend_groups(&mut self, kind: SpriteBatchKind, range: Range<u32>) {
+        match self.groups.last_mut() {
+            Some(group) if group.kind.matches(&kind) => group.range.end = range.end,
+            _ => self.groups.push(SpriteBatchGroup { kind, range }),
+        }
+    }
+    pub fn push(
+        &mut self,
+        texture: &Rc<Texture>,
+        blend_mode: BlendMode,
+        shading: SpriteShading,
+        instance: SpriteInstance,
+    ) {
+        let index = self.instances.len() as u32;
+        self.instances.push(instance);
+
+        let kind = SpriteBatchKind::Sprite {
+            texture: Rc::clone(texture),
+            blend_mode,
+            shading,
+        };
+        self.extend_groups(kind, index..index + 1);
+    }
+    pub fn push_solid(&mut self, instance: SolidInstance) {
+        let index = self.solid_instances.len() as u32;
+        self.solid_instances.push(instance);
+
+        self.extend_groups(SpriteBatchKind::Solid, index..index + 1);
+    }
+    pub fn push_path(&mut self, vertices: &[PathVertex], indices: &[u32]) {
+        if indices.is_empty() {
+            return;
+        }
+
+        let base_vertex = self.path_vertices.len() as u32;
+        let first_index = self.path_indices.len() as u32;
+
+        self.path_vertices.extend_from_slice(vertices);
+        self.path_indices
+            .extend(indices.iter().map(|index| base_vertex + index));
+
+        let range = first_index..self.path_indices.len() as u32;
+        self.extend_groups(SpriteBatchKind::Path, range);
+    }
+    // drops every queued draw and clears the target on the next flush
+    pub fn clear(&mut self) {
+        self.reset();
+        self.clear_pending = true;
+    }
+    // drops every queued draw, leaving the target as it is
+    pub fn reset(&mut self) {
+        self.instances.clear();
+        self.solid_instances.clear();
+        self.path_vertices.clear();
+        self.path_indices.clear();
+        self.groups.clear();
+    }
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty() && !self.clear_pending
+    }
+    // records every queued draw into a single render pass on the target, then drops them
+    pub fn encode(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        pipelines: &Pipelines,
+        target: &BatchTarget,
+    ) {
+        self.upload(device, queue);
+        self.encode_pass(encoder, pipelines, target);
+
+        self.reset();
+        self.clear_pending = false;
+    }
+    // writes the queued draws to their buffers, where encode_pass reads them
+    pub fn upload(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        if !self.instances.is_empty() {
+            self.instance_buffer
+                .write(device, queue, bytemuck::cast_slice(&self.instances));
+        }
+        if !self.solid_instances.is_empty() {
+            self.solid_instance_buffer.write(
+                device,
+                queue,
+                bytemuck::cast_slice(&self.solid_instances),
+            );
+        }
+        if !self.path_indices.is_empty() {
+            self.path_vertex_buffer
+                .write(device, queue, bytemuck::cast_slice(&self.path_vertices));
+            self.path_index_buffer
+                .write(device, queue, bytemuck::cast_slice(&self.path_indices));
+        }
+    }
+    // overwrites one uploaded sprite instance in place, leaving the rest of the buffer be
+    pub fn write_instance(&mut self, queue: &wgpu::Queue, index: u32, instance: SpriteInstance) {
+        self.instances[index as usize] = instance;
+
+        let offset = index as wgpu::BufferAddress
+            * std::mem::size_of::<SpriteInstance>() as wgpu::BufferAddress;
+        self.instance_buffer
+            .write_at(queue, offset, bytemuck::cast_slice(&[instance]));
+    }
+    // records the uploaded draws into a single render pass, keeping them for the next one
+    pub fn encode_pass(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        pipelines: &Pipelines,
+        target: &BatchTarget,
+    ) {
+        let load = if self.clear_pending {
+            wgpu::LoadOp::Clear(wgpu::Color {
+                r: 0.0,
+                g: 0.0,
+                b: 0.0,
+                a: 0.0,
+            })
+        } else {
+            wgpu::LoadOp::Load
+        };
+
+        // fetched up front, since hot reloading may swap pipelines between flushes
+        let draw_sprite = pipelines.draw_sprite();
+        let draw_solid = pipelines.draw_solid();
+        let draw_path = pipelines.draw_path();
+        let draw_indexed = pipelines.draw_indexed();
+        let material_pipelines: Vec<Option<Rc<SpritePipelines>>> = self
+            .groups
+            .iter()
+            .map(|group| match &group.kind {
+                SpriteBatchKind::Sprite {
+                    shading: SpriteShading::Material(material),
+                    ..
+                } => Some(material.pipelines()),
+                _ => None,
+            })
+            .collect();
+
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("sprite batch render pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: target.view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+
+            if let Some((position, dimensions)) = target.scissor {
+                render_pass.set_scissor_rect(
+                    position.x as u32,
+                    position.y as u32,
+                    dimensions.width,
+                    dimensions.height,
+                );
+            }
+
+            let instance_bytes = (self.instances.len() * std::mem::size_of::<SpriteInstance>())
+                as wgpu::BufferAddress;
+            let solid_instance_bytes = (self.solid_instances.len()
+                * std::mem::size_of::<SolidInstance>())
+                as wgpu::BufferAddress;
+            let path_vertex_bytes = (self.path_vertices.len() * std::mem::size_of::<PathVertex>())
+                as wgpu::BufferAddress;
+            let path_index_bytes =
+                (self.path_indices.len() * std::mem::size_of::<u32>()) as wgpu::BufferAddress;
+
+            // each kind binds its own vertex buffers, and the surface uniforms at its own slot
+            let mut bound = None;
+
+            for (group, material_pipelines) in self.groups.iter().zip(&material_pipelines) {
+                match &group.kind {
+                    SpriteBatchKind::Sprite {
+                        texture,
+                        blend_mode,
+                        shading,
+                    } => {
+                        if !matches!(bound, Some(BoundPipeline::Sprite(..))) {
+                            render_pass.set_bind_group(1, target.surface_uniforms_bind_group, &[]);
+                            render_pass.set_vertex_buffer(0, self.quad_vertex_buffer.slice(..));
+                            render_pass.set_vertex_buffer(
+                                1,
+                                self.instance_buffer.slice(0..instance_bytes),
+                            );
+                            render_pass.set_index_buffer(
+                                self.quad_index_buffer.slice(..),
+                                wgpu::IndexFormat::Uint16,
+                            );
+                        }
+                        // materials and palettes share the first two groups' layouts, so those
+                        // stay bound
+                        let sprite_pipeline =
+                            BoundPipeline::Sprite(*blend_mode, shading.pipeline_id());
+                        if bound != Some(sprite_pipeline) {
+                            match (shading, material_pipelines) {
+                                (SpriteShading::Material(material), Some(material_pipelines)) => {
+                                    render_pass.set_pipeline(material_pipelines.get(*blend_mode));
+                                    render_pass.set_bind_group(2, material.bind_group(), &[]);
+                                }
+                                (SpriteShading::Indexed(_), _) => {
+                                    render_pass.set_pipeline(draw_indexed.get(*blend_mode))
+                                }
+                                _ => render_pass.set_pipeline(draw_sprite.get(*blend_mode)),
+                            }
+                            bound = Some(sprite_pipeline);
+                        }
+                        if let SpriteShading::Indexed(palette) = shading {
+                            render_pass.set_bind_group(2, &palette.wgpu_bind_group, &[]);
+                        }
+                        render_pass.set_bind_group(0, &texture.wgpu_bind_group, &[]);
+                        render_pass.draw_indexed(
+                            0..RECT_INDICES.len() as u32,
+                            0,
+                            group.range.clone(),
+                        );
+                    }
+                    SpriteBatchKind::Solid => {
+                        if bound != Some(BoundPipeline::Solid) {
+                            render_pass.set_pipeline(&draw_solid);
+                            render_pass.set_bind_group(0, target.surface_uniforms_bind_group, &[]);
+                            render_pass.set_vertex_buffer(0, self.quad_vertex_buffer.slice(..));
+                            render_pass.set_vertex_buffer(
+                                1,
+                                self.solid_instance_buffer.slice(0..solid_instance_bytes),
+                            );
+                            render_pass.set_index_buffer(
+                                self.quad_index_buffer.slice(..),
+                                wgpu::IndexFormat::Uint16,
+                            );
+                            bound = Some(BoundPipeline::Solid);
+                        }
+                        render_pass.draw_indexed(
+                            0..RECT_INDICES.len() as u32,
+                            0,
+                            group.range.clone(),
+                        );
+                    }
+                    SpriteBatchKind::Path => {
+                        if bound != Some(BoundPipeline::Path) {
+                            render_pass.set_pipeline(&draw_path);
+                            render_pass.set_bind_group(0, target.surface_uniforms_bind_group, &[]);
+                            render_pass.set_vertex_buffer(
+                                0,
+                                self.path_vertex_buffer.slice(0..path_vertex_bytes),
+                            );
+                            render_pass.set_index_buffer(
+                                self.path_index_buffer.slice(0..path_index_bytes),
+                                wgpu::IndexFormat::Uint32,
+                            );
+                            bound = Some(BoundPipeline::Path);
+                        }
+                        render_pass.draw_indexed(group.range.clone(), 0, 0..1);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,520 @@
+use super::*;
+
+use animation::{AnimatedSprite, AnimationFrame, AnimationTag, PlaybackMode};
+
+use serde::Deserialize;
+use std::{collections::HashMap, path::Path};
+
+// frames without a duration (TexturePacker, free-tex-packer) get this when animated
+const DEFAULT_FRAME_DURATION: f32 = 0.1;
+
+#[derive(Debug)]
+pub enum SpriteSheetError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+}
+
+impl From<std::io::Error> for SpriteSheetError {
+    fn from(error: std::io::Error) -> Self {
+        SpriteSheetError::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for SpriteSheetError {
+    fn from(error: serde_json::Error) -> Self {
+        SpriteSheetError::Json(error)
+    }
+}
+
+#[derive(Deserialize)]
+struct JsonRect {
+    x: i32,
+    y: i32,
+    w: u32,
+    h: u32,
+}
+
+#[derive(Deserialize)]
+struct JsonSize {
+    w: u32,
+    h: u32,
+}
+
+#[derive(Deserialize)]
+struct JsonPoint {
+    x: f32,
+    y: f32,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonFrame {
+    #[serde(default)]
+    filename: Option<String>, // only present in the JSON-array layout
+    frame: JsonRect,
+    #[serde(default)]
+    rotated: bool,
+    sprite_source_size: Option<JsonRect>,
+    source_size: Option<JsonSize>,
+    pivot: Option<JsonPoint>,
+    duration: Option<u32>, // milliseconds, Aseprite only
+}
+
+// JSON-hash keeps the frame name as the key, JSON-array in a filename field
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JsonFrames {
+    Array(Vec<JsonFrame>),
+    Hash(serde_json::Map<String, serde_json::Value>),
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonFrameTag {
+    name: String,
+    from: usize,
+    to: usize,
+    #[serde(default)]
+    direction: String,
+    repeat: Option<JsonRepeat>, // Aseprite only, absent when the tag repeats forever
+}
+
+// Aseprite writes the repeat count as a string
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JsonRepeat {
+    Number(u32),
+    Text(String),
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonMeta {
+    image: String,
+    #[serde(default)]
+    frame_tags: Vec<JsonFrameTag>,
+}
+
+#[derive(Deserialize)]
+struct JsonSpriteSheet {
+    frames: JsonFrames,
+    meta: JsonMeta,
+}
+
+#[derive(Clone)]
+pub struct SheetFrame {
+    pub sprite: Sprite,
+    pub source_size: PixelDimensions,  // untrimmed size
+    pub trim_offset: PixelCoordinates, // where the trimmed sprite sits inside source_size
+    pub pivot: Option<(f32, f32)>,     // normalized to source_size
+    pub duration: Option<f32>,         // seconds
+}
+
+impl SheetFrame {
+    // the draw's position is the top-left of the untrimmed frame and the transform's origin is
+    // there too; its dimensions, when given, are the untrimmed frame's
+    pub fn draw(&self, surface: &PixelSurface, draw: &SpriteDraw) {
+        let (size_x, size_y) = match draw.dimensions {
+            Some(dimensions) => (
+                dimensions.width as f32 / self.source_size.width.max(1) as f32,
+                dimensions.height as f32 / self.source_size.height.max(1) as f32,
+            ),
+            None => (1.0, 1.0),
+        };
+        let dimensions = PixelDimensions {
+            width: (self.sprite.dimensions.width as f32 * size_x).round() as u32,
+            height: (self.sprite.dimensions.height as f32 * size_y).round() as u32,
+        };
+
+        // the offset scales and mirrors with the sprite, so the frame flips about its top-left
+        let trim = Transform2d::translation(
+            self.trim_offset.x as f32 * size_x * draw.scale.x,
+            self.trim_offset.y as f32 * size_y * draw.scale.y,
+        );
+
+        // placed by its top-left whatever pivot the sprite carries, since the trim is relative to it
+        surface.queue_draw(
+            &self.sprite.texture,
+            self.sprite.vertices,
+            self.sprite.dimensions,
+            SpritePivot::TopLeft,
+            &SpriteDraw {
+                dimensions: Some(dimensions),
+                transform: trim.then(&draw.transform),
+                ..draw.clone()
+            },
+            draw.shading(),
+        );
+    }
+}
+
+pub struct SpriteSheetTag {
+    pub name: String,
+    pub frames: std::ops::Range<usize>,
+    pub mode: PlaybackMode,
+    pub reverse: bool,
+    pub repeat: u32, // 0 repeats forever
+}
+
+pub struct SpriteSheet {
+    pub texture: Rc<Texture>,
+    pub frame_names: Vec<String>, // in file order
+    pub frames: HashMap<String, SheetFrame>,
+    pub tags: Vec<SpriteSheetTag>,
+}
+
+impl SpriteSheet {
+    pub(crate) fn load(
+        texture_manager: &TextureManager,
+        path: &str,
+    ) -> Result<Self, SpriteSheetError> {
+        let json = std::fs::read_to_string(path)?;
+        let sheet: JsonSpriteSheet = serde_json::from_str(&json)?;
+
+        // the image path is relative to the json file
+        let image_path = Path::new(path)
+            .parent()
+            .unwrap_or(Path::new(""))
+            .join(&sheet.meta.image);
+        let texture = texture_manager.load_texture(&image_path.to_string_lossy());
+
+        let named_frames = SpriteSheet::named_frames(sheet.frames)?;
+
+        let mut frame_names = Vec::with_capacity(named_frames.len());
+        let mut frames = HashMap::with_capacity(named_frames.len());
+
+        for (name, frame) in named_frames {
+            frame_names.push(name.clone());
+            frames.insert(name, SpriteSheet::create_frame(&texture, frame));
+        }
+
+        let tags = SpriteSheet::tags(sheet.meta.frame_tags, frame_names.len());
+
+        Ok(Self {
+            texture,
+            frame_names,
+            frames,
+            tags,
+        })
+    }
+    // frames in file order, named by their filename or index in the array layout
+    fn named_frames(frames: JsonFrames) -> Result<Vec<(String, JsonFrame)>, serde_json::Error> {
+        match frames {
+            JsonFrames::Array(frames) => Ok(frames
+                .into_iter()
+                .enumerate()
+                .map(|(index, frame)| {
+                    let name = frame.filename.clone().unwrap_or_else(|| index.to_string());
+                    (name, frame)
+                })
+                .collect()),
+            JsonFrames::Hash(frames) => frames
+                .into_iter()
+                .map(|(name, frame)| Ok((name, serde_json::from_value(frame)?)))
+                .collect(),
+        }
+    }
+    // tags reaching past the last frame are dropped
+    fn tags(frame_tags: Vec<JsonFrameTag>, frame_count: usize) -> Vec<SpriteSheetTag> {
+        frame_tags
+            .into_iter()
+            .filter(|tag| tag.from <= tag.to && tag.to < frame_count)
+            .map(|tag| SpriteSheetTag {
+                frames: tag.from..tag.to + 1,
+                mode: match tag.direction.as_str() {
+                    "pingpong" | "pingpong_reverse" => PlaybackMode::PingPong,
+                    _ => PlaybackMode::Loop,
+                },
+                reverse: matches!(tag.direction.as_str(), "reverse" | "pingpong_reverse"),
+                repeat: match tag.repeat {
+                    Some(JsonRepeat::Number(repeat)) => repeat,
+                    Some(JsonRepeat::Text(repeat)) => repeat.parse().unwrap_or(0),
+                    None => 0,
+                },
+                name: tag.name,
+            })
+            .collect()
+    }
+    // the exported pivot, normalized to the untrimmed frame, moved into the trimmed sprite
+    fn trimmed_pivot(
+        pivot: (f32, f32),
+        source_size: PixelDimensions,
+        trim_offset: PixelCoordinates,
+        dimensions: PixelDimensions,
+    ) -> SpritePivot {
+        let x = pivot.0 * source_size.width as f32 - trim_offset.x as f32;
+        let y = pivot.1 * source_size.height as f32 - trim_offset.y as f32;
+        SpritePivot::Normalized(
+            x / dimensions.width.max(1) as f32,
+            y / dimensions.height.max(1) as f32,
+        )
+    }
+    fn create_frame(texture: &Rc<Texture>, frame: JsonFrame) -> SheetFrame {
+        let JsonRect { x, y, w, h } = frame.frame;
+
+        let sprite = if frame.rotated {
+            // stored rotated 90 degrees clockwise, so the sheet area is h wide and w tall
+            let area = SpriteTextureArea {
+                coordinates: PixelCoordinates { x, y },
+                dimensions: PixelDimensions {
+                    width: h,
+                    height: w,
+                },
+            };
+            let stored = Sprite::create(Rc::clone(texture), Some(area));
+            let [top_left, top_right, bottom_left, bottom_right] = stored.vertices;
+            Sprite {
+                vertices: [top_right, bottom_right, top_left, bottom_left],
+                dimensions: PixelDimensions {
+                    width: w,
+                    height: h,
+                },
+                ..stored
+            }
+        } else {
+            let area = SpriteTextureArea {
+                coordinates: PixelCoordinates { x, y },
+                dimensions: PixelDimensions {
+                    width: w,
+                    height: h,
+                },
+            };
+            Sprite::create(Rc::clone(texture), Some(area))
+        };
+
+        let trim_offset = frame
+            .sprite_source_size
+            .as_ref()
+            .map(|rect| PixelCoordinates {
+                x: rect.x,
+                y: rect.y,
+            })
+            .unwrap_or(PixelCoordinates { x: 0, y: 0 });
+
+        let source_size = frame
+            .source_size
+            .map(|size| PixelDimensions {
+                width: size.w,
+                height: size.h,
+            })
+            .unwrap_or(sprite.dimensions);
+
+        // the exported pivot is moved into the trimmed sprite so that draw_sprite honors it
+        let pivot = frame.pivot.map(|pivot| (pivot.x, pivot.y));
+        let sprite = match pivot {
+            Some(pivot) => {
+                let dimensions = sprite.dimensions;
+                sprite.with_pivot(SpriteSheet::trimmed_pivot(
+                    pivot,
+                    source_size,
+                    trim_offset,
+                    dimensions,
+                ))
+            }
+            None => sprite,
+        };
+
+        SheetFrame {
+            sprite,
+            source_size,
+            trim_offset,
+            pivot,
+            duration: frame.duration.map(|duration| duration as f32 / 1000.0),
+        }
+    }
+    pub fn frame(&self, name: &str) -> Option<&SheetFrame> {
+        self.frames.get(name)
+    }
+    pub fn sprite(&self, name: &str) -> Option<&Sprite> {
+        self.frames.get(name).map(|frame| &frame.sprite)
+    }
+    // every frame in file order with the sheet's tags; each frame's pivot is the untrimmed
+    // frame's pivot, or its top-left, so that trimmed frames stay put as the animation plays
+    pub fn animated_sprite(&self) -> Option<AnimatedSprite> {
+        let frames: Vec<AnimationFrame> = self
+            .frame_names
+            .iter()
+            .map(|name| {
+                let frame = &self.frames[name];
+                let pivot = SpriteSheet::trimmed_pivot(
+                    frame.pivot.unwrap_or((0.0, 0.0)),
+                    frame.source_size,
+                    frame.trim_offset,
+                    frame.sprite.dimensions,
+                );
+                AnimationFrame {
+                    sprite: frame.sprite.clone().with_pivot(pivot),
+                    duration: frame.duration.unwrap_or(DEFAULT_FRAME_DURATION),
+                }
+            })
+            .collect();
+
+        if frames.is_empty() {
+            return None;
+        }
+
+        let mut animated_sprite = AnimatedSprite::new(frames, PlaybackMode::Loop);
+        for tag in &self.tags {
+            animated_sprite.insert_tag(
+                &tag.name,
+                AnimationTag::new(tag.frames.clone(), tag.mode)
+                    .reversed(tag.reverse)
+                    .repeated(tag.repeat),
+            );
+        }
+        Some(animated_sprite)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(json: &str) -> JsonSpriteSheet {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn names(frames: &[(String, JsonFrame)]) -> Vec<&str> {
+        frames.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    #[test]
+    fn hash_frames_keep_their_keys_in_file_order() {
+        let sheet = parse(
+            r#"{
+                "frames": {
+                    "walk_1": { "frame": { "x": 0, "y": 0, "w": 8, "h": 8 }, "duration": 120 },
+                    "walk_0": { "frame": { "x": 8, "y": 0, "w": 8, "h": 8 }, "rotated": true }
+                },
+                "meta": { "image": "walk.png" }
+            }"#,
+        );
+        let frames = SpriteSheet::named_frames(sheet.frames).unwrap();
+
+        assert_eq!(names(&frames), ["walk_1", "walk_0"]);
+        assert_eq!(frames[0].1.duration, Some(120));
+        assert!(!frames[0].1.rotated && frames[1].1.rotated);
+        assert_eq!(sheet.meta.image, "walk.png");
+    }
+
+    #[test]
+    fn array_frames_are_named_by_filename_or_index() {
+        let sheet = parse(
+            r#"{
+                "frames": [
+                    { "filename": "idle", "frame": { "x": 0, "y": 0, "w": 4, "h": 4 } },
+                    {
+                        "frame": { "x": 4, "y": 0, "w": 2, "h": 3 },
+                        "spriteSourceSize": { "x": 1, "y": 2, "w": 2, "h": 3 },
+                        "sourceSize": { "w": 4, "h": 6 }
+                    }
+                ],
+                "meta": { "image": "sheet.png" }
+            }"#,
+        );
+        let frames = SpriteSheet::named_frames(sheet.frames).unwrap();
+
+        assert_eq!(names(&frames), ["idle", "1"]);
+        let trim = frames[1].1.sprite_source_size.as_ref().unwrap();
+        assert_eq!((trim.x, trim.y), (1, 2));
+        assert_eq!(frames[1].1.source_size.as_ref().unwrap().w, 4);
+    }
+
+    #[test]
+    fn hash_frames_with_bad_fields_fail() {
+        let sheet = parse(
+            r#"{ "frames": { "broken": { "frame": { "x": 0 } } }, "meta": { "image": "a.png" } }"#,
+        );
+
+        assert!(SpriteSheet::named_frames(sheet.frames).is_err());
+    }
+
+    #[test]
+    fn tags_outside_the_frames_are_dropped() {
+        let sheet = parse(
+            r#"{
+                "frames": [],
+                "meta": {
+                    "image": "a.png",
+                    "frameTags": [
+                        { "name": "run", "from": 0, "to": 2, "direction": "forward" },
+                        { "name": "bounce", "from": 1, "to": 3, "direction": "pingpong" },
+                        { "name": "past", "from": 2, "to": 4 },
+                        { "name": "backwards", "from": 3, "to": 1 }
+                    ]
+                }
+            }"#,
+        );
+        let tags = SpriteSheet::tags(sheet.meta.frame_tags, 4);
+
+        assert_eq!(tags.len(), 2);
+        assert_eq!(
+            (tags[0].name.as_str(), tags[0].frames.clone()),
+            ("run", 0..3)
+        );
+        assert_eq!(tags[0].mode, PlaybackMode::Loop);
+        assert_eq!(tags[1].frames, 1..4);
+        assert_eq!(tags[1].mode, PlaybackMode::PingPong);
+        assert!(!tags[0].reverse && !tags[1].reverse);
+        assert_eq!((tags[0].repeat, tags[1].repeat), (0, 0));
+    }
+
+    #[test]
+    fn reverse_directions_and_repeats_are_kept() {
+        let sheet = parse(
+            r#"{
+                "frames": [],
+                "meta": {
+                    "image": "a.png",
+                    "frameTags": [
+                        { "name": "rewind", "from": 0, "to": 1, "direction": "reverse", "repeat": "2" },
+                        { "name": "sway", "from": 1, "to": 3, "direction": "pingpong_reverse", "repeat": 3 }
+                    ]
+                }
+            }"#,
+        );
+        let tags = SpriteSheet::tags(sheet.meta.frame_tags, 4);
+
+        assert_eq!(tags[0].mode, PlaybackMode::Loop);
+        assert_eq!((tags[0].reverse, tags[0].repeat), (true, 2));
+        assert_eq!(tags[1].mode, PlaybackMode::PingPong);
+        assert_eq!((tags[1].reverse, tags[1].repeat), (true, 3));
+    }
+
+    #[test]
+    fn pivots_move_into_the_trimmed_sprite() {
+        // the center of a 16x16 frame trimmed to the 8x4 at (4, 6)
+        let pivot = SpriteSheet::trimmed_pivot(
+            (0.5, 0.5),
+            PixelDimensions {
+                width: 16,
+                height: 16,
+            },
+            PixelCoordinates { x: 4, y: 6 },
+            PixelDimensions {
+                width: 8,
+                height: 4,
+            },
+        );
+
+        assert_eq!(pivot, SpritePivot::Normalized(0.5, 0.5));
+    }
+
+    #[test]
+    fn frames_without_a_pivot_are_placed_by_the_untrimmed_top_left() {
+        let pivot = SpriteSheet::trimmed_pivot(
+            (0.0, 0.0),
+            PixelDimensions {
+                width: 16,
+                height: 16,
+            },
+            PixelCoordinates { x: 4, y: 6 },
+            PixelDimensions {
+                width: 8,
+                height: 4,
+            },
+        );
+
+        assert_eq!(pivot, SpritePivot::Normalized(-0.5, -1.5));
+    }
+}
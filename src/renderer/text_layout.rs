@@ -0,0 +1,522 @@
+use super::*;
+
+use sprite_batch::SpriteShading;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum TextAlignment {
+    #[default]
+    Left,
+    Center,
+    Right,
+    Justify, // wrapped lines are stretched to the max width, the last line of a paragraph is left aligned
+}
+
+// a glyph as a font hands it to the layout, offset from the pen at the top of the line
+pub struct FontGlyph {
+    pub sprite: Sprite,
+    pub offset: PixelCoordinates,
+}
+
+// metrics in pixels; implemented by BitmapFont and by TrueTypeFont at a given size
+pub trait FontSource {
+    fn line_height(&self) -> f32;
+    fn ascent(&self) -> f32; // top of the line to the baseline
+    fn advance(&self, character: char) -> f32;
+    fn kerning(&self, first: char, second: char) -> f32;
+    fn glyph(&self, character: char) -> Option<FontGlyph>; // None when there is nothing to draw
+}
+
+#[derive(Clone)]
+pub enum TextSpan {
+    Text { text: String, color: Color },
+    Icon(Sprite), // sits on the baseline and advances by its width
+}
+
+impl TextSpan {
+    pub fn text(text: &str, color: Color) -> Self {
+        TextSpan::Text {
+            text: text.to_string(),
+            color,
+        }
+    }
+    pub fn icon(sprite: &Sprite) -> Self {
+        TextSpan::Icon(sprite.clone())
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TextLayoutOptions {
+    pub max_width: Option<u32>, // words wrap past this, None only breaks on newlines
+    pub alignment: TextAlignment,
+}
+
+#[derive(Clone)]
+pub struct PositionedGlyph {
+    pub sprite: Sprite,
+    pub position: PixelCoordinates, // relative to the top-left of the layout
+    pub color: Color,
+}
+
+#[derive(Clone)]
+enum Piece {
+    Character(char, Color),
+    Icon(Sprite),
+}
+
+enum Token {
+    Word(Vec<Piece>),
+    Space(f32),
+    Newline,
+}
+
+struct Word {
+    space_before: f32,
+    pieces: Vec<Piece>,
+}
+
+#[derive(Default)]
+struct Line {
+    words: Vec<Word>,
+    width: f32,
+    wrapped: bool,        // started by wrapping rather than by a newline
+    ends_paragraph: bool, // followed by a newline or the end of the text
+}
+
+pub struct TextLayout {
+    pub glyphs: Vec<PositionedGlyph>,
+    pub dimensions: PixelDimensions,
+}
+
+impl TextLayout {
+    pub fn new(font: &dyn FontSource, spans: &[TextSpan], options: TextLayoutOptions) -> Self {
+        let lines = TextLayout::break_lines(font, TextLayout::tokenize(font, spans), options);
+
+        let box_width = match options.max_width {
+            Some(max_width) => max_width as f32,
+            None => lines.iter().map(|line| line.width).fold(0.0, f32::max),
+        };
+        let line_height = font.line_height();
+        let ascent = font.ascent().round() as i32;
+
+        let mut glyphs = Vec::new();
+
+        for (index, line) in lines.iter().enumerate() {
+            let top = (index as f32 * line_height).round() as i32;
+
+            let mut x = match options.alignment {
+                TextAlignment::Left | TextAlignment::Justify => 0.0,
+                TextAlignment::Center => ((box_width - line.width) / 2.0).round(),
+                TextAlignment::Right => box_width - line.width,
+            };
+
+            let justify_gap = match options.alignment {
+                TextAlignment::Justify if !line.ends_paragraph && line.words.len() > 1 => {
+                    (box_width - line.width).max(0.0) / (line.words.len() - 1) as f32
+                }
+                _ => 0.0,
+            };
+
+            for (word_index, word) in line.words.iter().enumerate() {
+                x += word.space_before;
+                if word_index > 0 {
+                    x += justify_gap;
+                }
+
+                let mut previous = None;
+
+                for piece in &word.pieces {
+                    match piece {
+                        Piece::Character(character, color) => {
+                            if let Some(previous) = previous {
+                                x += font.kerning(previous, *character);
+                            }
+                            previous = Some(*character);
+
+                            if let Some(glyph) = font.glyph(*character) {
+                                glyphs.push(PositionedGlyph {
+                                    sprite: glyph.sprite,
+                                    position: PixelCoordinates {
+                                        x: x.round() as i32 + glyph.offset.x,
+                                        y: top + glyph.offset.y,
+                                    },
+                                    color: *color,
+                                });
+                            }
+                            x += font.advance(*character);
+                        }
+                        Piece::Icon(sprite) => {
+                            previous = None;
+                            glyphs.push(PositionedGlyph {
+                                sprite: sprite.clone(),
+                                position: PixelCoordinates {
+                                    x: x.round() as i32,
+                                    y: top + ascent - sprite.dimensions.height as i32,
+                                },
+                                color: Color::WHITE,
+                            });
+                            x += sprite.dimensions.width as f32;
+                        }
+                    }
+                }
+            }
+        }
+
+        let dimensions = PixelDimensions {
+            width: box_width.ceil() as u32,
+            height: (lines.len() as f32 * line_height).ceil() as u32,
+        };
+
+        Self { glyphs, dimensions }
+    }
+    pub fn from_text(
+        font: &dyn FontSource,
+        text: &str,
+        color: Color,
+        options: TextLayoutOptions,
+    ) -> Self {
+        TextLayout::new(font, &[TextSpan::text(text, color)], options)
+    }
+    // words run across spans until whitespace, so a color change mid-word doesn't allow a break
+    fn tokenize(font: &dyn FontSource, spans: &[TextSpan]) -> Vec<Token> {
+        fn end_word(word: &mut Vec<Piece>, tokens: &mut Vec<Token>) {
+            if !word.is_empty() {
+                tokens.push(Token::Word(std::mem::take(word)));
+            }
+        }
+
+        let mut tokens = Vec::new();
+        let mut word = Vec::new();
+
+        for span in spans {
+            match span {
+                TextSpan::Text { text, color } => {
+                    for character in text.chars() {
+                        if character == '\n' {
+                            end_word(&mut word, &mut tokens);
+                            tokens.push(Token::Newline);
+                        } else if character.is_whitespace() {
+                            end_word(&mut word, &mut tokens);
+                            tokens.push(Token::Space(font.advance(character)));
+                        } else {
+                            word.push(Piece::Character(character, *color));
+                        }
+                    }
+                }
+                TextSpan::Icon(sprite) => word.push(Piece::Icon(sprite.clone())),
+            }
+        }
+        end_word(&mut word, &mut tokens);
+
+        tokens
+    }
+    fn piece_width(font: &dyn FontSource, previous: Option<char>, piece: &Piece) -> f32 {
+        match piece {
+            Piece::Character(character, _) => {
+                let kerning = previous
+                    .map(|previous| font.kerning(previous, *character))
+                    .unwrap_or(0.0);
+                kerning + font.advance(*character)
+            }
+            Piece::Icon(sprite) => sprite.dimensions.width as f32,
+        }
+    }
+    fn previous_character(piece: &Piece) -> Option<char> {
+        match piece {
+            Piece::Character(character, _) => Some(*character),
+            Piece::Icon(_) => None,
+        }
+    }
+    fn word_width(font: &dyn FontSource, pieces: &[Piece]) -> f32 {
+        let mut width = 0.0;
+        let mut previous = None;
+
+        for piece in pieces {
+            width += TextLayout::piece_width(font, previous, piece);
+            previous = TextLayout::previous_character(piece);
+        }
+
+        width
+    }
+    // cuts a word that is wider than max_width on its own into pieces that fit, at least one piece each
+    fn split_word(font: &dyn FontSource, pieces: Vec<Piece>, max_width: f32) -> Vec<Vec<Piece>> {
+        let mut parts = Vec::new();
+        let mut part: Vec<Piece> = Vec::new();
+        let mut width = 0.0;
+        let mut previous = None;
+
+        for piece in pieces {
+            let piece_width = TextLayout::piece_width(font, previous, &piece);
+
+            if !part.is_empty() && width + piece_width > max_width {
+                parts.push(std::mem::take(&mut part));
+                width = 0.0;
+                previous = None;
+            }
+
+            width += TextLayout::piece_width(font, previous, &piece);
+            previous = TextLayout::previous_character(&piece);
+            part.push(piece);
+        }
+        parts.push(part);
+
+        parts
+    }
+    // greedy line breaking at spaces; spaces at a wrap are dropped
+    fn break_lines(
+        font: &dyn FontSource,
+        tokens: Vec<Token>,
+        options: TextLayoutOptions,
+    ) -> Vec<Line> {
+        let max_width = options.max_width.map(|max_width| max_width as f32);
+
+        let mut lines = Vec::new();
+        let mut line = Line::default();
+        let mut pending_space = 0.0;
+
+        for token in tokens {
+            match token {
+                Token::Newline => {
+                    line.ends_paragraph = true;
+                    lines.push(std::mem::take(&mut line));
+                    pending_space = 0.0;
+                }
+                Token::Space(width) => {
+                    if !(line.wrapped && line.words.is_empty()) {
+                        pending_space += width;
+                    }
+                }
+                Token::Word(pieces) => {
+                    let mut width = TextLayout::word_width(font, &pieces);
+                    let mut pieces = pieces;
+
+                    if let Some(max_width) = max_width {
+                        if !line.words.is_empty() && line.width + pending_space + width > max_width
+                        {
+                            lines.push(std::mem::replace(
+                                &mut line,
+                                Line {
+                                    wrapped: true,
+                                    ..Default::default()
+                                },
+                            ));
+                            pending_space = 0.0;
+                        }
+
+                        if pending_space + width > max_width {
+                            let mut parts = TextLayout::split_word(font, pieces, max_width);
+                            pieces = parts.pop().unwrap_or_default();
+
+                            for part in parts {
+                                line.width = TextLayout::word_width(font, &part);
+                                line.words.push(Word {
+                                    space_before: 0.0,
+                                    pieces: part,
+                                });
+                                lines.push(std::mem::replace(
+                                    &mut line,
+                                    Line {
+                                        wrapped: true,
+                                        ..Default::default()
+                                    },
+                                ));
+                            }
+                            pending_space = 0.0;
+                            width = TextLayout::word_width(font, &pieces);
+                        }
+                    }
+
+                    line.width += pending_space + width;
+                    line.words.push(Word {
+                        space_before: pending_space,
+                        pieces,
+                    });
+                    pending_space = 0.0;
+                }
+            }
+        }
+
+        line.ends_paragraph = true;
+        lines.push(line);
+
+        lines
+    }
+    pub fn draw(&self, surface: &PixelSurface, position: PixelCoordinates) {
+        for glyph in &self.glyphs {
+            surface.queue_draw(
+                &glyph.sprite.texture,
+                glyph.sprite.vertices,
+                glyph.sprite.dimensions,
+                SpritePivot::TopLeft,
+                &SpriteDraw {
+                    position: PixelCoordinates {
+                        x: position.x + glyph.position.x,
+                        y: position.y + glyph.position.y,
+                    },
+                    color: SpriteColor::tinted(glyph.color),
+                    ..Default::default()
+                },
+                SpriteShading::Diffuse,
+            );
+        }
+    }
+}
+
+// a single unwrapped block of text anchored at position: left aligned text starts there,
+// centered text is centered on it and right aligned text ends at it; returns the drawn bounds
+pub fn draw_text(
+    surface: &PixelSurface,
+    font: &dyn FontSource,
+    text: &str,
+    position: PixelCoordinates,
+    color: Color,
+    alignment: TextAlignment,
+) -> (PixelCoordinates, PixelDimensions) {
+    let layout = TextLayout::from_text(
+        font,
+        text,
+        color,
+        TextLayoutOptions {
+            max_width: None,
+            alignment,
+        },
+    );
+
+    let width = layout.dimensions.width as i32;
+    let position = PixelCoordinates {
+        x: match alignment {
+            TextAlignment::Left | TextAlignment::Justify => position.x,
+            TextAlignment::Center => position.x - width / 2,
+            TextAlignment::Right => position.x - width,
+        },
+        y: position.y,
+    };
+
+    layout.draw(surface, position);
+    (position, layout.dimensions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // one pixel per character, glyphs left out so no textures are needed
+    struct MonoFont {
+        kerning: f32,
+    }
+
+    impl FontSource for MonoFont {
+        fn line_height(&self) -> f32 {
+            2.0
+        }
+        fn ascent(&self) -> f32 {
+            1.0
+        }
+        fn advance(&self, _character: char) -> f32 {
+            1.0
+        }
+        fn kerning(&self, _first: char, _second: char) -> f32 {
+            self.kerning
+        }
+        fn glyph(&self, _character: char) -> Option<FontGlyph> {
+            None
+        }
+    }
+
+    const FONT: MonoFont = MonoFont { kerning: 0.0 };
+
+    fn lines(font: &MonoFont, text: &str, max_width: Option<u32>) -> Vec<Line> {
+        let tokens = TextLayout::tokenize(font, &[TextSpan::text(text, Color::WHITE)]);
+        TextLayout::break_lines(
+            font,
+            tokens,
+            TextLayoutOptions {
+                max_width,
+                ..Default::default()
+            },
+        )
+    }
+
+    fn line_text(line: &Line) -> String {
+        let mut text = String::new();
+        for word in &line.words {
+            if word.space_before > 0.0 {
+                text.push(' ');
+            }
+            for piece in &word.pieces {
+                if let Piece::Character(character, _) = piece {
+                    text.push(*character);
+                }
+            }
+        }
+        text
+    }
+
+    fn texts(lines: &[Line]) -> Vec<String> {
+        lines.iter().map(line_text).collect()
+    }
+
+    #[test]
+    fn words_wrap_at_the_last_space_that_fits() {
+        let lines = lines(&FONT, "aaa bb cc", Some(6));
+
+        assert_eq!(texts(&lines), ["aaa bb", "cc"]);
+        assert_eq!(lines[0].width, 6.0);
+        assert!(!lines[0].wrapped && lines[1].wrapped);
+        assert!(!lines[0].ends_paragraph && lines[1].ends_paragraph);
+    }
+
+    #[test]
+    fn spaces_at_a_wrap_are_dropped() {
+        let lines = lines(&FONT, "aa   bb", Some(3));
+
+        assert_eq!(texts(&lines), ["aa", "bb"]);
+        assert_eq!(lines[1].width, 2.0);
+    }
+
+    #[test]
+    fn newlines_end_paragraphs_without_wrapping() {
+        let lines = lines(&FONT, "a b\n\nc", None);
+
+        assert_eq!(texts(&lines), ["a b", "", "c"]);
+        assert!(lines
+            .iter()
+            .all(|line| line.ends_paragraph && !line.wrapped));
+    }
+
+    #[test]
+    fn words_wider_than_the_line_are_split() {
+        let lines = lines(&FONT, "x abcdefg", Some(3));
+
+        assert_eq!(texts(&lines), ["x", "abc", "def", "g"]);
+        assert!(lines[1..].iter().all(|line| line.wrapped));
+        assert!(lines.iter().all(|line| line.width <= 3.0));
+    }
+
+    #[test]
+    fn kerning_counts_toward_the_line_width() {
+        let font = MonoFont { kerning: -0.5 };
+        let lines = lines(&font, "ab cd", Some(4));
+
+        assert_eq!(texts(&lines), ["ab cd"]);
+        assert_eq!(lines[0].width, 4.0);
+    }
+
+    #[test]
+    fn layout_is_as_wide_as_its_widest_line_unless_wrapped() {
+        let layout = TextLayout::from_text(&FONT, "abc\nabcde", Color::WHITE, Default::default());
+        assert_eq!(layout.dimensions.width, 5);
+        assert_eq!(layout.dimensions.height, 4);
+
+        let layout = TextLayout::from_text(
+            &FONT,
+            "abc",
+            Color::WHITE,
+            TextLayoutOptions {
+                max_width: Some(10),
+                alignment: TextAlignment::Center,
+            },
+        );
+        assert_eq!(layout.dimensions.width, 10);
+        assert!(layout.glyphs.is_empty());
+    }
+}
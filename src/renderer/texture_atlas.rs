@@ -0,0 +1,337 @@
+use super::*;
+
+use std::collections::HashMap;
+
+#[derive(Debug)]
+pub enum TextureAtlasError {
+    Image(image::ImageError),
+    // the image plus its padding and extrusion can't fit on a page of max_page_dimensions, or
+    // of the largest texture the device allows
+    TooLarge {
+        name: String,
+        dimensions: PixelDimensions,
+    },
+}
+
+impl From<image::ImageError> for TextureAtlasError {
+    fn from(error: image::ImageError) -> Self {
+        TextureAtlasError::Image(error)
+    }
+}
+
+// where a named image ended up: its page and the un-padded, un-extruded area on that page
+#[derive(Clone, Copy, Debug)]
+pub struct AtlasPlacement {
+    pub page: usize,
+    pub texture_area: SpriteTextureArea,
+}
+
+pub struct TextureAtlasBuilder {
+    max_page_dimensions: PixelDimensions,
+    padding: u32,   // transparent gap between extruded images
+    extrusion: u32, // edge pixels repeated around each image to stop sampling bleed
+    images: Vec<(String, image::RgbaImage)>,
+}
+
+impl TextureAtlasBuilder {
+    pub fn new(max_page_dimensions: PixelDimensions) -> Self {
+        Self {
+            max_page_dimensions,
+            padding: 1,
+            extrusion: 1,
+            images: Vec::new(),
+        }
+    }
+    pub fn with_padding(mut self, padding: u32) -> Self {
+        self.padding = padding;
+        self
+    }
+    pub fn with_extrusion(mut self, extrusion: u32) -> Self {
+        self.extrusion = extrusion;
+        self
+    }
+    pub fn add_image(&mut self, name: &str, image: image::RgbaImage) {
+        self.images.push((name.to_string(), image));
+    }
+    // the file path is used as the sprite name
+    pub fn add_file(&mut self, path: &str) -> Result<(), TextureAtlasError> {
+        let image = image::open(path)?.to_rgba8();
+        self.add_image(path, image);
+        Ok(())
+    }
+    // shelf packing, tallest images first; a new page is started when the current one is full,
+    // and each page is cut down to the height its shelves use
+    pub fn pack(
+        self,
+    ) -> Result<(Vec<image::RgbaImage>, HashMap<String, AtlasPlacement>), TextureAtlasError> {
+        let border = self.padding + self.extrusion;
+
+        let mut order: Vec<usize> = (0..self.images.len()).collect();
+        order.sort_by_key(|&index| {
+            let (width, height) = self.images[index].1.dimensions();
+            (std::cmp::Reverse(height), std::cmp::Reverse(width))
+        });
+
+        let mut pages: Vec<image::RgbaImage> = Vec::new();
+        let mut used_heights: Vec<u32> = Vec::new();
+        let mut placements = HashMap::new();
+
+        // cursor of the current shelf on the last page
+        let (mut shelf_x, mut shelf_y, mut shelf_height) = (0, 0, 0);
+
+        for index in order {
+            let (name, image) = &self.images[index];
+            let (width, height) = image.dimensions();
+
+            let cell_width = width + border * 2;
+            let cell_height = height + border * 2;
+
+            if cell_width > self.max_page_dimensions.width
+                || cell_height > self.max_page_dimensions.height
+            {
+                return Err(TextureAtlasError::TooLarge {
+                    name: name.clone(),
+                    dimensions: PixelDimensions { width, height },
+                });
+            }
+
+            if shelf_x + cell_width > self.max_page_dimensions.width {
+                shelf_x = 0;
+                shelf_y += shelf_height;
+                shelf_height = 0;
+            }
+
+            if pages.is_empty() || shelf_y + cell_height > self.max_page_dimensions.height {
+                pages.push(image::RgbaImage::new(
+                    self.max_page_dimensions.width,
+                    self.max_page_dimensions.height,
+                ));
+                used_heights.push(0);
+                (shelf_x, shelf_y, shelf_height) = (0, 0, 0);
+            }
+
+            let page = pages.len() - 1;
+            let x = shelf_x + border;
+            let y = shelf_y + border;
+
+            blit_extruded(&mut pages[page], image, x, y, self.extrusion);
+
+            placements.insert(
+                name.clone(),
+                AtlasPlacement {
+                    page,
+                    texture_area: SpriteTextureArea {
+                        coordinates: PixelCoordinates {
+                            x: x as i32,
+                            y: y as i32,
+                        },
+                        dimensions: PixelDimensions { width, height },
+                    },
+                },
+            );
+
+            shelf_x += cell_width;
+            shelf_height = shelf_height.max(cell_height);
+            used_heights[page] = shelf_y + shelf_height;
+        }
+
+        let pages = pages
+            .into_iter()
+            .zip(used_heights)
+            .map(|(page, used_height)| {
+                // textures can't be empty, as a page of only empty images would be
+                let height = used_height.max(1);
+                image::imageops::crop_imm(&page, 0, 0, page.width(), height).to_image()
+            })
+            .collect();
+
+        Ok((pages, placements))
+    }
+}
+
+// copies image to (x, y) and repeats its outermost pixels extrusion times in every direction
+fn blit_extruded(
+    page: &mut image::RgbaImage,
+    image: &image::RgbaImage,
+    x: u32,
+    y: u32,
+    extrusion: u32,
+) {
+    let (width, height) = image.dimensions();
+    if width == 0 || height == 0 {
+        return;
+    }
+    let extrusion = extrusion as i64;
+
+    for dy in -extrusion..height as i64 + extrusion {
+        for dx in -extrusion..width as i64 + extrusion {
+            let source_x = dx.clamp(0, width as i64 - 1) as u32;
+            let source_y = dy.clamp(0, height as i64 - 1) as u32;
+            let target_x = (x as i64 + dx) as u32;
+            let target_y = (y as i64 + dy) as u32;
+            page.put_pixel(target_x, target_y, *image.get_pixel(source_x, source_y));
+        }
+    }
+}
+
+pub struct TextureAtlas {
+    pub textures: Vec<Rc<Texture>>,
+    pub sprites: HashMap<String, Sprite>,
+}
+
+impl TextureAtlas {
+    // pages larger than the device allows are cut down to its limit before packing
+    pub(crate) fn create(
+        texture_manager: &TextureManager,
+        builder: TextureAtlasBuilder,
+    ) -> Result<Self, TextureAtlasError> {
+        let max_dimension = texture_manager.max_texture_dimension();
+        let builder = TextureAtlasBuilder {
+            max_page_dimensions: PixelDimensions {
+                width: builder.max_page_dimensions.width.min(max_dimension),
+                height: builder.max_page_dimensions.height.min(max_dimension),
+            },
+            ..builder
+        };
+
+        let (pages, placements) = builder.pack()?;
+
+        let textures: Vec<Rc<Texture>> = pages
+            .into_iter()
+            .map(|page| Rc::new(texture_manager.create_texture(page)))
+            .collect();
+
+        let sprites = placements
+            .into_iter()
+            .map(|(name, placement)| {
+                let texture = Rc::clone(&textures[placement.page]);
+                (name, Sprite::create(texture, Some(placement.texture_area)))
+            })
+            .collect();
+
+        Ok(Self { textures, sprites })
+    }
+    pub fn sprite(&self, name: &str) -> Option<&Sprite> {
+        self.sprites.get(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn builder(width: u32, height: u32) -> TextureAtlasBuilder {
+        TextureAtlasBuilder::new(PixelDimensions { width, height })
+    }
+
+    fn coordinates(placements: &HashMap<String, AtlasPlacement>, name: &str) -> (i32, i32) {
+        let coordinates = placements[name].texture_area.coordinates;
+        (coordinates.x, coordinates.y)
+    }
+
+    #[test]
+    fn shelves_fill_left_to_right_tallest_first() {
+        let mut builder = builder(24, 64);
+        builder.add_image("small", image::RgbaImage::new(4, 4));
+        builder.add_image("tall", image::RgbaImage::new(8, 8));
+        builder.add_image("wide", image::RgbaImage::new(8, 4));
+
+        let (pages, placements) = builder.pack().unwrap();
+
+        // each cell is the image plus 2 pixels of padding and extrusion on every side
+        assert_eq!(coordinates(&placements, "tall"), (2, 2));
+        assert_eq!(coordinates(&placements, "wide"), (14, 2));
+        assert_eq!(coordinates(&placements, "small"), (2, 14));
+        assert_eq!(
+            placements["wide"].texture_area.dimensions,
+            PixelDimensions {
+                width: 8,
+                height: 4,
+            }
+        );
+
+        // cut down to the second shelf's bottom
+        assert_eq!(pages.len(), 1);
+        assert_eq!(pages[0].dimensions(), (24, 20));
+    }
+
+    #[test]
+    fn full_pages_start_new_ones() {
+        let mut builder = builder(16, 16);
+        builder.add_image("first", image::RgbaImage::new(10, 10));
+        builder.add_image("second", image::RgbaImage::new(10, 10));
+
+        let (pages, placements) = builder.pack().unwrap();
+
+        assert_eq!(pages.len(), 2);
+        assert_eq!(placements["first"].page, 0);
+        assert_eq!(placements["second"].page, 1);
+        assert_eq!(coordinates(&placements, "second"), (2, 2));
+        assert!(pages.iter().all(|page| page.dimensions() == (16, 14)));
+    }
+
+    #[test]
+    fn images_are_extruded_inside_their_padding() {
+        let red = image::Rgba([255, 0, 0, 255]);
+        let blue = image::Rgba([0, 0, 255, 255]);
+        let mut image = image::RgbaImage::from_pixel(2, 2, blue);
+        image.put_pixel(0, 0, red);
+
+        let mut builder = builder(16, 16).with_padding(2).with_extrusion(1);
+        builder.add_image("image", image);
+
+        let (pages, placements) = builder.pack().unwrap();
+        let page = &pages[0];
+
+        assert_eq!(coordinates(&placements, "image"), (3, 3));
+        assert_eq!(*page.get_pixel(3, 3), red);
+        assert_eq!(*page.get_pixel(4, 4), blue);
+        // the corner and edges repeat the image's outermost pixels
+        assert_eq!(*page.get_pixel(2, 2), red);
+        assert_eq!(*page.get_pixel(2, 4), blue);
+        assert_eq!(*page.get_pixel(5, 5), blue);
+        // with the padding left clear
+        assert_eq!(page.get_pixel(1, 1).0, [0, 0, 0, 0]);
+        assert_eq!(page.get_pixel(6, 3).0, [0, 0, 0, 0]);
+        assert_eq!(page.dimensions(), (16, 8));
+    }
+
+    #[test]
+    fn without_padding_or_extrusion_images_touch() {
+        let mut builder = builder(8, 8).with_padding(0).with_extrusion(0);
+        builder.add_image("left", image::RgbaImage::new(4, 4));
+        builder.add_image("right", image::RgbaImage::new(4, 4));
+
+        let (pages, placements) = builder.pack().unwrap();
+
+        let mut placed = [
+            coordinates(&placements, "left"),
+            coordinates(&placements, "right"),
+        ];
+        placed.sort();
+        assert_eq!(placed, [(0, 0), (4, 0)]);
+        assert_eq!(pages[0].dimensions(), (8, 4));
+    }
+
+    #[test]
+    fn images_too_large_for_a_page_are_rejected() {
+        // 15 pixels fit, but not with 2 pixels of padding and extrusion on both sides
+        let mut builder = builder(16, 16);
+        builder.add_image("fits", image::RgbaImage::new(4, 4));
+        builder.add_image("huge", image::RgbaImage::new(15, 4));
+
+        match builder.pack() {
+            Err(TextureAtlasError::TooLarge { name, dimensions }) => {
+                assert_eq!(name, "huge");
+                assert_eq!(
+                    dimensions,
+                    PixelDimensions {
+                        width: 15,
+                        height: 4,
+                    }
+                );
+            }
+            _ => panic!("the image was packed"),
+        }
+    }
+}
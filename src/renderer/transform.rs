@@ -0,0 +1,226 @@
+use nalgebra::{Matrix3, Point2, Vector2};
+
+use std::{f32::consts::PI, ops::Mul};
+
+// affine transform of surface pixels with y pointing down; angles are fractions of a turn and
+// positive rotations turn counter-clockwise on screen
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform2d {
+    matrix: Matrix3<f32>,
+}
+
+impl Transform2d {
+    pub fn identity() -> Self {
+        Self {
+            matrix: Matrix3::identity(),
+        }
+    }
+    // the bottom row is expected to be 0, 0, 1
+    pub fn from_matrix(matrix: Matrix3<f32>) -> Self {
+        Self { matrix }
+    }
+    pub fn matrix(&self) -> Matrix3<f32> {
+        self.matrix
+    }
+    pub fn translation(x: f32, y: f32) -> Self {
+        Self::from_matrix(Matrix3::new(
+            1.0, 0.0, x, //
+            0.0, 1.0, y, //
+            0.0, 0.0, 1.0,
+        ))
+    }
+    pub fn rotation(fraction: f32) -> Self {
+        let (sin, cos) = (fraction * 2.0 * PI).sin_cos();
+        Self::from_matrix(Matrix3::new(
+            cos, sin, 0.0, //
+            -sin, cos, 0.0, //
+            0.0, 0.0, 1.0,
+        ))
+    }
+    pub fn scale(x: f32, y: f32) -> Self {
+        Self::from_matrix(Matrix3::new(
+            x, 0.0, 0.0, //
+            0.0, y, 0.0, //
+            0.0, 0.0, 1.0,
+        ))
+    }
+    // x leans vertical lines and y leans horizontal lines by that fraction of a turn
+    pub fn skew(x: f32, y: f32) -> Self {
+        let (x, y) = ((x * 2.0 * PI).tan(), (y * 2.0 * PI).tan());
+        Self::from_matrix(Matrix3::new(
+            1.0, x, 0.0, //
+            y, 1.0, 0.0, //
+            0.0, 0.0, 1.0,
+        ))
+    }
+    // scales, skews and rotates about the pivot, then translates
+    pub fn from_parts(
+        translation: Vector2<f32>,
+        rotation: f32,
+        scale: Vector2<f32>,
+        skew: Vector2<f32>,
+        pivot: Point2<f32>,
+    ) -> Self {
+        Transform2d::scale(scale.x, scale.y)
+            .skewed(skew.x, skew.y)
+            .rotated(rotation)
+            .around(pivot)
+            .translated(translation.x, translation.y)
+    }
+    // self is applied first, then next
+    pub fn then(&self, next: &Transform2d) -> Self {
+        Self::from_matrix(next.matrix * self.matrix)
+    }
+    pub fn translated(self, x: f32, y: f32) -> Self {
+        self.then(&Transform2d::translation(x, y))
+    }
+    pub fn rotated(self, fraction: f32) -> Self {
+        self.then(&Transform2d::rotation(fraction))
+    }
+    pub fn scaled(self, x: f32, y: f32) -> Self {
+        self.then(&Transform2d::scale(x, y))
+    }
+    pub fn skewed(self, x: f32, y: f32) -> Self {
+        self.then(&Transform2d::skew(x, y))
+    }
+    // the same transform with pivot as its origin, so that pivot stays in place
+    pub fn around(self, pivot: Point2<f32>) -> Self {
+        Transform2d::translation(-pivot.x, -pivot.y)
+            .then(&self)
+            .translated(pivot.x, pivot.y)
+    }
+    // None when the transform collapses everything onto a line or a point
+    pub fn inverse(&self) -> Option<Self> {
+        self.matrix.try_inverse().map(Self::from_matrix)
+    }
+    pub fn transform_point(&self, point: Point2<f32>) -> Point2<f32> {
+        self.matrix.transform_point(&point)
+    }
+    pub fn transform_vector(&self, vector: Vector2<f32>) -> Vector2<f32> {
+        self.matrix.transform_vector(&vector)
+    }
+    // the three columns of the top two rows, as read by sprite2d.wgsl
+    pub(crate) fn to_columns(self) -> [[f32; 2]; 3] {
+        let m = &self.matrix;
+        [
+            [m[(0, 0)], m[(1, 0)]],
+            [m[(0, 1)], m[(1, 1)]],
+            [m[(0, 2)], m[(1, 2)]],
+        ]
+    }
+}
+
+impl Default for Transform2d {
+    fn default() -> Self {
+        Transform2d::identity()
+    }
+}
+
+// a * b applies b first, as with matrices
+impl Mul for Transform2d {
+    type Output = Transform2d;
+
+    fn mul(self, rhs: Transform2d) -> Transform2d {
+        rhs.then(&self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(actual: Point2<f32>, expected: (f32, f32)) {
+        assert!(
+            (actual.x - expected.0).abs() < 1e-5 && (actual.y - expected.1).abs() < 1e-5,
+            "{actual:?} is not near {expected:?}"
+        );
+    }
+
+    #[test]
+    fn quarter_turns_go_counter_clockwise_on_screen() {
+        let turn = Transform2d::rotation(0.25);
+
+        // with y down, right turns to up
+        assert_near(turn.transform_point(Point2::new(1.0, 0.0)), (0.0, -1.0));
+        assert_near(turn.transform_point(Point2::new(0.0, -1.0)), (-1.0, 0.0));
+    }
+
+    #[test]
+    fn then_applies_self_first() {
+        let point = Point2::new(1.0, 0.0);
+        let scaled_then_moved =
+            Transform2d::scale(2.0, 2.0).then(&Transform2d::translation(3.0, 0.0));
+        let moved_then_scaled =
+            Transform2d::translation(3.0, 0.0).then(&Transform2d::scale(2.0, 2.0));
+
+        assert_near(scaled_then_moved.transform_point(point), (5.0, 0.0));
+        assert_near(moved_then_scaled.transform_point(point), (8.0, 0.0));
+        assert_eq!(
+            Transform2d::translation(3.0, 0.0) * Transform2d::scale(2.0, 2.0),
+            scaled_then_moved
+        );
+    }
+
+    #[test]
+    fn around_keeps_the_pivot_in_place() {
+        let pivot = Point2::new(4.0, 2.0);
+        let transform = Transform2d::rotation(0.25).scaled(2.0, 3.0).around(pivot);
+
+        assert_near(transform.transform_point(pivot), (4.0, 2.0));
+        assert_near(
+            transform.transform_point(Point2::new(5.0, 2.0)),
+            (4.0, -1.0),
+        );
+    }
+
+    #[test]
+    fn from_parts_translates_after_transforming_about_the_pivot() {
+        let transform = Transform2d::from_parts(
+            Vector2::new(10.0, 0.0),
+            0.5,
+            Vector2::new(2.0, 2.0),
+            Vector2::zeros(),
+            Point2::new(1.0, 1.0),
+        );
+
+        assert_near(
+            transform.transform_point(Point2::new(1.0, 1.0)),
+            (11.0, 1.0),
+        );
+        assert_near(transform.transform_point(Point2::new(2.0, 1.0)), (9.0, 1.0));
+    }
+
+    #[test]
+    fn inverse_undoes_the_transform() {
+        let transform = Transform2d::translation(3.0, -2.0)
+            .rotated(0.1)
+            .skewed(0.05, 0.0)
+            .scaled(2.0, 0.5);
+        let inverse = transform.inverse().unwrap();
+
+        let point = Point2::new(7.0, -4.0);
+        let round_trip = inverse.transform_point(transform.transform_point(point));
+        assert_near(round_trip, (7.0, -4.0));
+        // vectors ignore the translation
+        assert_near(
+            Point2::from(transform.transform_vector(Vector2::zeros())),
+            (0.0, 0.0),
+        );
+    }
+
+    #[test]
+    fn collapsing_transforms_have_no_inverse() {
+        assert!(Transform2d::scale(0.0, 1.0).inverse().is_none());
+    }
+
+    #[test]
+    fn columns_are_the_top_two_rows() {
+        let transform = Transform2d::from_matrix(Matrix3::new(
+            1.0, 2.0, 3.0, //
+            4.0, 5.0, 6.0, //
+            0.0, 0.0, 1.0,
+        ));
+
+        assert_eq!(transform.to_columns(), [[1.0, 4.0], [2.0, 5.0], [3.0, 6.0]]);
+    }
+}
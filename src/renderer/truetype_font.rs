@@ -0,0 +1,227 @@
+use super::*;
+
+use glyph_atlas::GlyphAtlas;
+use text_layout::{FontGlyph, FontSource, TextAlignment};
+
+use std::{cell::RefCell, collections::HashMap};
+
+#[derive(Debug)]
+pub enum TrueTypeFontError {
+    Io(std::io::Error),
+    Malformed(&'static str),
+}
+
+impl From<std::io::Error> for TrueTypeFontError {
+    fn from(error: std::io::Error) -> Self {
+        TrueTypeFontError::Io(error)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct GlyphKey {
+    index: u16,
+    size: u32, // bits of the f32 pixel size
+    pixel_snap: bool,
+}
+
+#[derive(Clone, Copy)]
+struct RasterizedGlyph {
+    texture_area: Option<SpriteTextureArea>, // None for glyphs with no pixels, like space
+    offset: PixelCoordinates,                // top-left relative to the pen on the baseline
+}
+
+pub struct TrueTypeFont {
+    font: fontdue::Font,
+    atlas: RefCell<GlyphAtlas>,
+    glyphs: RefCell<HashMap<GlyphKey, RasterizedGlyph>>,
+    // whole-pixel advances and hard-edged glyphs, for low resolution surfaces
+    pub pixel_snap: bool,
+}
+
+impl TrueTypeFont {
+    pub(crate) fn load(
+        texture_manager: &TextureManager,
+        path: &str,
+    ) -> Result<Self, TrueTypeFontError> {
+        let bytes = std::fs::read(path)?;
+        TrueTypeFont::from_bytes(texture_manager, &bytes)
+    }
+    // ttf, otf or a ttc, in which case the first face is used
+    pub(crate) fn from_bytes(
+        texture_manager: &TextureManager,
+        bytes: &[u8],
+    ) -> Result<Self, TrueTypeFontError> {
+        let font = fontdue::Font::from_bytes(bytes, fontdue::FontSettings::default())
+            .map_err(TrueTypeFontError::Malformed)?;
+
+        Ok(Self {
+            font,
+            atlas: RefCell::new(GlyphAtlas::new(texture_manager.clone())),
+            glyphs: RefCell::new(HashMap::new()),
+            pixel_snap: true,
+        })
+    }
+    fn snap(&self, value: f32) -> f32 {
+        if self.pixel_snap {
+            value.round()
+        } else {
+            value
+        }
+    }
+    pub fn ascent(&self, size: f32) -> f32 {
+        let ascent = self
+            .font
+            .horizontal_line_metrics(size)
+            .map(|metrics| metrics.ascent)
+            .unwrap_or(size);
+        self.snap(ascent)
+    }
+    // distance between the baselines of consecutive lines
+    pub fn line_height(&self, size: f32) -> f32 {
+        let line_height = self
+            .font
+            .horizontal_line_metrics(size)
+            .map(|metrics| metrics.new_line_size)
+            .unwrap_or(size);
+        self.snap(line_height)
+    }
+    pub fn kerning(&self, first: char, second: char, size: f32) -> f32 {
+        let kerning = self
+            .font
+            .horizontal_kern(first, second, size)
+            .unwrap_or(0.0);
+        self.snap(kerning)
+    }
+    pub fn advance(&self, character: char, size: f32) -> f32 {
+        self.snap(self.font.metrics(character, size).advance_width)
+    }
+    // advance width of a single line, kerning included
+    pub fn line_width(&self, line: &str, size: f32) -> f32 {
+        let mut width = 0.0;
+        let mut previous = None;
+
+        for character in line.chars() {
+            if let Some(previous) = previous {
+                width += self.kerning(previous, character, size);
+            }
+            width += self.advance(character, size);
+            previous = Some(character);
+        }
+
+        width
+    }
+    pub fn measure(&self, text: &str, size: f32) -> PixelDimensions {
+        let width = text
+            .lines()
+            .map(|line| self.line_width(line, size))
+            .fold(0.0, f32::max);
+        let lines = text.lines().count().max(1) as f32;
+
+        PixelDimensions {
+            width: width.ceil() as u32,
+            height: (lines * self.line_height(size)).ceil() as u32,
+        }
+    }
+    // rasterizes the glyph into the atlas the first time it is drawn at this size
+    fn glyph(&self, character: char, size: f32) -> RasterizedGlyph {
+        let key = GlyphKey {
+            index: self.font.lookup_glyph_index(character),
+            size: size.to_bits(),
+            pixel_snap: self.pixel_snap,
+        };
+
+        if let Some(glyph) = self.glyphs.borrow().get(&key) {
+            return *glyph;
+        }
+
+        let (metrics, coverage) = self.font.rasterize_indexed(key.index, size);
+
+        let texture_area = if metrics.width > 0 && metrics.height > 0 {
+            let glyph_image =
+                image::RgbaImage::from_fn(metrics.width as u32, metrics.height as u32, |x, y| {
+                    let alpha = coverage[y as usize * metrics.width + x as usize];
+                    let alpha = match self.pixel_snap {
+                        true if alpha >= 128 => 255,
+                        true => 0,
+                        false => alpha,
+                    };
+                    image::Rgba([255, 255, 255, alpha])
+                });
+            self.atlas.borrow_mut().insert(&glyph_image)
+        } else {
+            None
+        };
+
+        // fontdue measures ymin upwards from the baseline to the bottom of the bitmap
+        let glyph = RasterizedGlyph {
+            texture_area,
+            offset: PixelCoordinates {
+                x: metrics.xmin,
+                y: -(metrics.ymin + metrics.height as i32),
+            },
+        };
+
+        self.glyphs.borrow_mut().insert(key, glyph);
+        glyph
+    }
+    // the atlas texture changes as it grows, check it again after drawing new glyphs
+    pub fn atlas_texture(&self) -> Rc<Texture> {
+        Rc::clone(self.atlas.borrow().texture())
+    }
+    pub fn at_size(&self, size: f32) -> SizedFont<'_> {
+        SizedFont { font: self, size }
+    }
+    // position is the top of the first line; returns the bounds of the drawn text
+    pub fn draw_text(
+        &self,
+        surface: &PixelSurface,
+        text: &str,
+        position: PixelCoordinates,
+        size: f32,
+        color: Color,
+        alignment: TextAlignment,
+    ) -> (PixelCoordinates, PixelDimensions) {
+        text_layout::draw_text(
+            surface,
+            &self.at_size(size),
+            text,
+            position,
+            color,
+            alignment,
+        )
+    }
+}
+
+// a TrueTypeFont at one pixel size, which is what text layout works with
+pub struct SizedFont<'a> {
+    font: &'a TrueTypeFont,
+    size: f32,
+}
+
+impl FontSource for SizedFont<'_> {
+    fn line_height(&self) -> f32 {
+        self.font.line_height(self.size)
+    }
+    fn ascent(&self) -> f32 {
+        self.font.ascent(self.size)
+    }
+    fn advance(&self, character: char) -> f32 {
+        self.font.advance(character, self.size)
+    }
+    fn kerning(&self, first: char, second: char) -> f32 {
+        self.font.kerning(first, second, self.size)
+    }
+    fn glyph(&self, character: char) -> Option<FontGlyph> {
+        let glyph = self.font.glyph(character, self.size);
+        // the atlas is looked up after rasterizing, since inserting the glyph may have grown it
+        let sprite = Sprite::create(self.font.atlas_texture(), Some(glyph.texture_area?));
+
+        Some(FontGlyph {
+            sprite,
+            offset: PixelCoordinates {
+                x: glyph.offset.x,
+                y: self.ascent().round() as i32 + glyph.offset.y,
+            },
+        })
+    }
+}
@@ -0,0 +1,514 @@
+use super::*;
+
+use lyon_tessellation::{
+    geom::{Angle, Arc},
+    math::{point, vector, Point},
+    path::{self, iterator::PathIterator, Path, PathEvent},
+    BuffersBuilder, FillOptions, FillTessellator, FillVertex, Side, StrokeOptions,
+    StrokeTessellator, StrokeVertex, VertexBuffers,
+};
+use nalgebra::Point2;
+
+use std::f32::consts::PI;
+
+// distance in pixels a flattened curve may stray from the real one
+const TOLERANCE: f32 = 0.1;
+
+// read by path2d.wgsl; antialias is (side, half width) where side runs from -1 on one edge
+// of a stroke to 1 on the other, and coverage fades over the outermost pixel
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct PathVertex {
+    pub position: [f32; 2],
+    pub color: Color,
+    pub antialias: [f32; 2],
+}
+
+impl PathVertex {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 3] = wgpu::vertex_attr_array![
+        0 => Float32x2, // position
+        1 => Float32x4, // color
+        2 => Float32x2, // antialias
+    ];
+
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<PathVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineJoin {
+    Miter,
+    Round,
+    Bevel,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineCap {
+    Butt,
+    Round,
+    Square,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct StrokeStyle {
+    pub width: f32,
+    pub join: LineJoin,
+    pub cap: LineCap,
+}
+
+impl Default for StrokeStyle {
+    fn default() -> Self {
+        Self {
+            width: 1.0,
+            join: LineJoin::Miter,
+            cap: LineCap::Butt,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct VectorPath {
+    path: Path,
+}
+
+impl VectorPath {
+    pub fn builder() -> VectorPathBuilder {
+        VectorPathBuilder {
+            builder: Path::builder(),
+            in_subpath: false,
+            start: point(0.0, 0.0),
+            current: point(0.0, 0.0),
+        }
+    }
+    pub fn circle(center: Point2<f32>, radius: f32) -> Self {
+        VectorPath::builder()
+            .arc(center, radius, 0.0, 1.0)
+            .close()
+            .build()
+    }
+    pub fn rounded_rect(position: Point2<f32>, width: f32, height: f32, radius: f32) -> Self {
+        let radius = radius.min(width / 2.0).min(height / 2.0).max(0.0);
+        let (left, top) = (position.x, position.y);
+        let (right, bottom) = (left + width, top + height);
+
+        // quarter turns clockwise from the top-right corner, since y points down
+        VectorPath::builder()
+            .move_to(Point2::new(left + radius, top))
+            .arc(
+                Point2::new(right - radius, top + radius),
+                radius,
+                0.75,
+                0.25,
+            )
+            .arc(
+                Point2::new(right - radius, bottom - radius),
+                radius,
+                0.0,
+                0.25,
+            )
+            .arc(
+                Point2::new(left + radius, bottom - radius),
+                radius,
+                0.25,
+                0.25,
+            )
+            .arc(Point2::new(left + radius, top + radius), radius, 0.5, 0.25)
+            .close()
+            .build()
+    }
+    pub(crate) fn fill_mesh(&self, color: Color) -> (Vec<PathVertex>, Vec<u32>) {
+        let mut buffers: VertexBuffers<PathVertex, u32> = VertexBuffers::new();
+
+        let interior = FillTessellator::new().tessellate_path(
+            &self.path,
+            &FillOptions::non_zero().with_tolerance(TOLERANCE),
+            &mut BuffersBuilder::new(&mut buffers, |vertex: FillVertex| PathVertex {
+                position: vertex.position().to_array(),
+                color,
+                antialias: [0.0, 1.0],
+            }),
+        );
+
+        // a one pixel wide fringe outside the outline feathers the edge, fading from full
+        // coverage on the outline to none a pixel out; it stays off the fill, so translucent
+        // colors don't pile up along the edge
+        let mut fringe = Ok(());
+        for (subpath, outer_side) in self.closed_subpaths() {
+            let result = StrokeTessellator::new().tessellate_path(
+                &subpath,
+                &StrokeOptions::default()
+                    .with_line_width(2.0)
+                    .with_line_join(path::LineJoin::Round)
+                    .with_tolerance(TOLERANCE),
+                &mut BuffersBuilder::new(&mut buffers, |vertex: StrokeVertex| {
+                    let outer = vertex.side() == outer_side;
+                    let position = if outer {
+                        vertex.position()
+                    } else {
+                        vertex.position_on_path()
+                    };
+                    PathVertex {
+                        position: position.to_array(),
+                        color,
+                        antialias: [if outer { 1.0 } else { 0.0 }, 1.0],
+                    }
+                }),
+            );
+            fringe = fringe.and(result);
+        }
+
+        match (interior, fringe) {
+            (Ok(_), Ok(_)) => (buffers.vertices, buffers.indices),
+            _ => (Vec::new(), Vec::new()),
+        }
+    }
+    // each subpath closed, as filling closes it, with the side of the stroke tessellator that
+    // faces away from the fill; subpaths without area are left out
+    fn closed_subpaths(&self) -> Vec<(Path, Side)> {
+        let mut subpaths = Vec::new();
+        let mut builder = Path::builder();
+
+        for event in self.path.iter() {
+            match event {
+                PathEvent::End { last, first, .. } => {
+                    builder.path_event(PathEvent::End {
+                        last,
+                        first,
+                        close: true,
+                    });
+                    subpaths.push(std::mem::replace(&mut builder, Path::builder()).build());
+                }
+                event => builder.path_event(event),
+            }
+        }
+
+        subpaths
+            .into_iter()
+            .filter_map(|subpath| {
+                // twice the signed area, positive for clockwise on screen since y points down
+                let area: f32 = subpath
+                    .iter()
+                    .flattened(TOLERANCE)
+                    .map(|event| match event {
+                        PathEvent::Line { from, to } => from.to_vector().cross(to.to_vector()),
+                        PathEvent::End { last, first, .. } => {
+                            last.to_vector().cross(first.to_vector())
+                        }
+                        _ => 0.0,
+                    })
+                    .sum();
+
+                match area {
+                    area if area > 0.0 => Some((subpath, Side::Negative)),
+                    area if area < 0.0 => Some((subpath, Side::Positive)),
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+    pub(crate) fn stroke_mesh(
+        &self,
+        style: StrokeStyle,
+        color: Color,
+    ) -> (Vec<PathVertex>, Vec<u32>) {
+        let mut buffers: VertexBuffers<PathVertex, u32> = VertexBuffers::new();
+
+        // widened by a pixel so the outermost half pixel on each side can fade out
+        let half_width = (style.width + 1.0) / 2.0;
+
+        let join = match style.join {
+            LineJoin::Miter => path::LineJoin::Miter,
+            LineJoin::Round => path::LineJoin::Round,
+            LineJoin::Bevel => path::LineJoin::Bevel,
+        };
+        let cap = match style.cap {
+            LineCap::Butt => path::LineCap::Butt,
+            LineCap::Round => path::LineCap::Round,
+            LineCap::Square => path::LineCap::Square,
+        };
+
+        let result = StrokeTessellator::new().tessellate_path(
+            &self.path,
+            &StrokeOptions::default()
+                .with_line_width(half_width * 2.0)
+                .with_line_join(join)
+                .with_line_cap(cap)
+                .with_tolerance(TOLERANCE),
+            &mut BuffersBuilder::new(&mut buffers, |vertex: StrokeVertex| PathVertex {
+                position: vertex.position().to_array(),
+                color,
+                antialias: [side_sign(vertex.side()), half_width],
+            }),
+        );
+
+        match result {
+            Ok(_) => (buffers.vertices, buffers.indices),
+            Err(_) => (Vec::new(), Vec::new()),
+        }
+    }
+}
+
+fn side_sign(side: Side) -> f32 {
+    match side {
+        Side::Positive => 1.0,
+        Side::Negative => -1.0,
+    }
+}
+
+// each method takes and returns the builder so segments can be chained
+pub struct VectorPathBuilder {
+    builder: path::path::Builder,
+    in_subpath: bool,
+    start: Point, // of the current subpath, where close returns to
+    current: Point,
+}
+
+impl VectorPathBuilder {
+    fn to_point(position: Point2<f32>) -> Point {
+        point(position.x, position.y)
+    }
+    // starts the subpath at the current point when a segment is added without a move_to
+    fn ensure_subpath(&mut self) {
+        if !self.in_subpath {
+            self.builder.begin(self.current);
+            self.start = self.current;
+            self.in_subpath = true;
+        }
+    }
+    // ends the current subpath without closing it
+    pub fn move_to(mut self, to: Point2<f32>) -> Self {
+        if self.in_subpath {
+            self.builder.end(false);
+        }
+        self.current = VectorPathBuilder::to_point(to);
+        self.builder.begin(self.current);
+        self.start = self.current;
+        self.in_subpath = true;
+        self
+    }
+    pub fn line_to(mut self, to: Point2<f32>) -> Self {
+        self.ensure_subpath();
+        self.current = VectorPathBuilder::to_point(to);
+        self.builder.line_to(self.current);
+        self
+    }
+    pub fn quadratic_to(mut self, control: Point2<f32>, to: Point2<f32>) -> Self {
+        self.ensure_subpath();
+        self.current = VectorPathBuilder::to_point(to);
+        self.builder
+            .quadratic_bezier_to(VectorPathBuilder::to_point(control), self.current);
+        self
+    }
+    pub fn cubic_to(
+        mut self,
+        control_1: Point2<f32>,
+        control_2: Point2<f32>,
+        to: Point2<f32>,
+    ) -> Self {
+        self.ensure_subpath();
+        self.current = VectorPathBuilder::to_point(to);
+        self.builder.cubic_bezier_to(
+            VectorPathBuilder::to_point(control_1),
+            VectorPathBuilder::to_point(control_2),
+            self.current,
+        );
+        self
+    }
+    // a circular arc with angles in fractions of a full turn, clockwise from the positive x
+    // axis since y points down; a line joins the current point to the start of the arc
+    pub fn arc(mut self, center: Point2<f32>, radius: f32, start: f32, sweep: f32) -> Self {
+        let arc = Arc {
+            center: VectorPathBuilder::to_point(center),
+            radii: vector(radius, radius),
+            start_angle: Angle::radians(start * 2.0 * PI),
+            sweep_angle: Angle::radians(sweep * 2.0 * PI),
+            x_rotation: Angle::radians(0.0),
+        };
+
+        let arc_start = arc.from();
+        if self.in_subpath {
+            self.builder.line_to(arc_start);
+        } else {
+            self.builder.begin(arc_start);
+            self.start = arc_start;
+            self.in_subpath = true;
+        }
+
+        let builder = &mut self.builder;
+        arc.for_each_quadratic_bezier(&mut |segment| {
+            builder.quadratic_bezier_to(segment.ctrl, segment.to);
+        });
+
+        self.current = arc.to();
+        self
+    }
+    // joins the current point back to the start of the subpath
+    pub fn close(mut self) -> Self {
+        if self.in_subpath {
+            self.builder.end(true);
+            self.in_subpath = false;
+            self.current = self.start;
+        }
+        self
+    }
+    pub fn build(mut self) -> VectorPath {
+        if self.in_subpath {
+            self.builder.end(false);
+        }
+        VectorPath {
+            path: self.builder.build(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(clockwise: bool) -> VectorPath {
+        let corners = [(0.0, 0.0), (4.0, 0.0), (4.0, 4.0), (0.0, 4.0)];
+        let mut builder = VectorPath::builder().move_to(Point2::new(0.0, 0.0));
+        for index in 1..4 {
+            let (x, y) = corners[if clockwise { index } else { 4 - index }];
+            builder = builder.line_to(Point2::new(x, y));
+        }
+        builder.close().build()
+    }
+
+    fn events(path: &VectorPath) -> Vec<PathEvent> {
+        path.path.iter().collect()
+    }
+
+    fn close_to(point: Point, x: f32, y: f32) -> bool {
+        (point.x - x).abs() < 1e-4 && (point.y - y).abs() < 1e-4
+    }
+
+    // the corners of the box around the flattened path
+    fn bounds(path: &VectorPath) -> (Point, Point) {
+        let points: Vec<Point> = path
+            .path
+            .iter()
+            .flattened(TOLERANCE)
+            .filter_map(|event| match event {
+                PathEvent::Begin { at } => Some(at),
+                PathEvent::Line { to, .. } => Some(to),
+                _ => None,
+            })
+            .collect();
+        let min = points.iter().fold(points[0], |min, point| min.min(*point));
+        let max = points.iter().fold(points[0], |max, point| max.max(*point));
+        (min, max)
+    }
+
+    #[test]
+    fn arcs_run_clockwise_in_fractions_of_a_turn() {
+        // a quarter turn from straight down to straight left, since y points down
+        let path = VectorPath::builder()
+            .arc(Point2::new(10.0, 10.0), 5.0, 0.25, 0.25)
+            .build();
+        let events = events(&path);
+
+        let PathEvent::Begin { at } = events[0] else {
+            panic!("{:?} doesn't begin the path", events[0]);
+        };
+        assert!(close_to(at, 10.0, 15.0));
+        let Some(PathEvent::End { last, close, .. }) = events.last() else {
+            panic!("the path isn't ended");
+        };
+        assert!(close_to(*last, 5.0, 10.0));
+        assert!(!close);
+    }
+
+    #[test]
+    fn an_arc_after_a_segment_is_joined_by_a_line() {
+        let path = VectorPath::builder()
+            .move_to(Point2::new(0.0, 0.0))
+            .arc(Point2::new(10.0, 0.0), 2.0, 0.0, 0.5)
+            .build();
+        let events = events(&path);
+
+        let PathEvent::Line { from, to } = events[1] else {
+            panic!("{:?} isn't a line", events[1]);
+        };
+        assert!(close_to(from, 0.0, 0.0));
+        assert!(close_to(to, 12.0, 0.0));
+    }
+
+    #[test]
+    fn close_returns_to_the_start_of_the_subpath() {
+        let path = VectorPath::builder()
+            .move_to(Point2::new(1.0, 1.0))
+            .line_to(Point2::new(5.0, 1.0))
+            .line_to(Point2::new(5.0, 5.0))
+            .close()
+            .line_to(Point2::new(9.0, 9.0))
+            .build();
+        let events = events(&path);
+
+        let closes: Vec<bool> = events
+            .iter()
+            .filter_map(|event| match event {
+                PathEvent::End { close, .. } => Some(*close),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(closes, [true, false]);
+
+        // a segment after close starts a new subpath where the closed one began
+        let begins: Vec<Point> = events
+            .iter()
+            .filter_map(|event| match event {
+                PathEvent::Begin { at } => Some(*at),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(begins, [point(1.0, 1.0), point(1.0, 1.0)]);
+    }
+
+    #[test]
+    fn rounded_rect_radii_are_clamped_to_the_rect() {
+        let path = VectorPath::rounded_rect(Point2::new(0.0, 0.0), 10.0, 4.0, 100.0);
+        let PathEvent::Begin { at } = events(&path)[0] else {
+            panic!("the path doesn't begin");
+        };
+        // the radius is clamped to half the height, where the top edge starts
+        assert!(close_to(at, 2.0, 0.0));
+        let (min, max) = bounds(&path);
+        assert!(close_to(min, 0.0, 0.0) && close_to(max, 10.0, 4.0));
+
+        let square = VectorPath::rounded_rect(Point2::new(0.0, 0.0), 10.0, 4.0, -3.0);
+        let PathEvent::Begin { at } = events(&square)[0] else {
+            panic!("the path doesn't begin");
+        };
+        assert!(close_to(at, 0.0, 0.0));
+        let (min, max) = bounds(&square);
+        assert!(close_to(min, 0.0, 0.0) && close_to(max, 10.0, 4.0));
+    }
+
+    #[test]
+    fn the_fill_fringe_lies_outside_the_outline() {
+        for clockwise in [true, false] {
+            let (vertices, indices) = square(clockwise).fill_mesh(Color::WHITE);
+            assert!(!indices.is_empty());
+
+            for vertex in vertices
+                .iter()
+                .filter(|vertex| vertex.antialias == [1.0, 1.0])
+            {
+                let [x, y] = vertex.position;
+                let outside = !(-0.5..=4.5).contains(&x) || !(-0.5..=4.5).contains(&y);
+                assert!(outside, "{:?} is inside the square", vertex.position);
+            }
+            // the fringe's inner edge and the fill meet on the outline
+            for vertex in vertices.iter().filter(|vertex| vertex.antialias[0] == 0.0) {
+                let [x, y] = vertex.position;
+                assert!((-0.01..=4.01).contains(&x) && (-0.01..=4.01).contains(&y));
+            }
+        }
+    }
+}
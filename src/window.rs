@@ -1,7 +1,10 @@
 use super::events::*;
 
+#[cfg(target_arch = "wasm32")]
 use web_sys::HtmlCanvasElement;
+#[cfg(target_arch = "wasm32")]
 use winit::platform::web::WindowBuilderExtWebSys;
+#[cfg(target_arch = "wasm32")]
 use wasm_bindgen::JsCast;
 
 pub struct Window {
@@ -10,16 +13,24 @@ pub struct Window {
 
 impl Window {
     pub fn create(event_loop: &EventLoop) -> Self {
-        let document = web_sys::window().unwrap().document().unwrap();
-        let canvas: HtmlCanvasElement = document.get_element_by_id("wgpuCanvas").unwrap().dyn_into().unwrap();
+        let builder = winit::window::WindowBuilder::new()
+            .with_title("A fantastic window!");
 
-        let subsystem_window = winit::window::WindowBuilder::new()
-            .with_title("A fantastic window!")
-            .with_canvas(Some(canvas))
-            .build(&event_loop.subsystem).unwrap();
+        // on the web the window draws into the page's canvas
+        #[cfg(target_arch = "wasm32")]
+        let builder = {
+            let document = web_sys::window().unwrap().document().unwrap();
+            let canvas: HtmlCanvasElement = document.get_element_by_id("wgpuCanvas").unwrap().dyn_into().unwrap();
+            builder.with_canvas(Some(canvas))
+        };
+
+        let subsystem_window = builder.build(&event_loop.subsystem).unwrap();
 
         Self {
             subsystem_window
         }
     }
-}
\ No newline at end of file
+    pub fn winit_window(&self) -> &winit::window::Window {
+        &self.subsystem_window
+    }
+}
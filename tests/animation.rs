@@ -0,0 +1,90 @@
+use ecliptic::renderer::{
+    animation::{AnimatedSprite, PlaybackMode},
+    golden::block_on,
+    PixelDimensions, Renderer,
+};
+
+// six 1x1 frames of a tenth of a second each, in a 3x2 grid
+fn animated_sprite() -> AnimatedSprite {
+    let renderer = block_on(Renderer::new_headless(PixelDimensions {
+        width: 1,
+        height: 1,
+    }));
+    let texture = renderer.create_texture(image::RgbaImage::new(3, 2));
+
+    AnimatedSprite::from_grid(
+        texture,
+        PixelDimensions {
+            width: 1,
+            height: 1,
+        },
+        6,
+        0.1,
+        PlaybackMode::Loop,
+    )
+}
+
+#[test]
+fn tags_switch_ranges_and_modes() {
+    let mut animation = animated_sprite();
+    animation.add_tag("walk", 0..3, PlaybackMode::Loop);
+    animation.add_tag("jump", 3..6, PlaybackMode::Once);
+
+    assert!(animation.play("walk"));
+    animation.update(0.25);
+    assert_eq!(animation.current_frame(), 2);
+
+    assert!(animation.play("jump"));
+    assert_eq!(animation.current_tag(), Some("jump"));
+    assert_eq!(animation.current_frame(), 3);
+    animation.update(1.0);
+    assert_eq!(animation.current_frame(), 5);
+    assert!(animation.is_finished());
+
+    assert!(!animation.play("missing"));
+    assert_eq!(animation.current_tag(), Some("jump"));
+}
+
+#[test]
+fn playing_the_current_tag_keeps_its_place_until_it_finishes() {
+    let mut animation = animated_sprite();
+    animation.add_tag("jump", 3..6, PlaybackMode::Once);
+
+    animation.play("jump");
+    animation.update(0.15);
+    animation.play("jump");
+    assert_eq!(animation.current_frame(), 4);
+
+    animation.update(1.0);
+    assert!(animation.is_finished());
+    animation.play("jump");
+    assert!(!animation.is_finished());
+    assert_eq!(animation.current_frame(), 3);
+}
+
+#[test]
+fn speed_scales_the_time_step() {
+    let mut animation = animated_sprite();
+
+    animation.speed = 2.0;
+    animation.update(0.1);
+    assert_eq!(animation.current_frame(), 2);
+
+    animation.speed = 0.5;
+    animation.update(0.1);
+    assert_eq!(animation.current_frame(), 2);
+    animation.update(0.1);
+    assert_eq!(animation.current_frame(), 3);
+}
+
+#[test]
+fn play_all_ignores_tags() {
+    let mut animation = animated_sprite();
+    animation.add_tag("walk", 0..3, PlaybackMode::Loop);
+    animation.play("walk");
+
+    animation.play_all(PlaybackMode::Loop);
+    assert_eq!(animation.current_tag(), None);
+    animation.update(0.55);
+    assert_eq!(animation.current_frame(), 5);
+}
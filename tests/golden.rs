@@ -0,0 +1,14 @@
+use ecliptic::renderer::golden::{scenes, GoldenHarness};
+
+// renders every built-in scene headless and compares it with its PNG in tests/golden; run with
+// ECLIPTIC_UPDATE_GOLDEN set to write the references instead
+#[test]
+fn golden_scenes_match_references() {
+    let failures: Vec<String> = GoldenHarness::default()
+        .run_all(&scenes())
+        .into_iter()
+        .filter_map(|(name, result)| result.err().map(|error| format!("{}: {}", name, error)))
+        .collect();
+
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
@@ -0,0 +1,36 @@
+use ecliptic::renderer::{golden::block_on, PixelDimensions, Renderer};
+
+fn renderer() -> Renderer {
+    block_on(Renderer::new_headless(PixelDimensions {
+        width: 4,
+        height: 4,
+    }))
+}
+
+#[test]
+fn valid_materials_are_created() {
+    let source = "
+        @fragment
+        fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+            return in.tint;
+        }
+    ";
+
+    assert!(renderer()
+        .create_material("tint", source, &[0.0f32; 4], &[])
+        .is_ok());
+}
+
+#[test]
+fn rejected_shaders_come_back_as_errors() {
+    let source = "
+        @fragment
+        fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+            return undeclared;
+        }
+    ";
+
+    assert!(renderer()
+        .create_material("broken", source, &[0.0f32; 4], &[])
+        .is_err());
+}
@@ -0,0 +1,36 @@
+use ecliptic::renderer::{golden::block_on, PixelDimensions, Renderer};
+
+fn renderer() -> Renderer {
+    block_on(Renderer::new_headless(PixelDimensions {
+        width: 4,
+        height: 4,
+    }))
+}
+
+#[test]
+fn valid_effects_are_created() {
+    let source = "
+        @fragment
+        fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+            return textureSampleLevel(t_source, s_source, in.tex_coords, 0.0);
+        }
+    ";
+
+    assert!(renderer()
+        .create_post_effect("copy", source, &[], None)
+        .is_ok());
+}
+
+#[test]
+fn rejected_shaders_come_back_as_errors() {
+    let source = "
+        @fragment
+        fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+            return undeclared;
+        }
+    ";
+
+    assert!(renderer()
+        .create_post_effect("broken", source, &[], None)
+        .is_err());
+}
@@ -0,0 +1,27 @@
+use ecliptic::renderer::{golden::block_on, Color, PixelCoordinates, PixelDimensions, Renderer};
+
+// without a Vulkan driver the headless renderer runs on GL, where map callbacks only fire from
+// inside device.poll; block_on parks until woken, so this hangs if the readback stops polling
+#[test]
+fn async_readback_matches_blocking_readback() {
+    let renderer = block_on(Renderer::new_headless(PixelDimensions {
+        width: 8,
+        height: 4,
+    }));
+    renderer.clear();
+    renderer.fill_rect(
+        PixelCoordinates { x: 2, y: 1 },
+        PixelDimensions {
+            width: 3,
+            height: 2,
+        },
+        Color::WHITE,
+    );
+
+    let blocking = renderer.read_pixels();
+    let asynchronous = block_on(renderer.read_pixels_async());
+
+    assert_eq!(asynchronous, blocking);
+    assert_eq!(asynchronous.get_pixel(3, 2).0, [255, 255, 255, 255]);
+    assert_ne!(asynchronous.get_pixel(0, 0).0, [255, 255, 255, 255]);
+}
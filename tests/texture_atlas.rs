@@ -0,0 +1,49 @@
+use ecliptic::renderer::{
+    golden::block_on,
+    texture_atlas::{TextureAtlasBuilder, TextureAtlasError},
+    PixelDimensions, Renderer,
+};
+
+fn renderer() -> Renderer {
+    block_on(Renderer::new_headless(PixelDimensions {
+        width: 4,
+        height: 4,
+    }))
+}
+
+#[test]
+fn pages_are_limited_to_the_device_texture_size() {
+    // far wider than any device allows, though the builder's pages would hold it
+    let mut builder = TextureAtlasBuilder::new(PixelDimensions {
+        width: 1 << 20,
+        height: 64,
+    });
+    builder.add_image("wide", image::RgbaImage::new(1 << 17, 1));
+
+    assert!(matches!(
+        renderer().create_texture_atlas(builder),
+        Err(TextureAtlasError::TooLarge { name, .. }) if name == "wide"
+    ));
+}
+
+#[test]
+fn atlas_textures_are_the_packed_pages() {
+    let mut builder = TextureAtlasBuilder::new(PixelDimensions {
+        width: 64,
+        height: 64,
+    });
+    builder.add_image("a", image::RgbaImage::new(8, 6));
+    builder.add_image("b", image::RgbaImage::new(4, 4));
+
+    let atlas = renderer().create_texture_atlas(builder).unwrap();
+
+    assert_eq!(atlas.textures.len(), 1);
+    assert_eq!(
+        atlas.textures[0].dimensions,
+        PixelDimensions {
+            width: 64,
+            height: 10,
+        }
+    );
+    assert_eq!(atlas.sprites.len(), 2);
+}